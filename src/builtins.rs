@@ -1,17 +1,71 @@
 mod arithmetic;
+mod bytevector;
 mod comparison;
 mod conditional;
+mod continuation;
 mod define;
+mod environment;
 mod equality;
+mod exception;
+mod file;
+mod input;
 mod lambda;
+mod library;
+mod load;
+mod output;
 mod pair;
+mod process;
+mod promise;
 mod quote;
+mod record;
+mod string_port;
+mod syntax;
+mod values;
 
+pub use self::process::set_command_line;
+pub use self::record::apply_record_procedure;
 use crate::evaluate::*;
 use crate::*;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+fn procedure_arg(procedure_name: &str, arg: &Expression) -> Result<Procedure, EvaluationError> {
+  match arg {
+    Expression::Procedure(procedure) => Ok(procedure.clone()),
+    non_procedure => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "procedure",
+      non_procedure,
+    )),
+  }
+}
+
+/// The result of something that was evaluated to completion on its own, like a loaded file,
+/// which passes anything it raised on to the handlers of the evaluation that started it
+fn nested_result(result: EvaluationResult) -> ProcedureResult {
+  match result {
+    Ok(value) => Ok(ProcedureValue::Expression(value)),
+    Err(EvaluationError::Raised(raised)) => Ok(ProcedureValue::Raise(*raised)),
+    Err(err) => Err(err),
+  }
+}
+
+/// A procedure taking no arguments that does something in Rust, like putting a port back, for
+/// the before and after thunks of a dynamic-wind
+fn rust_thunk(
+  procedure_name: &str,
+  thunk: impl Fn() -> Result<(), EvaluationError> + 'static,
+) -> Procedure {
+  Procedure::ClosureFixedArgumentForm(
+    procedure_name.to_string(),
+    BuiltinClosure(Rc::new(move |_| {
+      thunk()?;
+      Ok(void!())
+    })),
+    0,
+  )
+}
+
 fn define_builtin(scope: &mut Scope, procedure: Procedure) {
   match procedure {
     Procedure::BuiltinFixedArgumentForm(procedure_name, _, _) => {
@@ -20,6 +74,12 @@ fn define_builtin(scope: &mut Scope, procedure: Procedure) {
     Procedure::BuiltinVariableArgumentForm(procedure_name, _, _) => {
       scope.define(procedure_name, Expression::Procedure(procedure))
     }
+    Procedure::SpecialFixedArgumentForm(procedure_name, _, _) => {
+      scope.define(procedure_name, Expression::Procedure(procedure))
+    }
+    Procedure::SpecialVariableArgumentForm(procedure_name, _, _) => {
+      scope.define(procedure_name, Expression::Procedure(procedure))
+    }
     _ => panic!("Cannot define non-builtin procedure as builtin"),
   }
 }
@@ -44,4 +104,105 @@ pub fn define_builtins(scope: Rc<RefCell<Scope>>) {
   define_builtin(scope, define::DEFINE);
   define_builtin(scope, lambda::LAMBDA);
   define_builtin(scope, conditional::COND);
+  define_builtin(scope, syntax::SYNTAX_RULES);
+  define_builtin(scope, syntax::DEFINE_SYNTAX);
+  define_builtin(scope, syntax::LET_SYNTAX);
+  define_builtin(scope, syntax::LETREC_SYNTAX);
+  define_builtin(scope, syntax::MACROEXPAND_1);
+  define_builtin(scope, syntax::MACROEXPAND);
+  define_builtin(scope, continuation::CALL_WITH_CURRENT_CONTINUATION);
+  define_builtin(scope, continuation::CALL_CC);
+  define_builtin(scope, continuation::DYNAMIC_WIND);
+  define_builtin(scope, exception::ERROR);
+  define_builtin(scope, exception::RAISE);
+  define_builtin(scope, exception::RAISE_CONTINUABLE);
+  define_builtin(scope, exception::WITH_EXCEPTION_HANDLER);
+  define_builtin(scope, exception::GUARD);
+  define_builtin(scope, exception::ERROR_OBJECT);
+  define_builtin(scope, exception::ERROR_OBJECT_MESSAGE);
+  define_builtin(scope, exception::ERROR_OBJECT_IRRITANTS);
+  define_builtin(scope, values::VALUES);
+  define_builtin(scope, values::CALL_WITH_VALUES);
+  define_builtin(scope, values::LET_VALUES);
+  define_builtin(scope, values::LET_STAR_VALUES);
+  define_builtin(scope, values::DEFINE_VALUES);
+  define_builtin(scope, values::RECEIVE);
+  define_builtin(scope, arithmetic::FLOOR_DIVIDE);
+  define_builtin(scope, arithmetic::TRUNCATE_DIVIDE);
+  define_builtin(scope, arithmetic::EXACT_INTEGER_SQRT);
+  define_builtin(scope, promise::DELAY);
+  define_builtin(scope, promise::DELAY_FORCE);
+  define_builtin(scope, promise::MAKE_PROMISE);
+  define_builtin(scope, promise::IS_PROMISE);
+  define_builtin(scope, promise::FORCE);
+  define_builtin(scope, promise::CONS_STREAM);
+  define_builtin(scope, promise::STREAM_CAR);
+  define_builtin(scope, promise::STREAM_CDR);
+  define_builtin(scope, promise::IS_STREAM_PAIR);
+  define_builtin(scope, promise::IS_STREAM_NULL);
+  scope.define("the-empty-stream", null!());
+  define_builtin(scope, record::DEFINE_RECORD_TYPE);
+  define_builtin(scope, environment::EVAL);
+  define_builtin(scope, environment::INTERACTION_ENVIRONMENT);
+  define_builtin(scope, environment::SCHEME_REPORT_ENVIRONMENT);
+  define_builtin(scope, environment::NULL_ENVIRONMENT);
+  define_builtin(scope, environment::ENVIRONMENT_BOUND);
+  define_builtin(scope, environment::ENVIRONMENT_NAMES);
+  define_builtin(scope, load::LOAD);
+  define_builtin(scope, load::INCLUDE);
+  define_builtin(scope, library::DEFINE_LIBRARY);
+  define_builtin(scope, library::IMPORT);
+  define_builtin(scope, output::CURRENT_OUTPUT_PORT);
+  define_builtin(scope, output::CURRENT_ERROR_PORT);
+  define_builtin(scope, output::DISPLAY);
+  define_builtin(scope, output::WRITE);
+  define_builtin(scope, output::WRITE_STRING);
+  define_builtin(scope, output::WRITE_CHAR);
+  define_builtin(scope, output::NEWLINE);
+  define_builtin(scope, output::FLUSH_OUTPUT_PORT);
+  define_builtin(scope, input::CURRENT_INPUT_PORT);
+  define_builtin(scope, input::READ_CHAR);
+  define_builtin(scope, input::PEEK_CHAR);
+  define_builtin(scope, input::READ_LINE);
+  define_builtin(scope, input::READ_STRING);
+  define_builtin(scope, input::CHAR_READY);
+  define_builtin(scope, input::READ);
+  define_builtin(scope, input::EOF_OBJECT);
+  define_builtin(scope, input::IS_EOF_OBJECT);
+  define_builtin(scope, bytevector::IS_BYTEVECTOR);
+  define_builtin(scope, bytevector::MAKE_BYTEVECTOR);
+  define_builtin(scope, bytevector::BYTEVECTOR);
+  define_builtin(scope, bytevector::BYTEVECTOR_LENGTH);
+  define_builtin(scope, bytevector::BYTEVECTOR_U8_REF);
+  define_builtin(scope, bytevector::BYTEVECTOR_U8_SET);
+  define_builtin(scope, bytevector::UTF8_TO_STRING);
+  define_builtin(scope, bytevector::STRING_TO_UTF8);
+  define_builtin(scope, input::READ_U8);
+  define_builtin(scope, input::PEEK_U8);
+  define_builtin(scope, input::U8_READY);
+  define_builtin(scope, input::READ_BYTEVECTOR);
+  define_builtin(scope, output::WRITE_U8);
+  define_builtin(scope, output::WRITE_BYTEVECTOR);
+  define_builtin(scope, file::OPEN_INPUT_FILE);
+  define_builtin(scope, file::OPEN_BINARY_INPUT_FILE);
+  define_builtin(scope, file::OPEN_OUTPUT_FILE);
+  define_builtin(scope, file::OPEN_BINARY_OUTPUT_FILE);
+  define_builtin(scope, file::CLOSE_PORT);
+  define_builtin(scope, file::CLOSE_INPUT_PORT);
+  define_builtin(scope, file::CLOSE_OUTPUT_PORT);
+  define_builtin(scope, file::CALL_WITH_INPUT_FILE);
+  define_builtin(scope, file::CALL_WITH_OUTPUT_FILE);
+  define_builtin(scope, file::WITH_INPUT_FROM_FILE);
+  define_builtin(scope, file::WITH_OUTPUT_TO_FILE);
+  define_builtin(scope, file::FILE_EXISTS);
+  define_builtin(scope, file::DELETE_FILE);
+  define_builtin(scope, string_port::OPEN_INPUT_STRING);
+  define_builtin(scope, string_port::OPEN_OUTPUT_STRING);
+  define_builtin(scope, string_port::GET_OUTPUT_STRING);
+  define_builtin(scope, string_port::OPEN_INPUT_BYTEVECTOR);
+  define_builtin(scope, string_port::OPEN_OUTPUT_BYTEVECTOR);
+  define_builtin(scope, string_port::GET_OUTPUT_BYTEVECTOR);
+  define_builtin(scope, string_port::WITH_OUTPUT_TO_STRING);
+  define_builtin(scope, process::COMMAND_LINE);
+  define_builtin(scope, process::EXIT);
 }
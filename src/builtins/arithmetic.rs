@@ -5,11 +5,11 @@ use std::rc::Rc;
 fn _add(
   _args: Vec<Expression>,
   varargs: Vec<Expression>,
-  scope: Rc<RefCell<Scope>>,
+  _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-  return varargs
-    .iter()
-    .map(|expression| match evaluate(expression, scope.clone())? {
+  varargs
+    .into_iter()
+    .map(|expression| match expression {
       Expression::Number(Number::Integer(number)) => Ok(number),
       non_number => Err(EvaluationError::invalid_argument(
         "+",
@@ -18,9 +18,8 @@ fn _add(
       )),
     })
     .reduce(|left, right| Ok(left? + right?))
-    .or(Some(Ok(0)))
-    .unwrap()
-    .map(|sum| ProcedureValue::Expression(Expression::Number(Number::Integer(sum))));
+    .unwrap_or(Ok(0))
+    .map(|sum| ProcedureValue::Expression(Expression::Number(Number::Integer(sum))))
 }
 
 pub const ADD: Procedure = Procedure::BuiltinVariableArgumentForm("+", _add, 0);
@@ -28,11 +27,11 @@ pub const ADD: Procedure = Procedure::BuiltinVariableArgumentForm("+", _add, 0);
 pub fn _multiply(
   _args: Vec<Expression>,
   varargs: Vec<Expression>,
-  scope: Rc<RefCell<Scope>>,
+  _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-  return varargs
-    .iter()
-    .map(|expression| match evaluate(expression, scope.clone())? {
+  varargs
+    .into_iter()
+    .map(|expression| match expression {
       Expression::Number(Number::Integer(number)) => Ok(number),
       non_number => Err(EvaluationError::invalid_argument(
         "*",
@@ -41,9 +40,8 @@ pub fn _multiply(
       )),
     })
     .reduce(|left, right| Ok(left? * right?))
-    .or(Some(Ok(1)))
-    .unwrap()
-    .map(|sum| ProcedureValue::Expression(Expression::Number(Number::Integer(sum))));
+    .unwrap_or(Ok(1))
+    .map(|sum| ProcedureValue::Expression(Expression::Number(Number::Integer(sum))))
 }
 
 pub const MULTIPLY: Procedure = Procedure::BuiltinVariableArgumentForm("*", _multiply, 0);
@@ -51,15 +49,14 @@ pub const MULTIPLY: Procedure = Procedure::BuiltinVariableArgumentForm("*", _mul
 pub fn _subtract(
   args: Vec<Expression>,
   varargs: Vec<Expression>,
-  scope: Rc<RefCell<Scope>>,
+  _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-  let subtraction = evaluate(args.get(0).unwrap(), scope.clone())?;
+  let subtraction = args.first().unwrap().clone();
   if let Expression::Number(Number::Integer(mut subtraction)) = subtraction {
     if varargs.is_empty() {
       Ok(ProcedureValue::Expression(int!(-subtraction)))
     } else {
-      for arg in varargs {
-        let term = evaluate(&arg, scope.clone())?;
+      for term in varargs {
         if let Expression::Number(Number::Integer(integer)) = term {
           subtraction -= integer;
         } else {
@@ -82,9 +79,9 @@ pub const SUBTRACT: Procedure = Procedure::BuiltinVariableArgumentForm("-", _sub
 pub fn _divide(
   args: Vec<Expression>,
   varargs: Vec<Expression>,
-  scope: Rc<RefCell<Scope>>,
+  _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-  let quotient = evaluate(args.get(0).unwrap(), scope.clone())?;
+  let quotient = args.first().unwrap().clone();
   if let Expression::Number(Number::Integer(mut quotient)) = quotient {
     if varargs.is_empty() {
       if quotient == 0 {
@@ -93,8 +90,7 @@ pub fn _divide(
         Ok(ProcedureValue::Expression(int!(1 / quotient)))
       }
     } else {
-      for arg in varargs {
-        let term = evaluate(&arg, scope.clone())?;
+      for term in varargs {
         if let Expression::Number(Number::Integer(integer)) = term {
           if integer == 0 {
             return Err(EvaluationError::DivideByZero(Number::Integer(quotient)));
@@ -113,6 +109,82 @@ pub fn _divide(
 
 pub const DIVIDE: Procedure = Procedure::BuiltinVariableArgumentForm("/", _divide, 1);
 
+fn integer_arg(procedure_name: &str, arg: &Expression) -> Result<i32, EvaluationError> {
+  match arg {
+    Expression::Number(Number::Integer(integer)) => Ok(*integer),
+    non_number => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "number",
+      non_number,
+    )),
+  }
+}
+
+/// The dividend and divisor of an integer division, which can't be zero
+fn division_args(procedure_name: &str, args: &[Expression]) -> Result<(i32, i32), EvaluationError> {
+  let dividend = integer_arg(procedure_name, args.first().unwrap())?;
+  let divisor = integer_arg(procedure_name, args.get(1).unwrap())?;
+  if divisor == 0 {
+    return Err(EvaluationError::DivideByZero(Number::Integer(dividend)));
+  }
+  Ok((dividend, divisor))
+}
+
+fn _floor_divide(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let (dividend, divisor) = division_args("floor/", &args)?;
+  let mut quotient = dividend / divisor;
+  // Division truncates towards zero, so round down when the result is negative
+  if dividend % divisor != 0 && (dividend < 0) != (divisor < 0) {
+    quotient -= 1;
+  }
+  Ok(ProcedureValue::Expression(Expression::Values(vec![
+    int!(quotient),
+    int!(dividend - quotient * divisor),
+  ])))
+}
+
+pub const FLOOR_DIVIDE: Procedure = Procedure::BuiltinFixedArgumentForm("floor/", _floor_divide, 2);
+
+fn _truncate_divide(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let (dividend, divisor) = division_args("truncate/", &args)?;
+  Ok(ProcedureValue::Expression(Expression::Values(vec![
+    int!(dividend / divisor),
+    int!(dividend % divisor),
+  ])))
+}
+
+pub const TRUNCATE_DIVIDE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("truncate/", _truncate_divide, 2);
+
+fn _exact_integer_sqrt(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let arg = args.first().unwrap();
+  let number = integer_arg("exact-integer-sqrt", arg)?;
+  if number < 0 {
+    return Err(EvaluationError::invalid_argument(
+      "exact-integer-sqrt",
+      "non-negative number",
+      arg,
+    ));
+  }
+  // Widen so that squaring a root just past the answer can't overflow
+  let number = number as i64;
+  let mut root = (number as f64).sqrt() as i64;
+  // Correct any rounding in the floating point square root
+  while root * root > number {
+    root -= 1;
+  }
+  while (root + 1) * (root + 1) <= number {
+    root += 1;
+  }
+  Ok(ProcedureValue::Expression(Expression::Values(vec![
+    int!(root as i32),
+    int!((number - root * root) as i32),
+  ])))
+}
+
+pub const EXACT_INTEGER_SQRT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("exact-integer-sqrt", _exact_integer_sqrt, 1);
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -220,4 +292,74 @@ mod test {
       EvaluationError::invalid_argument("/", "list", &cons!(&int!(1), &int!(2))),
     );
   }
+
+  #[test]
+  fn test_evaluate_floor_divide() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(floor/ 7 2)", Expression::Values(vec![int!(3), int!(1)]));
+    ctx.assert_eq("(floor/ -7 2)", Expression::Values(vec![int!(-4), int!(1)]));
+    ctx.assert_eq(
+      "(floor/ 7 -2)",
+      Expression::Values(vec![int!(-4), int!(-1)]),
+    );
+    ctx.assert_eq(
+      "(floor/ -7 -2)",
+      Expression::Values(vec![int!(3), int!(-1)]),
+    );
+    ctx.assert_eq("(floor/ -6 2)", Expression::Values(vec![int!(-3), int!(0)]));
+    ctx.assert_err(
+      "(floor/ 3 0)",
+      EvaluationError::DivideByZero(Number::Integer(3)),
+    );
+    ctx.assert_err(
+      "(floor/ 3 ())",
+      EvaluationError::invalid_argument("floor/", "number", &null!()),
+    );
+  }
+
+  #[test]
+  fn test_evaluate_truncate_divide() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(truncate/ 7 2)",
+      Expression::Values(vec![int!(3), int!(1)]),
+    );
+    ctx.assert_eq(
+      "(truncate/ -7 2)",
+      Expression::Values(vec![int!(-3), int!(-1)]),
+    );
+    ctx.assert_eq(
+      "(truncate/ 7 -2)",
+      Expression::Values(vec![int!(-3), int!(1)]),
+    );
+    ctx.assert_err(
+      "(truncate/ 3 0)",
+      EvaluationError::DivideByZero(Number::Integer(3)),
+    );
+  }
+
+  #[test]
+  fn test_evaluate_exact_integer_sqrt() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(exact-integer-sqrt 0)",
+      Expression::Values(vec![int!(0), int!(0)]),
+    );
+    ctx.assert_eq(
+      "(exact-integer-sqrt 4)",
+      Expression::Values(vec![int!(2), int!(0)]),
+    );
+    ctx.assert_eq(
+      "(exact-integer-sqrt 17)",
+      Expression::Values(vec![int!(4), int!(1)]),
+    );
+    ctx.assert_eq(
+      "(exact-integer-sqrt 2147395600)",
+      Expression::Values(vec![int!(46340), int!(0)]),
+    );
+    ctx.assert_err(
+      "(exact-integer-sqrt -1)",
+      EvaluationError::invalid_argument("exact-integer-sqrt", "non-negative number", &int!(-1)),
+    );
+  }
 }
@@ -0,0 +1,200 @@
+use super::*;
+
+pub fn bytevector_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<Rc<RefCell<Vec<u8>>>, EvaluationError> {
+  match arg {
+    Expression::Bytevector(bytes) => Ok(bytes.clone()),
+    non_bytevector => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "bytevector",
+      non_bytevector,
+    )),
+  }
+}
+
+pub fn byte_arg(procedure_name: &str, arg: &Expression) -> Result<u8, EvaluationError> {
+  match arg {
+    Expression::Number(Number::Integer(byte)) if (0..=255).contains(byte) => Ok(*byte as u8),
+    non_byte => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "byte",
+      non_byte,
+    )),
+  }
+}
+
+/// An index into a bytevector, which has to be inside it
+fn index_arg(
+  procedure_name: &str,
+  arg: &Expression,
+  bytes: &[u8],
+) -> Result<usize, EvaluationError> {
+  match arg {
+    Expression::Number(Number::Integer(index))
+      if *index >= 0 && (*index as usize) < bytes.len() =>
+    {
+      Ok(*index as usize)
+    }
+    non_index => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "index",
+      non_index,
+    )),
+  }
+}
+
+fn _is_bytevector(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(matches!(
+    args.first().unwrap(),
+    Expression::Bytevector(_)
+  ))))
+}
+pub const IS_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("bytevector?", _is_bytevector, 1);
+
+fn _make_bytevector(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let length = match args.first().unwrap() {
+    Expression::Number(Number::Integer(length)) if *length >= 0 => *length as usize,
+    non_length => {
+      return Err(EvaluationError::invalid_argument(
+        "make-bytevector",
+        "non-negative integer",
+        non_length,
+      ))
+    }
+  };
+  let fill = match varargs.as_slice() {
+    [] => 0,
+    [fill] => byte_arg("make-bytevector", fill)?,
+    _ => {
+      return Err(EvaluationError::WrongNumberOfArguments(
+        "make-bytevector".to_string(),
+        2,
+        varargs.len() + 1,
+      ))
+    }
+  };
+  Ok(ProcedureValue::Expression(bytevector!(vec![fill; length])))
+}
+pub const MAKE_BYTEVECTOR: Procedure =
+  Procedure::BuiltinVariableArgumentForm("make-bytevector", _make_bytevector, 1);
+
+fn _bytevector(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let bytes = varargs
+    .iter()
+    .map(|byte| byte_arg("bytevector", byte))
+    .collect::<Result<_, _>>()?;
+  Ok(ProcedureValue::Expression(bytevector!(bytes)))
+}
+pub const BYTEVECTOR: Procedure =
+  Procedure::BuiltinVariableArgumentForm("bytevector", _bytevector, 0);
+
+fn _bytevector_length(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let bytes = bytevector_arg("bytevector-length", args.first().unwrap())?;
+  let length = bytes.borrow().len();
+  Ok(ProcedureValue::Expression(int!(length as i32)))
+}
+pub const BYTEVECTOR_LENGTH: Procedure =
+  Procedure::BuiltinFixedArgumentForm("bytevector-length", _bytevector_length, 1);
+
+fn _bytevector_u8_ref(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let bytes = bytevector_arg("bytevector-u8-ref", args.first().unwrap())?;
+  let bytes = bytes.borrow();
+  let index = index_arg("bytevector-u8-ref", args.get(1).unwrap(), &bytes)?;
+  Ok(ProcedureValue::Expression(int!(bytes[index] as i32)))
+}
+pub const BYTEVECTOR_U8_REF: Procedure =
+  Procedure::BuiltinFixedArgumentForm("bytevector-u8-ref", _bytevector_u8_ref, 2);
+
+fn _bytevector_u8_set(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let bytes = bytevector_arg("bytevector-u8-set!", args.first().unwrap())?;
+  let mut bytes = bytes.borrow_mut();
+  let index = index_arg("bytevector-u8-set!", args.get(1).unwrap(), &bytes)?;
+  bytes[index] = byte_arg("bytevector-u8-set!", args.get(2).unwrap())?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const BYTEVECTOR_U8_SET: Procedure =
+  Procedure::BuiltinFixedArgumentForm("bytevector-u8-set!", _bytevector_u8_set, 3);
+
+fn _utf8_to_string(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let arg = args.first().unwrap();
+  let bytes = bytevector_arg("utf8->string", arg)?;
+  let string = String::from_utf8(bytes.borrow().clone())
+    .map_err(|_| EvaluationError::invalid_argument("utf8->string", "UTF-8 bytevector", arg))?;
+  Ok(ProcedureValue::Expression(Expression::String(string)))
+}
+pub const UTF8_TO_STRING: Procedure =
+  Procedure::BuiltinFixedArgumentForm("utf8->string", _utf8_to_string, 1);
+
+fn _string_to_utf8(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  match args.first().unwrap() {
+    Expression::String(string) => Ok(ProcedureValue::Expression(bytevector!(string
+      .as_bytes()
+      .to_vec()))),
+    non_string => Err(EvaluationError::invalid_argument(
+      "string->utf8",
+      "string",
+      non_string,
+    )),
+  }
+}
+pub const STRING_TO_UTF8: Procedure =
+  Procedure::BuiltinFixedArgumentForm("string->utf8", _string_to_utf8, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_bytevector() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(bytevector 1 2 3)", bytevector!(vec![1, 2, 3]));
+    ctx.assert_eq("(make-bytevector 2 7)", bytevector!(vec![7, 7]));
+    ctx.assert_eq("(make-bytevector 2)", bytevector!(vec![0, 0]));
+    ctx.assert_eq("(bytevector? #u8(1))", boolean!(true));
+    ctx.assert_eq("(bytevector? '(1))", boolean!(false));
+    ctx.exec("(define bytes #u8(1 2 3))");
+    ctx.assert_eq("(bytevector-length bytes)", int!(3));
+    ctx.assert_eq("(bytevector-u8-ref bytes 1)", int!(2));
+    ctx.exec("(bytevector-u8-set! bytes 1 200)");
+    ctx.assert_eq("bytes", bytevector!(vec![1, 200, 3]));
+    ctx.assert_eq("(string->utf8 \"λ\")", bytevector!(vec![206, 187]));
+    ctx.assert_eq("(utf8->string #u8(104 105))", string!("hi"));
+  }
+
+  #[test]
+  fn test_bytevector_errors() {
+    let ctx = TestContext::new();
+    ctx.assert_err(
+      "(bytevector 256)",
+      EvaluationError::invalid_argument("bytevector", "byte", &int!(256)),
+    );
+    ctx.assert_err(
+      "(bytevector-u8-ref #u8(1) 1)",
+      EvaluationError::invalid_argument("bytevector-u8-ref", "index", &int!(1)),
+    );
+    ctx.assert_err(
+      "(bytevector-length \"a\")",
+      EvaluationError::invalid_argument("bytevector-length", "bytevector", &string!("a")),
+    );
+    ctx.assert_err(
+      "(utf8->string #u8(255))",
+      EvaluationError::invalid_argument(
+        "utf8->string",
+        "UTF-8 bytevector",
+        &bytevector!(vec![255]),
+      ),
+    );
+  }
+}
@@ -1,15 +1,15 @@
 use super::*;
 
 macro_rules! comparison_operator {
-    ($operator_name:expr, $comparison:tt, $args:expr, $varargs:expr, $scope:expr) => {{
+    ($operator_name:expr, $comparison:tt, $args:expr, $varargs:expr) => {{
         // Evaluate the arguments and verify that all results are numbers
         // fn to_number(expression: &Expression) -> Result<Number, EvaluationError> {
         // }
-        let to_number = |expression| match evaluate(expression, $scope.clone())? {
-            Expression::Number(number) => Ok(number),
-            non_number => Err(EvaluationError::invalid_argument($operator_name, "number", &non_number)),
+        let to_number = |expression: &Expression| match expression {
+            Expression::Number(number) => Ok(number.clone()),
+            non_number => Err(EvaluationError::invalid_argument($operator_name, "number", non_number)),
         };
-        let mut previous_arg = &to_number($args.get(0).unwrap())?;
+        let mut previous_arg = &to_number($args.first().unwrap())?;
         let varargs = $varargs
             .iter()
             .map(to_number)
@@ -27,36 +27,36 @@ macro_rules! comparison_operator {
 fn _equals(
     args: Vec<Expression>,
     varargs: Vec<Expression>,
-    scope: Rc<RefCell<Scope>>,
+    _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-    comparison_operator!("=", ==, args, varargs, scope)
+    comparison_operator!("=", ==, args, varargs)
 }
 pub const EQUALS: Procedure = Procedure::BuiltinVariableArgumentForm("=", _equals, 1);
 
 fn _less_than(
     args: Vec<Expression>,
     varargs: Vec<Expression>,
-    scope: Rc<RefCell<Scope>>,
+    _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-    comparison_operator!("<", <, args, varargs, scope)
+    comparison_operator!("<", <, args, varargs)
 }
 pub const LESS_THAN: Procedure = Procedure::BuiltinVariableArgumentForm("<", _less_than, 1);
 
 fn _greater_than(
     args: Vec<Expression>,
     varargs: Vec<Expression>,
-    scope: Rc<RefCell<Scope>>,
+    _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-    comparison_operator!(">",>, args, varargs, scope)
+    comparison_operator!(">",>, args, varargs)
 }
 pub const GREATER_THAN: Procedure = Procedure::BuiltinVariableArgumentForm(">", _greater_than, 1);
 
 fn _less_than_or_equal(
     args: Vec<Expression>,
     varargs: Vec<Expression>,
-    scope: Rc<RefCell<Scope>>,
+    _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-    comparison_operator!("<=",<=, args, varargs, scope)
+    comparison_operator!("<=",<=, args, varargs)
 }
 pub const LESS_THAN_OR_EQUAL: Procedure =
     Procedure::BuiltinVariableArgumentForm("<=", _less_than_or_equal, 1);
@@ -64,9 +64,9 @@ pub const LESS_THAN_OR_EQUAL: Procedure =
 fn _greater_than_or_equal(
     args: Vec<Expression>,
     varargs: Vec<Expression>,
-    scope: Rc<RefCell<Scope>>,
+    _scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-    comparison_operator!(">=", >=, args, varargs, scope)
+    comparison_operator!(">=", >=, args, varargs)
 }
 pub const GREATER_THAN_OR_EQUAL: Procedure =
     Procedure::BuiltinVariableArgumentForm(">=", _greater_than_or_equal, 1);
@@ -20,7 +20,7 @@ fn _cond(
   // The final else clause (if it exists) needs to be handled specially
   let else_clause = if let Some(last_clause) = varargs.pop() {
     let last_clause_vec = arg_vec("cond", &last_clause)?;
-    if last_clause_vec.get(0) == Some(&symbol!("else")) {
+    if last_clause_vec.first() == Some(&symbol!("else")) {
       if last_clause_vec.len() < 2 {
         // the else clause needs at least one expression
         return Err(EvaluationError::invalid_argument(
@@ -38,42 +38,63 @@ fn _cond(
   } else {
     None
   };
-  for clause in varargs {
-    let clause = arg_vec("cond", &clause)?;
-    // This was verified as the first step of this function
-    let test = clause.get(0).unwrap();
-    let test = evaluate(test, scope.clone())?;
-    if test != boolean!(false) {
-      let mut expression = test;
-      let expressions = if clause.get(1).unwrap_or(&void!()) == &symbol!("=>") {
-        // case clauses may be of the form (test => expression ...)
-        // If so, skip the test and the =>
-        clause[2..].iter()
-      } else {
-        // case clauses may also be of the form (test expression ...)
-        // If so, skip the test since we have already evaluated it
-        clause[1..].iter()
-      };
-      for line in expressions {
-        expression = evaluate(line, scope.clone())?;
+  let clauses = varargs
+    .iter()
+    .map(|clause| arg_vec("cond", clause))
+    .collect::<Result<Vec<Vec<Expression>>, EvaluationError>>()?;
+  let else_body = match else_clause {
+    Some(else_clause) => Some(arg_vec("cond", &else_clause)?[1..].to_vec()),
+    None => None,
+  };
+  evaluate_clauses(Rc::new(clauses), 0, Rc::new(else_body), scope)
+}
+
+/// Evaluate the tests of the clauses starting at index until one of them passes
+fn evaluate_clauses(
+  clauses: Rc<Vec<Vec<Expression>>>,
+  index: usize,
+  else_body: Rc<Option<Vec<Expression>>>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let clause = match clauses.get(index) {
+    Some(clause) => clause,
+    None => {
+      return match else_body.as_ref() {
+        Some(else_body) => _evaluate_procedure_body(else_body, scope),
+        None => Ok(ProcedureValue::Expression(void!())),
       }
-      return Ok(ProcedureValue::Expression(expression));
     }
-  }
-  if let Some(else_clause) = else_clause {
-    let else_clause = arg_vec("cond", &else_clause)?;
-    let mut else_body = else_clause[1..].iter();
-    let mut expression = evaluate_in_tail_position(else_body.next().unwrap(), scope.clone())?;
-    for next_expression in else_body {
-      expression = evaluate_in_tail_position(next_expression, scope.clone())?;
-    }
-    Ok(expression)
-  } else {
-    Ok(ProcedureValue::Expression(void!()))
-  }
+  };
+  // This was verified as the first step of _cond
+  let test = clause.first().unwrap().clone();
+  Ok(ProcedureValue::Evaluate(
+    test,
+    scope.clone(),
+    Continue::new(move |test| {
+      if test != boolean!(false) {
+        let clause = &clauses[index];
+        let expressions = if clause.get(1).unwrap_or(&void!()) == &symbol!("=>") {
+          // case clauses may be of the form (test => expression ...)
+          // If so, skip the test and the =>
+          &clause[2..]
+        } else {
+          // case clauses may also be of the form (test expression ...)
+          // If so, skip the test since we have already evaluated it
+          &clause[1..]
+        };
+        if expressions.is_empty() {
+          Ok(ProcedureValue::Expression(test))
+        } else {
+          _evaluate_procedure_body(expressions, scope.clone())
+        }
+      } else {
+        evaluate_clauses(clauses.clone(), index + 1, else_body.clone(), scope.clone())
+      }
+    }),
+  ))
 }
 
-pub const COND: Procedure = Procedure::BuiltinVariableArgumentForm("cond", _cond, 0);
+pub const COND: Procedure = Procedure::SpecialVariableArgumentForm("cond", _cond, 0);
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,210 @@
+use super::*;
+
+fn _call_with_current_continuation(
+  args: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let procedure = procedure_arg("call-with-current-continuation", args.first().unwrap())?;
+  Ok(ProcedureValue::CallWithCurrentContinuation(
+    procedure, scope,
+  ))
+}
+pub const CALL_WITH_CURRENT_CONTINUATION: Procedure = Procedure::BuiltinFixedArgumentForm(
+  "call-with-current-continuation",
+  _call_with_current_continuation,
+  1,
+);
+pub const CALL_CC: Procedure =
+  Procedure::BuiltinFixedArgumentForm("call/cc", _call_with_current_continuation, 1);
+
+fn _dynamic_wind(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let before = procedure_arg("dynamic-wind", args.first().unwrap())?;
+  let thunk = procedure_arg("dynamic-wind", args.get(1).unwrap())?;
+  let after = procedure_arg("dynamic-wind", args.get(2).unwrap())?;
+  Ok(ProcedureValue::DynamicWind(
+    before,
+    Box::new(ProcedureValue::TailApply(thunk, vec![], scope.clone())),
+    after,
+    scope,
+  ))
+}
+pub const DYNAMIC_WIND: Procedure =
+  Procedure::BuiltinFixedArgumentForm("dynamic-wind", _dynamic_wind, 3);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  thread_local! {
+    static TRACE: RefCell<Vec<Expression>> = const { RefCell::new(vec![]) };
+  }
+
+  /// Record the order that dynamic-wind thunks are called in
+  fn _trace(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+    TRACE.with(|trace| trace.borrow_mut().push(args[0].clone()));
+    Ok(ProcedureValue::Expression(args[0].clone()))
+  }
+
+  fn trace_context() -> TestContext {
+    TRACE.with(|trace| trace.borrow_mut().clear());
+    let ctx = TestContext::new();
+    ctx.scope.borrow_mut().define(
+      "trace",
+      Expression::Procedure(Procedure::BuiltinFixedArgumentForm("trace", _trace, 1)),
+    );
+    ctx
+  }
+
+  fn assert_trace(expected: Expression) {
+    TRACE.with(|trace| assert_eq!(vec_arg(trace.borrow().clone()), Ok(expected)));
+  }
+
+  #[test]
+  fn test_call_cc_escape() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(call/cc (lambda (k) 1))", int!(1));
+    ctx.assert_eq("(call/cc (lambda (k) (k 1) 2))", int!(1));
+    ctx.assert_eq("(+ 1 (call/cc (lambda (k) (+ 10 (k 1)))))", int!(2));
+    ctx.assert_eq(
+      "(+ 1 (call-with-current-continuation (lambda (k) (+ 10 (k 1)))))",
+      int!(2),
+    );
+    ctx.exec(
+      "
+(define find-first (lambda (predicate items)
+  (call/cc (lambda (return)
+    ((lambda (loop) (loop loop items))
+      (lambda (loop items)
+        (cond
+          ((eq? items '()) #f)
+          ((predicate (car items)) (return (car items)))
+          (else (loop loop (cdr items))))))))))",
+    );
+    ctx.assert_eq("(find-first (lambda (x) (> x 2)) '(1 2 3 4))", int!(3));
+    ctx.assert_eq(
+      "(find-first (lambda (x) (> x 5)) '(1 2 3 4))",
+      boolean!(false),
+    );
+  }
+
+  #[test]
+  fn test_call_cc_reentry() {
+    let ctx = TestContext::new();
+    // Re-entering the continuation runs the define again with the new value
+    ctx.exec("(define k (call/cc (lambda (k) k)))");
+    ctx.assert_eq("(k 5)", void!());
+    ctx.assert_eq("k", int!(5));
+    ctx.assert_eq(
+      "
+((lambda ()
+  (define k (call/cc (lambda (k) k)))
+  (cond ((eq? k 5) (+ k 1)) (else (k 5)))))",
+      int!(6),
+    );
+    // The continuation of a closure's body still sees the closure's bindings when re-entered
+    ctx.exec(
+      "
+(define count-up (lambda (n)
+  (define k (call/cc (lambda (k) (cons 0 k))))
+  (cond
+    ((eq? (car k) n) (car k))
+    (else ((cdr k) (cons (+ (car k) 1) (cdr k)))))))",
+    );
+    ctx.assert_eq("(count-up 10)", int!(10));
+  }
+
+  #[test]
+  fn test_call_cc_errors() {
+    let ctx = TestContext::new();
+    ctx.assert_err(
+      "(call/cc 1)",
+      EvaluationError::invalid_argument("call-with-current-continuation", "procedure", &int!(1)),
+    );
+    ctx.assert_err(
+      "(call/cc (lambda () 1))",
+      EvaluationError::WrongNumberOfArguments("#<procedure>".to_string(), 0, 1),
+    );
+    // Continuations accept any number of values
+    ctx.assert_eq(
+      "(call/cc (lambda (k) (k 1 2)))",
+      Expression::Values(vec![int!(1), int!(2)]),
+    );
+  }
+
+  #[test]
+  fn test_dynamic_wind() {
+    let ctx = trace_context();
+    ctx.assert_eq(
+      "(dynamic-wind (lambda () (trace 'before)) (lambda () (trace 'during)) (lambda () (trace 'after)))",
+      symbol!("during"),
+    );
+    assert_trace(list!(
+      symbol!("before"),
+      symbol!("during"),
+      symbol!("after")
+    ));
+    ctx.assert_err(
+      "(dynamic-wind 1 2 3)",
+      EvaluationError::invalid_argument("dynamic-wind", "procedure", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_dynamic_wind_escape() {
+    let ctx = trace_context();
+    ctx.assert_eq(
+      "
+(call/cc (lambda (k)
+  (dynamic-wind
+    (lambda () (trace 'before))
+    (lambda () (k 'escaped) (trace 'unreachable))
+    (lambda () (trace 'after)))))",
+      symbol!("escaped"),
+    );
+    assert_trace(list!(symbol!("before"), symbol!("after")));
+  }
+
+  #[test]
+  fn test_dynamic_wind_reentry() {
+    let ctx = trace_context();
+    ctx.exec(
+      "
+(define k
+  (dynamic-wind
+    (lambda () (trace 'before))
+    (lambda () (call/cc (lambda (k) k)))
+    (lambda () (trace 'after))))",
+    );
+    ctx.exec(
+      "
+(dynamic-wind
+  (lambda () (trace 'outer-before))
+  (lambda () (k 'reentered))
+  (lambda () (trace 'outer-after)))",
+    );
+    ctx.assert_eq("k", symbol!("reentered"));
+    assert_trace(list!(
+      symbol!("before"),
+      symbol!("after"),
+      symbol!("outer-before"),
+      symbol!("outer-after"),
+      symbol!("before"),
+      symbol!("after")
+    ));
+  }
+
+  #[test]
+  fn test_dynamic_wind_error() {
+    let ctx = trace_context();
+    ctx.assert_err(
+      "
+(dynamic-wind
+  (lambda () (trace 'before))
+  (lambda () (car 1))
+  (lambda () (trace 'after)))",
+      EvaluationError::invalid_argument("car", "list", &int!(1)),
+    );
+    assert_trace(list!(symbol!("before"), symbol!("after")));
+  }
+}
@@ -1,18 +1,24 @@
 use super::*;
 
 fn _define(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  let symbol = args.get(0).unwrap();
-  let expression = evaluate(args.get(1).unwrap(), scope.clone())?;
+  let symbol = args.first().unwrap();
   if let Expression::Symbol(symbol) = symbol {
-    scope.borrow_mut().define(symbol, expression);
-    Ok(ProcedureValue::Expression(void!()))
+    let symbol = symbol.clone();
+    Ok(ProcedureValue::Evaluate(
+      args.get(1).unwrap().clone(),
+      scope.clone(),
+      Continue::new(move |expression| {
+        scope.borrow_mut().define(&symbol, expression);
+        Ok(ProcedureValue::Expression(void!()))
+      }),
+    ))
   } else {
     Err(EvaluationError::invalid_argument(
       "define", "symbol", symbol,
     ))
   }
 }
-pub const DEFINE: Procedure = Procedure::BuiltinFixedArgumentForm("define", _define, 2);
+pub const DEFINE: Procedure = Procedure::SpecialFixedArgumentForm("define", _define, 2);
 
 #[cfg(test)]
 mod test {
@@ -0,0 +1,329 @@
+use super::*;
+
+fn environment_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<Rc<RefCell<Scope>>, EvaluationError> {
+  match arg {
+    Expression::Environment(scope) => Ok(scope.clone()),
+    non_environment => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "environment",
+      non_environment,
+    )),
+  }
+}
+
+fn _eval(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  // Without an environment, expressions are evaluated at the top level
+  let environment = match varargs.as_slice() {
+    [] => Scope::root(&scope),
+    [environment] => environment_arg("eval", environment)?,
+    _ => {
+      return Err(EvaluationError::WrongNumberOfArguments(
+        "eval".to_string(),
+        2,
+        varargs.len() + 1,
+      ))
+    }
+  };
+  _evaluate_procedure_body(&args, environment)
+}
+pub const EVAL: Procedure = Procedure::BuiltinVariableArgumentForm("eval", _eval, 1);
+
+fn _interaction_environment(_args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Environment(
+    Scope::root(&scope),
+  )))
+}
+pub const INTERACTION_ENVIRONMENT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("interaction-environment", _interaction_environment, 0);
+
+/// The special forms of the report, which are all that a null environment has
+const REPORT_SYNTAX: &[&str] = &[
+  "quote",
+  "lambda",
+  "cond",
+  "define",
+  "define-values",
+  "define-record-type",
+  "define-syntax",
+  "let-syntax",
+  "letrec-syntax",
+  "syntax-rules",
+  "let-values",
+  "let*-values",
+  "guard",
+  "delay",
+  "delay-force",
+];
+
+/// The procedures of the report that a report environment has. These only work with the values
+/// they are given and the current ports, so code evaluated in a report environment can't get at
+/// files, the interaction environment or the rest of the program.
+const REPORT_PROCEDURES: &[&str] = &[
+  "+",
+  "*",
+  "-",
+  "/",
+  "=",
+  "<",
+  ">",
+  "<=",
+  ">=",
+  "floor/",
+  "truncate/",
+  "exact-integer-sqrt",
+  "eq?",
+  "cons",
+  "car",
+  "cdr",
+  "call-with-current-continuation",
+  "call/cc",
+  "dynamic-wind",
+  "values",
+  "call-with-values",
+  "error",
+  "raise",
+  "raise-continuable",
+  "with-exception-handler",
+  "error-object?",
+  "error-object-message",
+  "error-object-irritants",
+  "make-promise",
+  "promise?",
+  "force",
+  "bytevector?",
+  "make-bytevector",
+  "bytevector",
+  "bytevector-length",
+  "bytevector-u8-ref",
+  "bytevector-u8-set!",
+  "utf8->string",
+  "string->utf8",
+  "current-input-port",
+  "current-output-port",
+  "current-error-port",
+  "read",
+  "read-char",
+  "peek-char",
+  "read-line",
+  "read-string",
+  "char-ready?",
+  "read-u8",
+  "peek-u8",
+  "u8-ready?",
+  "read-bytevector",
+  "eof-object",
+  "eof-object?",
+  "display",
+  "write",
+  "write-string",
+  "write-char",
+  "write-u8",
+  "write-bytevector",
+  "newline",
+  "flush-output-port",
+  "open-input-string",
+  "open-output-string",
+  "get-output-string",
+  "open-input-bytevector",
+  "open-output-bytevector",
+  "get-output-bytevector",
+  "close-port",
+  "close-input-port",
+  "close-output-port",
+];
+
+/// Check that the version of the report an environment is asked for is one we have
+fn check_version(procedure_name: &str, version: &Expression) -> Result<(), EvaluationError> {
+  match version {
+    Expression::Number(Number::Integer(5)) | Expression::Number(Number::Integer(7)) => Ok(()),
+    version => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "5 or 7",
+      version,
+    )),
+  }
+}
+
+fn _scheme_report_environment(
+  args: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  check_version("scheme-report-environment", args.first().unwrap())?;
+  let names: Vec<&str> = REPORT_SYNTAX
+    .iter()
+    .chain(REPORT_PROCEDURES)
+    .copied()
+    .collect();
+  Ok(ProcedureValue::Expression(Expression::Environment(
+    Scope::builtins_named(&names),
+  )))
+}
+pub const SCHEME_REPORT_ENVIRONMENT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("scheme-report-environment", _scheme_report_environment, 1);
+
+fn _null_environment(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  check_version("null-environment", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::Environment(
+    Scope::builtins_named(REPORT_SYNTAX),
+  )))
+}
+pub const NULL_ENVIRONMENT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("null-environment", _null_environment, 1);
+
+fn _environment_bound(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let environment = environment_arg("environment-bound?", args.first().unwrap())?;
+  match args.get(1).unwrap() {
+    Expression::Symbol(symbol) => Ok(ProcedureValue::Expression(boolean!(environment
+      .borrow()
+      .lookup(symbol)
+      .is_ok()))),
+    non_symbol => Err(EvaluationError::invalid_argument(
+      "environment-bound?",
+      "symbol",
+      non_symbol,
+    )),
+  }
+}
+pub const ENVIRONMENT_BOUND: Procedure =
+  Procedure::BuiltinFixedArgumentForm("environment-bound?", _environment_bound, 2);
+
+fn _environment_names(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let environment = environment_arg("environment-names", args.first().unwrap())?;
+  let names = environment.borrow().names();
+  Ok(ProcedureValue::Expression(vec_arg(
+    names.into_iter().map(Expression::Symbol).collect(),
+  )?))
+}
+pub const ENVIRONMENT_NAMES: Procedure =
+  Procedure::BuiltinFixedArgumentForm("environment-names", _environment_names, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_eval() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(eval '(+ 1 2))", int!(3));
+    ctx.assert_eq("(eval (cons '+ '(1 2)) (interaction-environment))", int!(3));
+    ctx.exec("(define x 10)");
+    // Without an environment, eval doesn't see the scope it is called from
+    ctx.assert_eq("((lambda (x) (eval 'x)) 1)", int!(10));
+    ctx.exec("(eval '(define y 20) (interaction-environment))");
+    ctx.assert_eq("y", int!(20));
+    ctx.assert_err(
+      "(eval 'x 1)",
+      EvaluationError::invalid_argument("eval", "environment", &int!(1)),
+    );
+    ctx.assert_err(
+      "(eval 'x 1 2)",
+      EvaluationError::WrongNumberOfArguments("eval".to_string(), 2, 3),
+    );
+  }
+
+  #[test]
+  fn test_restricted_environments() {
+    let ctx = TestContext::new();
+    ctx.exec("(define x 10)");
+    ctx.exec("(define report (scheme-report-environment 5))");
+    ctx.assert_eq("(eval '(+ 1 2) report)", int!(3));
+    // Definitions stay inside of the environment they were evaluated in
+    ctx.assert_err(
+      "(eval 'x report)",
+      EvaluationError::UndefinedSymbol("x".to_string()),
+    );
+    ctx.exec("(eval '(define z 1) report)");
+    ctx.assert_eq("(eval 'z report)", int!(1));
+    ctx.assert_err("z", EvaluationError::UndefinedSymbol("z".to_string()));
+    ctx.assert_err(
+      "(eval 'z (scheme-report-environment 7))",
+      EvaluationError::UndefinedSymbol("z".to_string()),
+    );
+    // The null environment only has special forms
+    ctx.assert_eq(
+      "(eval '((lambda (a) (quote b)) 1) (null-environment 5))",
+      symbol!("b"),
+    );
+    ctx.assert_err(
+      "(eval '(+ 1 2) (null-environment 5))",
+      EvaluationError::UndefinedSymbol("+".to_string()),
+    );
+    // Restricted environments can't get at files or the rest of the program
+    for name in [
+      "load",
+      "include",
+      "import",
+      "delete-file",
+      "open-output-file",
+      "eval",
+      "interaction-environment",
+      "exit",
+    ] {
+      for environment in ["(scheme-report-environment 5)", "(null-environment 5)"] {
+        ctx.assert_eq(
+          &format!("(environment-bound? {} '{})", environment, name),
+          boolean!(false),
+        );
+      }
+    }
+    ctx.assert_err(
+      "(eval '(include \"/etc/passwd\") (null-environment 5))",
+      EvaluationError::UndefinedSymbol("include".to_string()),
+    );
+    ctx.assert_err(
+      "(eval '(load \"/etc/passwd\") (scheme-report-environment 7))",
+      EvaluationError::UndefinedSymbol("load".to_string()),
+    );
+    ctx.assert_err(
+      "(null-environment 6)",
+      EvaluationError::invalid_argument("null-environment", "5 or 7", &int!(6)),
+    );
+  }
+
+  #[test]
+  fn test_environment_introspection() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(environment-bound? (scheme-report-environment 5) 'car)",
+      boolean!(true),
+    );
+    ctx.assert_eq(
+      "(environment-bound? (null-environment 5) 'car)",
+      boolean!(false),
+    );
+    ctx.assert_eq(
+      "(environment-bound? (null-environment 5) 'lambda)",
+      boolean!(true),
+    );
+    ctx.exec("(define env (null-environment 5))");
+    ctx.exec("(eval '(define zebra 1) env)");
+    ctx.assert_eq(
+      "(eval '(car (cdr '(a b))) (scheme-report-environment 5))",
+      symbol!("b"),
+    );
+    let names = evaluate(
+      &parse("(environment-names env)").unwrap(),
+      ctx.scope.clone(),
+    )
+    .unwrap();
+    let names = arg_vec("environment-names", &names).unwrap();
+    assert!(names.contains(&symbol!("zebra")));
+    assert!(names.contains(&symbol!("define")));
+    assert!(!names.contains(&symbol!("car")));
+    let mut sorted = names.clone();
+    sorted.sort_by_key(|name| name.to_string());
+    assert_eq!(names, sorted);
+    ctx.assert_err(
+      "(environment-bound? env 1)",
+      EvaluationError::invalid_argument("environment-bound?", "symbol", &int!(1)),
+    );
+  }
+}
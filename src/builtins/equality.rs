@@ -1,8 +1,8 @@
 use super::*;
 
-fn _eq(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  let a = evaluate(args.get(0).unwrap(), scope.clone())?;
-  let b = evaluate(args.get(1).unwrap(), scope)?;
+fn _eq(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let a = args.first().unwrap();
+  let b = args.get(1).unwrap();
   Ok(ProcedureValue::Expression(boolean!(a == b)))
 }
 
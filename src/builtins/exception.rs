@@ -0,0 +1,277 @@
+use super::conditional::COND;
+use super::quote::QUOTE;
+use super::*;
+
+fn _error(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  match args.first().unwrap() {
+    Expression::String(message) => Ok(ProcedureValue::Raise(Expression::Error(Rc::new(
+      EvaluationError::Error(message.clone(), varargs),
+    )))),
+    non_string => Err(EvaluationError::invalid_argument(
+      "error", "string", non_string,
+    )),
+  }
+}
+pub const ERROR: Procedure = Procedure::BuiltinVariableArgumentForm("error", _error, 1);
+
+fn _raise(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Raise(args.first().unwrap().clone()))
+}
+pub const RAISE: Procedure = Procedure::BuiltinFixedArgumentForm("raise", _raise, 1);
+
+fn _raise_continuable(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::RaiseContinuable(
+    args.first().unwrap().clone(),
+  ))
+}
+pub const RAISE_CONTINUABLE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("raise-continuable", _raise_continuable, 1);
+
+fn _with_exception_handler(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let handler = procedure_arg("with-exception-handler", args.first().unwrap())?;
+  let thunk = procedure_arg("with-exception-handler", args.get(1).unwrap())?;
+  Ok(ProcedureValue::WithExceptionHandler(handler, thunk, scope))
+}
+pub const WITH_EXCEPTION_HANDLER: Procedure =
+  Procedure::BuiltinFixedArgumentForm("with-exception-handler", _with_exception_handler, 2);
+
+/// Whether the last cond clause is an else clause
+fn has_else_clause(clauses: &[Expression]) -> bool {
+  match clauses.last() {
+    Some(Expression::Cons(clause)) => clause.car.as_ref() == &symbol!("else"),
+    _ => false,
+  }
+}
+
+fn _guard(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let spec = args.first().unwrap();
+  let (variable, clauses) = match arg_vec("guard", spec)?.split_first() {
+    Some((Expression::Symbol(variable), clauses)) => (variable.clone(), clauses.to_vec()),
+    _ => {
+      return Err(EvaluationError::invalid_argument(
+        "guard",
+        "(variable clause ...)",
+        spec,
+      ))
+    }
+  };
+  let mut body = varargs;
+  body.insert(0, args.get(1).unwrap().clone());
+  let guard_scope = scope.clone();
+  Ok(ProcedureValue::Guard(
+    body,
+    scope,
+    Continue::new(move |raised| {
+      let clause_scope = Scope::child(guard_scope.clone());
+      clause_scope.borrow_mut().define(&variable, raised.clone());
+      let mut clauses = clauses.clone();
+      // If no clause handles it, raise it again to whatever handler the guard is inside of
+      if !has_else_clause(&clauses) {
+        clauses.push(list!(
+          symbol!("else"),
+          list!(
+            Expression::Procedure(RAISE_CONTINUABLE),
+            list!(Expression::Procedure(QUOTE), raised)
+          )
+        ));
+      }
+      Ok(ProcedureValue::TailApply(COND, clauses, clause_scope))
+    }),
+  ))
+}
+pub const GUARD: Procedure = Procedure::SpecialVariableArgumentForm("guard", _guard, 2);
+
+fn error_object_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<Rc<EvaluationError>, EvaluationError> {
+  match arg {
+    Expression::Error(err) => Ok(err.clone()),
+    non_error => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "error object",
+      non_error,
+    )),
+  }
+}
+
+fn _error_object(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(matches!(
+    args.first().unwrap(),
+    Expression::Error(_)
+  ))))
+}
+pub const ERROR_OBJECT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("error-object?", _error_object, 1);
+
+fn _error_object_message(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let err = error_object_arg("error-object-message", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::String(
+    err.message(),
+  )))
+}
+pub const ERROR_OBJECT_MESSAGE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("error-object-message", _error_object_message, 1);
+
+fn _error_object_irritants(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let err = error_object_arg("error-object-irritants", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(vec_arg(err.irritants())?))
+}
+pub const ERROR_OBJECT_IRRITANTS: Procedure =
+  Procedure::BuiltinFixedArgumentForm("error-object-irritants", _error_object_irritants, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_error() {
+    let ctx = TestContext::new();
+    ctx.assert_err(
+      "(error \"boom\" 1 'two)",
+      EvaluationError::Error("boom".to_string(), vec![int!(1), symbol!("two")]),
+    );
+    ctx.assert_err(
+      "(error 'boom)",
+      EvaluationError::invalid_argument("error", "string", &symbol!("boom")),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (error-object-message e))) (error \"boom\" 1 2))",
+      string!("boom"),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (error-object-irritants e))) (error \"boom\" 1 2))",
+      list!(int!(1), int!(2)),
+    );
+    ctx.assert_eq("(error-object? 1)", boolean!(false));
+    ctx.assert_err(
+      "(error-object-message 1)",
+      EvaluationError::invalid_argument("error-object-message", "error object", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_builtin_errors() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(guard (e ((error-object? e) (cons (error-object-message e) (error-object-irritants e)))) (car 1))",
+      list!(
+        string!("invalid argument"),
+        string!("car"),
+        string!("list"),
+        int!(1)
+      ),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (cons (error-object-message e) (error-object-irritants e)))) (/ 1 0))",
+      list!(string!("divide by zero"), int!(1)),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (error-object-irritants e))) (+ 1 undefined))",
+      list!(symbol!("undefined")),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (error-object-irritants e))) ((lambda (x) x)))",
+      list!(string!("#<procedure>"), int!(1), int!(0)),
+    );
+  }
+
+  #[test]
+  fn test_guard() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(guard (e (#t 1)) 2 3)", int!(3));
+    ctx.assert_eq("(guard (e ((eq? e 'oops) 42)) (raise 'oops))", int!(42));
+    ctx.assert_eq("(guard (e ((eq? e 'a) 1) (else 2)) (raise 'b))", int!(2));
+    // Nothing matches, so it is raised again
+    ctx.assert_err(
+      "(guard (e ((eq? e 'a) 1)) (raise 'b))",
+      EvaluationError::Raised(Box::new(symbol!("b"))),
+    );
+    ctx.assert_err(
+      "(guard (e ((eq? e 'a) 1)) (car 1))",
+      EvaluationError::invalid_argument("car", "list", &int!(1)),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t (cons 'outer e))) (guard (e ((eq? e 'a) 1)) (raise 'b)))",
+      cons!(&symbol!("outer"), &symbol!("b")),
+    );
+    // The handlers outside the guard are back in place after it handles something
+    ctx.assert_eq(
+      "
+(with-exception-handler
+  (lambda (e) 10)
+  (lambda () (+ (guard (e (#t 1)) (raise 'x)) (raise-continuable 'y))))",
+      int!(11),
+    );
+    ctx.assert_err(
+      "(guard 1 2)",
+      EvaluationError::invalid_argument("guard", "list", &int!(1)),
+    );
+    ctx.assert_err(
+      "(guard (1) 2)",
+      EvaluationError::invalid_argument("guard", "(variable clause ...)", &list!(int!(1))),
+    );
+  }
+
+  #[test]
+  fn test_with_exception_handler() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(with-exception-handler (lambda (e) (+ e 1)) (lambda () (+ 10 (raise-continuable 1))))",
+      int!(12),
+    );
+    // Handlers run with the handlers outside of them installed
+    ctx.assert_eq(
+      "
+(with-exception-handler
+  (lambda (e) (cons 'outer e))
+  (lambda ()
+    (with-exception-handler
+      (lambda (e) (raise-continuable (cons 'inner e)))
+      (lambda () (raise-continuable 1)))))",
+      cons!(&symbol!("outer"), &cons!(&symbol!("inner"), &int!(1))),
+    );
+    ctx.assert_eq(
+      "
+(call/cc (lambda (k)
+  (with-exception-handler
+    (lambda (e) (k (error-object-message e)))
+    (lambda () (undefined-procedure)))))",
+      string!("undefined symbol"),
+    );
+    // Returning from a handler for raise raises it again
+    ctx.assert_err(
+      "(with-exception-handler (lambda (e) 0) (lambda () (raise 'oops)))",
+      EvaluationError::Raised(Box::new(symbol!("oops"))),
+    );
+    ctx.assert_err(
+      "(with-exception-handler (lambda (e) 0) (lambda () (car 1)))",
+      EvaluationError::invalid_argument("car", "list", &int!(1)),
+    );
+    ctx.assert_err(
+      "(with-exception-handler 1 (lambda () 1))",
+      EvaluationError::invalid_argument("with-exception-handler", "procedure", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_guard_stack_overflow() {
+    let ctx = TestContext::new();
+    crate::evaluate::set_max_stack_depth(1000);
+    ctx.exec("(define forever (lambda (index) (+ 1 (forever index))))");
+    ctx.assert_eq(
+      "(guard (e (#t (error-object-message e))) (forever 0))",
+      string!("stack overflow"),
+    );
+    crate::evaluate::set_max_stack_depth(crate::evaluate::DEFAULT_MAX_STACK_DEPTH);
+  }
+}
@@ -0,0 +1,287 @@
+use super::*;
+use crate::port::{set_current_input_port, set_current_output_port, Port};
+use std::fs;
+use std::path::Path;
+
+fn filename_arg(procedure_name: &str, arg: &Expression) -> Result<String, EvaluationError> {
+  match arg {
+    Expression::String(filename) => Ok(filename.clone()),
+    non_string => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "string",
+      non_string,
+    )),
+  }
+}
+
+fn port_arg(procedure_name: &str, arg: &Expression) -> Result<Rc<Port>, EvaluationError> {
+  match arg {
+    Expression::Port(port) => Ok(port.clone()),
+    non_port => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "port",
+      non_port,
+    )),
+  }
+}
+
+fn _open_input_file(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("open-input-file", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::open_input_file(&filename, false)?,
+  )))
+}
+pub const OPEN_INPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-input-file", _open_input_file, 1);
+
+fn _open_binary_input_file(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("open-binary-input-file", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::open_input_file(&filename, true)?,
+  )))
+}
+pub const OPEN_BINARY_INPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-binary-input-file", _open_binary_input_file, 1);
+
+fn _open_output_file(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("open-output-file", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::open_output_file(&filename, false)?,
+  )))
+}
+pub const OPEN_OUTPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-output-file", _open_output_file, 1);
+
+fn _open_binary_output_file(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("open-binary-output-file", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::open_output_file(&filename, true)?,
+  )))
+}
+pub const OPEN_BINARY_OUTPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-binary-output-file", _open_binary_output_file, 1);
+
+fn _close_port(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  port_arg("close-port", args.first().unwrap())?.close()?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const CLOSE_PORT: Procedure = Procedure::BuiltinFixedArgumentForm("close-port", _close_port, 1);
+
+fn _close_input_port(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  port_arg("close-input-port", args.first().unwrap())?.close()?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const CLOSE_INPUT_PORT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("close-input-port", _close_input_port, 1);
+
+fn _close_output_port(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  port_arg("close-output-port", args.first().unwrap())?.close()?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const CLOSE_OUTPUT_PORT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("close-output-port", _close_output_port, 1);
+
+/// Call a procedure with a port, then close the port however the procedure finishes
+fn call_with_port(
+  port: Rc<Port>,
+  procedure: Procedure,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let inside = ProcedureValue::TailApply(
+    procedure,
+    vec![Expression::Port(port.clone())],
+    scope.clone(),
+  );
+  Ok(ProcedureValue::DynamicWind(
+    rust_thunk("call-with-port", || Ok(())),
+    Box::new(inside),
+    rust_thunk("call-with-port", move || port.close()),
+    scope,
+  ))
+}
+
+fn _call_with_input_file(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("call-with-input-file", args.first().unwrap())?;
+  let procedure = procedure_arg("call-with-input-file", args.get(1).unwrap())?;
+  call_with_port(Port::open_input_file(&filename, false)?, procedure, scope)
+}
+pub const CALL_WITH_INPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("call-with-input-file", _call_with_input_file, 2);
+
+fn _call_with_output_file(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("call-with-output-file", args.first().unwrap())?;
+  let procedure = procedure_arg("call-with-output-file", args.get(1).unwrap())?;
+  call_with_port(Port::open_output_file(&filename, false)?, procedure, scope)
+}
+pub const CALL_WITH_OUTPUT_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("call-with-output-file", _call_with_output_file, 2);
+
+/// Call a thunk with a port as the current input or output port, then put back the port it
+/// replaced and close it, however the thunk finishes
+fn with_port(
+  port: Rc<Port>,
+  set_current_port: fn(Rc<Port>) -> Rc<Port>,
+  thunk: Procedure,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let previous = Rc::new(RefCell::new(None));
+  let replaced = previous.clone();
+  let current = port.clone();
+  Ok(ProcedureValue::DynamicWind(
+    rust_thunk("with-port", move || {
+      *replaced.borrow_mut() = Some(set_current_port(current.clone()));
+      Ok(())
+    }),
+    Box::new(ProcedureValue::TailApply(thunk, vec![], scope.clone())),
+    rust_thunk("with-port", move || {
+      if let Some(previous) = previous.borrow_mut().take() {
+        set_current_port(previous);
+      }
+      port.close()
+    }),
+    scope,
+  ))
+}
+
+fn _with_input_from_file(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("with-input-from-file", args.first().unwrap())?;
+  let thunk = procedure_arg("with-input-from-file", args.get(1).unwrap())?;
+  let port = Port::open_input_file(&filename, false)?;
+  with_port(port, set_current_input_port, thunk, scope)
+}
+pub const WITH_INPUT_FROM_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("with-input-from-file", _with_input_from_file, 2);
+
+fn _with_output_to_file(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("with-output-to-file", args.first().unwrap())?;
+  let thunk = procedure_arg("with-output-to-file", args.get(1).unwrap())?;
+  let port = Port::open_output_file(&filename, false)?;
+  with_port(port, set_current_output_port, thunk, scope)
+}
+pub const WITH_OUTPUT_TO_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("with-output-to-file", _with_output_to_file, 2);
+
+fn _file_exists(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("file-exists?", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(boolean!(
+    Path::new(&filename).exists()
+  )))
+}
+pub const FILE_EXISTS: Procedure =
+  Procedure::BuiltinFixedArgumentForm("file-exists?", _file_exists, 1);
+
+fn _delete_file(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let filename = filename_arg("delete-file", args.first().unwrap())?;
+  fs::remove_file(&filename)
+    .map_err(|err| EvaluationError::FileError(filename, err.to_string()))?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const DELETE_FILE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("delete-file", _delete_file, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::{TestContext, TestFiles};
+
+  #[test]
+  fn test_call_with_file() {
+    let files = TestFiles::new("call-with-file", &[("in.txt", "(1 2)\nsecond line\n")]);
+    let ctx = TestContext::new();
+    let input = files.path("in.txt");
+    let output = files.path("out.txt");
+    ctx.exec(&format!("(define input {:?})", input));
+    ctx.exec(&format!("(define output {:?})", output));
+    ctx.assert_eq(
+      "(call-with-input-file input (lambda (port) (read port) (read-line port) (read-line port)))",
+      string!("second line"),
+    );
+    ctx.exec("(call-with-output-file output (lambda (port) (write \"a\" port) (newline port)))");
+    ctx.assert_eq("(file-exists? output)", boolean!(true));
+    ctx.assert_eq("(call-with-input-file output read-line)", string!("\"a\""));
+    // The port is closed once the procedure returns
+    ctx.exec("(define port (call-with-input-file input (lambda (port) port)))");
+    let port = ctx.scope.borrow().lookup("port").unwrap();
+    ctx.assert_err(
+      "(read-line port)",
+      EvaluationError::invalid_argument("read-line", "input port", &port),
+    );
+  }
+
+  #[test]
+  fn test_with_file() {
+    let files = TestFiles::new("with-file", &[("in.txt", "from file\n")]);
+    let ctx = TestContext::new();
+    ctx.exec(&format!("(define input {:?})", files.path("in.txt")));
+    ctx.exec(&format!("(define output {:?})", files.path("out.txt")));
+    ctx.assert_eq(
+      "(with-input-from-file input read-line)",
+      string!("from file"),
+    );
+    ctx.exec("(define stdout (current-output-port))");
+    ctx.exec("(with-output-to-file output (lambda () (display \"to file\")))");
+    ctx.assert_eq("(eq? stdout (current-output-port))", boolean!(true));
+    ctx.assert_eq(
+      "(with-input-from-file output read-line)",
+      string!("to file"),
+    );
+    // The current output port is put back even when the thunk fails
+    ctx.assert_err(
+      "(with-output-to-file output (lambda () (car 1)))",
+      EvaluationError::invalid_argument("car", "list", &int!(1)),
+    );
+    ctx.assert_eq("(eq? stdout (current-output-port))", boolean!(true));
+    // and when a continuation escapes from it, which leaves the rest of the program running
+    ctx.assert_eq(
+      "(+ 1 (call/cc (lambda (k) (with-output-to-file output (lambda () (display \"escaped\") (k 41))))))",
+      int!(42),
+    );
+    ctx.assert_eq("(eq? stdout (current-output-port))", boolean!(true));
+    ctx.assert_eq(
+      "(with-input-from-file output read-line)",
+      string!("escaped"),
+    );
+    ctx.assert_eq(
+      "(guard (e (#t e)) (with-input-from-file input (lambda () (raise (read-line)))))",
+      string!("from file"),
+    );
+  }
+
+  #[test]
+  fn test_file_ports() {
+    let files = TestFiles::new("file-ports", &[]);
+    let ctx = TestContext::new();
+    ctx.exec(&format!("(define filename {:?})", files.path("out.bin")));
+    ctx.exec("(define out (open-binary-output-file filename))");
+    ctx.exec("(write-u8 1 out)");
+    ctx.exec("(write-bytevector #u8(2 255) out)");
+    ctx.exec("(close-port out)");
+    ctx.exec("(define in (open-binary-input-file filename))");
+    ctx.assert_eq("(peek-u8 in)", int!(1));
+    ctx.assert_eq("(read-u8 in)", int!(1));
+    ctx.assert_eq("(u8-ready? in)", boolean!(true));
+    ctx.assert_eq("(read-bytevector 5 in)", bytevector!(vec![2, 255]));
+    ctx.assert_eq("(eof-object? (read-u8 in))", boolean!(true));
+    ctx.exec("(close-input-port in)");
+    ctx.exec("(define in (open-binary-input-file filename))");
+    let port = ctx.scope.borrow().lookup("in").unwrap();
+    ctx.assert_err(
+      "(read-line in)",
+      EvaluationError::invalid_argument("read-line", "input port", &port),
+    );
+    ctx.exec("(define out (open-output-file filename))");
+    ctx.exec("(display \"text\" out)");
+    ctx.exec("(close-output-port out)");
+    ctx.exec("(define in (open-input-file filename))");
+    ctx.assert_eq("(read-line in)", string!("text"));
+    ctx.exec("(delete-file filename)");
+    ctx.assert_eq("(file-exists? filename)", boolean!(false));
+    ctx.assert_err(
+      "(open-input-file filename)",
+      EvaluationError::FileError(
+        files.path("out.bin"),
+        "No such file or directory (os error 2)".to_string(),
+      ),
+    );
+  }
+}
@@ -0,0 +1,268 @@
+use super::*;
+use crate::port::{current_input_port, Port};
+
+/// The port given as the optional last argument of an input procedure, or the current input
+/// port if there isn't one
+fn input_port_arg(
+  procedure_name: &str,
+  argc: usize,
+  varargs: &[Expression],
+) -> Result<Rc<Port>, EvaluationError> {
+  match varargs {
+    [] => Ok(current_input_port()),
+    [Expression::Port(port)] if port.is_input() && !port.binary => Ok(port.clone()),
+    [non_port] => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "input port",
+      non_port,
+    )),
+    _ => Err(EvaluationError::WrongNumberOfArguments(
+      procedure_name.to_string(),
+      argc + 1,
+      argc + varargs.len(),
+    )),
+  }
+}
+
+/// A binary input port, which has to be given since the standard ports are textual
+fn binary_input_port_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<Rc<Port>, EvaluationError> {
+  match arg {
+    Expression::Port(port) if port.is_input() && port.binary => Ok(port.clone()),
+    non_port => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "binary input port",
+      non_port,
+    )),
+  }
+}
+
+/// How many characters or bytes to read
+fn count_arg(procedure_name: &str, arg: &Expression) -> Result<usize, EvaluationError> {
+  match arg {
+    Expression::Number(Number::Integer(k)) if *k >= 0 => Ok(*k as usize),
+    non_count => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "non-negative integer",
+      non_count,
+    )),
+  }
+}
+
+fn byte_or_eof(byte: Option<u8>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(
+    byte
+      .map(|byte| int!(byte as i32))
+      .unwrap_or(Expression::Eof),
+  ))
+}
+
+fn char_or_eof(c: Option<char>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(
+    c.map(Expression::Char).unwrap_or(Expression::Eof),
+  ))
+}
+
+fn string_or_eof(string: Option<String>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(
+    string.map(Expression::String).unwrap_or(Expression::Eof),
+  ))
+}
+
+fn _current_input_port(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Port(
+    current_input_port(),
+  )))
+}
+pub const CURRENT_INPUT_PORT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("current-input-port", _current_input_port, 0);
+
+fn _read_char(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = input_port_arg("read-char", 0, &varargs)?;
+  char_or_eof(port.with_input(|input| input.read_char())?)
+}
+pub const READ_CHAR: Procedure = Procedure::BuiltinVariableArgumentForm("read-char", _read_char, 0);
+
+fn _peek_char(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = input_port_arg("peek-char", 0, &varargs)?;
+  char_or_eof(port.with_input(|input| input.peek_char())?)
+}
+pub const PEEK_CHAR: Procedure = Procedure::BuiltinVariableArgumentForm("peek-char", _peek_char, 0);
+
+fn _read_line(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = input_port_arg("read-line", 0, &varargs)?;
+  string_or_eof(port.with_input(|input| input.read_line())?)
+}
+pub const READ_LINE: Procedure = Procedure::BuiltinVariableArgumentForm("read-line", _read_line, 0);
+
+fn _read_string(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let k = count_arg("read-string", args.first().unwrap())?;
+  let port = input_port_arg("read-string", 1, &varargs)?;
+  string_or_eof(port.with_input(|input| input.read_string(k))?)
+}
+pub const READ_STRING: Procedure =
+  Procedure::BuiltinVariableArgumentForm("read-string", _read_string, 1);
+
+fn _char_ready(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = input_port_arg("char-ready?", 0, &varargs)?;
+  let ready = port.with_input(|input| Ok(input.ready()))?;
+  Ok(ProcedureValue::Expression(boolean!(ready)))
+}
+pub const CHAR_READY: Procedure =
+  Procedure::BuiltinVariableArgumentForm("char-ready?", _char_ready, 0);
+
+fn _read(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = input_port_arg("read", 0, &varargs)?;
+  Ok(ProcedureValue::Expression(
+    port.read()?.unwrap_or(Expression::Eof),
+  ))
+}
+pub const READ: Procedure = Procedure::BuiltinVariableArgumentForm("read", _read, 0);
+
+fn _read_u8(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let port = binary_input_port_arg("read-u8", args.first().unwrap())?;
+  byte_or_eof(port.with_input(|input| input.read_byte())?)
+}
+pub const READ_U8: Procedure = Procedure::BuiltinFixedArgumentForm("read-u8", _read_u8, 1);
+
+fn _peek_u8(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let port = binary_input_port_arg("peek-u8", args.first().unwrap())?;
+  byte_or_eof(port.with_input(|input| input.peek_byte())?)
+}
+pub const PEEK_U8: Procedure = Procedure::BuiltinFixedArgumentForm("peek-u8", _peek_u8, 1);
+
+fn _u8_ready(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let port = binary_input_port_arg("u8-ready?", args.first().unwrap())?;
+  let ready = port.with_input(|input| Ok(input.ready()))?;
+  Ok(ProcedureValue::Expression(boolean!(ready)))
+}
+pub const U8_READY: Procedure = Procedure::BuiltinFixedArgumentForm("u8-ready?", _u8_ready, 1);
+
+fn _read_bytevector(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let k = count_arg("read-bytevector", args.first().unwrap())?;
+  let port = binary_input_port_arg("read-bytevector", args.get(1).unwrap())?;
+  Ok(ProcedureValue::Expression(
+    port
+      .with_input(|input| input.read_bytes(k))?
+      .map(|bytes| bytevector!(bytes))
+      .unwrap_or(Expression::Eof),
+  ))
+}
+pub const READ_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("read-bytevector", _read_bytevector, 2);
+
+fn _eof_object(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Eof))
+}
+pub const EOF_OBJECT: Procedure = Procedure::BuiltinFixedArgumentForm("eof-object", _eof_object, 0);
+
+fn _is_eof_object(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(
+    args.first().unwrap() == &Expression::Eof
+  )))
+}
+pub const IS_EOF_OBJECT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("eof-object?", _is_eof_object, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::parse::ParseError;
+  use crate::port::{Input, PortState};
+  use crate::test::TestContext;
+  use std::io::Cursor;
+
+  fn input_context(contents: &str) -> TestContext {
+    let ctx = TestContext::new();
+    let input = Input::new(Box::new(Cursor::new(contents.as_bytes().to_vec())), false);
+    ctx.scope.borrow_mut().define(
+      "in",
+      Expression::Port(Port::new("test", PortState::Input(input))),
+    );
+    ctx
+  }
+
+  #[test]
+  fn test_read_chars() {
+    let ctx = input_context("aλ\nline two\r\nrest");
+    ctx.assert_eq("(peek-char in)", Expression::Char('a'));
+    ctx.assert_eq("(read-char in)", Expression::Char('a'));
+    ctx.assert_eq("(char-ready? in)", boolean!(true));
+    ctx.assert_eq("(read-line in)", string!("λ"));
+    ctx.assert_eq("(read-string 4 in)", string!("line"));
+    ctx.assert_eq("(read-line in)", string!(" two\r"));
+    ctx.assert_eq("(read-string 10 in)", string!("rest"));
+    ctx.assert_eq("(read-string 10 in)", Expression::Eof);
+    ctx.assert_eq("(read-line in)", Expression::Eof);
+    ctx.assert_eq("(peek-char in)", Expression::Eof);
+    ctx.assert_eq("(eof-object? (read-char in))", boolean!(true));
+    ctx.assert_eq("(eof-object? (eof-object))", boolean!(true));
+    ctx.assert_eq("(eof-object? \"\")", boolean!(false));
+  }
+
+  #[test]
+  fn test_read() {
+    let ctx = input_context("(define x\n  \"a\n b\") 'y ; comment\n#\\a 12");
+    ctx.assert_eq(
+      "(read in)",
+      list!(symbol!("define"), symbol!("x"), string!("a\n b")),
+    );
+    ctx.assert_eq("(read in)", list!(symbol!("quote"), symbol!("y")));
+    ctx.assert_eq("(read-char in)", Expression::Char(' '));
+    ctx.assert_eq("(read in)", Expression::Char('a'));
+    ctx.assert_eq("(read in)", int!(12));
+    ctx.assert_eq("(eof-object? (read in))", boolean!(true));
+    let ctx = input_context("(1 2");
+    ctx.assert_err(
+      "(read in)",
+      EvaluationError::ParseError("test".to_string(), ParseError::UnexpectedEOF),
+    );
+  }
+
+  #[test]
+  fn test_input_errors() {
+    let ctx = input_context("");
+    ctx.assert_err(
+      "(read-char 1)",
+      EvaluationError::invalid_argument("read-char", "input port", &int!(1)),
+    );
+    ctx.assert_err(
+      "(read-char (current-output-port))",
+      EvaluationError::invalid_argument(
+        "read-char",
+        "input port",
+        &Expression::Port(crate::port::current_output_port()),
+      ),
+    );
+    ctx.assert_err(
+      "(read-string -1 in)",
+      EvaluationError::invalid_argument("read-string", "non-negative integer", &int!(-1)),
+    );
+  }
+}
@@ -5,9 +5,9 @@ use std::rc::Rc;
 fn _lambda(
   args: Vec<Expression>,
   varargs: Vec<Expression>,
-  _scope: Rc<RefCell<Scope>>,
+  scope: Rc<RefCell<Scope>>,
 ) -> ProcedureResult {
-  let mut formals = args.get(0).unwrap();
+  let mut formals = args.first().unwrap();
   // lambda requires two arguments: the formals, and at least one statement in the body.
   // We will graft that required statement onto the beginning of varargs to build to the statement list.
   let first_statement = args.get(1).unwrap();
@@ -17,7 +17,8 @@ fn _lambda(
     Expression::Symbol(symbol) => Ok(ProcedureValue::Expression(procedure!(
       vec![],
       symbol.clone(),
-      body
+      body,
+      scope
     ))),
     Expression::Cons(_) | Expression::Null => {
       let mut args = vec![];
@@ -39,7 +40,8 @@ fn _lambda(
           Ok(ProcedureValue::Expression(procedure!(
             args,
             symbol.clone(),
-            body
+            body,
+            scope
           )))
         } else {
           Err(EvaluationError::invalid_argument(
@@ -47,13 +49,13 @@ fn _lambda(
           ))
         }
       } else {
-        Ok(ProcedureValue::Expression(procedure!(args, body)))
+        Ok(ProcedureValue::Expression(procedure!(args, body, scope)))
       }
     }
     _ => Err(EvaluationError::invalid_argument("lambda", "list", formals)),
   }
 }
-pub const LAMBDA: Procedure = Procedure::BuiltinVariableArgumentForm("lambda", _lambda, 2);
+pub const LAMBDA: Procedure = Procedure::SpecialVariableArgumentForm("lambda", _lambda, 2);
 
 #[cfg(test)]
 mod test {
@@ -130,6 +132,74 @@ mod test {
     ctx.assert_eq("(fibonacci 5)", int!(5));
     ctx.assert_eq("(fibonacci 6)", int!(8));
   }
+  #[test]
+  fn test_lambda_deep_recursion() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define sum (lambda (index)
+  (cond
+    ((eq? index 0) 0)
+    (else (+ index (sum (- index 1))))
+  )
+))",
+    );
+    // This recursion is far deeper than the Rust stack could handle
+    ctx.assert_eq("(sum 20000)", int!(200010000));
+    ctx.exec(
+      "
+(define count (lambda (index)
+  (cond
+    ((eq? index 0) '())
+    (else (cons index (count (- index 1))))
+  )
+))",
+    );
+    ctx.assert_eq("(car (count 5000))", int!(5000));
+  }
+
+  #[test]
+  fn test_lambda_stack_overflow() {
+    let ctx = TestContext::new();
+    crate::evaluate::set_max_stack_depth(1000);
+    ctx.exec("(define forever (lambda (index) (+ 1 (forever index))))");
+    ctx.assert_err("(forever 0)", EvaluationError::StackOverflow(1000));
+    // The scope is still usable after the overflow
+    ctx.assert_eq("(+ 1 1)", int!(2));
+    crate::evaluate::set_max_stack_depth(crate::evaluate::DEFAULT_MAX_STACK_DEPTH);
+  }
+
+  #[test]
+  fn test_lambda_interrupted() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    let ctx = TestContext::new();
+    let flag = Arc::new(AtomicBool::new(false));
+    crate::evaluate::set_interrupt_flag(flag.clone());
+    let interrupt_soon = || {
+      let flag = flag.clone();
+      thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        flag.store(true, Ordering::Relaxed);
+      })
+    };
+    ctx.exec("(define forever (lambda (index) (forever (+ index 1))))");
+    ctx.exec("(define x 1)");
+    interrupt_soon();
+    ctx.assert_err("(forever 0)", EvaluationError::Interrupted);
+    // Interrupting can't be handled like an error
+    interrupt_soon();
+    ctx.assert_err(
+      "(guard (e (#t 'handled)) (forever 0))",
+      EvaluationError::Interrupted,
+    );
+    // The scope is still usable after the interrupt
+    ctx.assert_eq("x", int!(1));
+  }
+
   #[test]
   fn test_lambda_tail_call_recursion() {
     let ctx = TestContext::new();
@@ -149,10 +219,20 @@ mod test {
     ctx.assert_eq("(loopy 10000)", int!(0));
   }
 
+  #[test]
+  fn test_lambda_closure() {
+    let ctx = TestContext::new();
+    ctx.exec("(define make-adder (lambda (n) (lambda (x) (+ x n))))");
+    ctx.exec("(define add-two (make-adder 2))");
+    ctx.assert_eq("(add-two 3)", int!(5));
+    ctx.assert_eq("((make-adder 10) 3)", int!(13));
+    ctx.assert_err("n", EvaluationError::UndefinedSymbol("n".to_string()));
+  }
+
   #[test]
   fn test_lambda_not_a_procedure() {
     let ctx = TestContext::new();
     ctx.exec("(define foo 1)");
-    ctx.assert_err("(foo)", EvaluationError::NotAProcedure(int!(1)));
+    ctx.assert_err("(foo)", EvaluationError::NotAProcedure(Box::new(int!(1))));
   }
 }
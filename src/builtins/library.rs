@@ -0,0 +1,185 @@
+use super::*;
+
+fn _define_library(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  nested_result(
+    crate::library::define_library(args.first().unwrap(), &varargs, &scope).map(|_| void!()),
+  )
+}
+pub const DEFINE_LIBRARY: Procedure =
+  Procedure::SpecialVariableArgumentForm("define-library", _define_library, 1);
+
+fn _import(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  for spec in args.iter().chain(&varargs) {
+    if let Err(err) = crate::library::import(spec, &scope) {
+      return nested_result(Err(err));
+    }
+  }
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const IMPORT: Procedure = Procedure::SpecialVariableArgumentForm("import", _import, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::{TestContext, TestFiles};
+  use std::path::PathBuf;
+
+  #[test]
+  fn test_define_library() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define-library (shapes square)
+  (import (scheme base))
+  (export square (rename square-area area))
+  (begin
+    (define square (lambda (x) (* x x)))
+    (define helper 4)
+    (define square-area (lambda (side) (square side)))))",
+    );
+    // Nothing in a library is visible until it is imported, and then only its exports are
+    ctx.assert_err(
+      "square",
+      EvaluationError::UndefinedSymbol("square".to_string()),
+    );
+    ctx.exec("(import (shapes square))");
+    ctx.assert_eq("(square 3)", int!(9));
+    ctx.assert_eq("(area 4)", int!(16));
+    ctx.assert_err(
+      "helper",
+      EvaluationError::UndefinedSymbol("helper".to_string()),
+    );
+    ctx.assert_err(
+      "square-area",
+      EvaluationError::UndefinedSymbol("square-area".to_string()),
+    );
+    // Libraries can import other libraries
+    ctx.exec(
+      "
+(define-library (shapes cube)
+  (import (scheme base) (only (shapes square) square))
+  (export cube)
+  (begin (define cube (lambda (x) (* x (square x))))))",
+    );
+    ctx.exec("(import (shapes cube))");
+    ctx.assert_eq("(cube 2)", int!(8));
+    // Only the libraries of the report are built in, and each only has its own bindings
+    ctx.assert_err(
+      "(import (scheme bsae))",
+      EvaluationError::LibraryNotFound(Box::new(list!(symbol!("scheme"), symbol!("bsae")))),
+    );
+    ctx.assert_err(
+      "(define-library (sneaky) (import (scheme base)) (export l) (begin (define l load)))",
+      EvaluationError::UndefinedSymbol("load".to_string()),
+    );
+    ctx.exec(
+      "(define-library (loader) (import (scheme base) (scheme load)) (export l) (begin (define l load)))",
+    );
+    // Library bodies only see what they import
+    ctx.assert_err(
+      "(define-library (bare) (begin (define x 1)))",
+      EvaluationError::UndefinedSymbol("define".to_string()),
+    );
+    // Each program has libraries of its own
+    let other = TestContext::new();
+    other.assert_err(
+      "(import (shapes square))",
+      EvaluationError::LibraryNotFound(Box::new(list!(symbol!("shapes"), symbol!("square")))),
+    );
+    ctx.assert_err(
+      "(define-library (broken) (export missing))",
+      EvaluationError::UndefinedSymbol("missing".to_string()),
+    );
+    ctx.assert_err(
+      "(define-library (broken) (exports x))",
+      EvaluationError::invalid_argument(
+        "define-library",
+        "library declaration",
+        &list!(symbol!("exports"), symbol!("x")),
+      ),
+    );
+  }
+
+  #[test]
+  fn test_import_sets() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define-library (numbers)
+  (import (scheme base))
+  (export one two three)
+  (begin (define one 1) (define two 2) (define three 3)))",
+    );
+    ctx.exec("(import (only (numbers) one))");
+    ctx.assert_eq("one", int!(1));
+    ctx.assert_err("two", EvaluationError::UndefinedSymbol("two".to_string()));
+    ctx.exec("(import (except (numbers) one three))");
+    ctx.assert_eq("two", int!(2));
+    ctx.assert_err(
+      "three",
+      EvaluationError::UndefinedSymbol("three".to_string()),
+    );
+    ctx.exec("(import (prefix (rename (numbers) (three drei)) n:))");
+    ctx.assert_eq("n:drei", int!(3));
+    ctx.assert_eq("n:one", int!(1));
+    ctx.assert_err(
+      "(import (only (numbers) four))",
+      EvaluationError::invalid_argument("import", "imported name", &symbol!("four")),
+    );
+    ctx.assert_err(
+      "(import (rename (numbers) (one)))",
+      EvaluationError::invalid_argument("import", "(name new-name)", &list!(symbol!("one"))),
+    );
+    // Things raised by a library are raised where it is imported
+    ctx.assert_eq(
+      "(guard (e (#t e)) (define-library (raises) (import (scheme base)) (begin (raise 'oops))))",
+      symbol!("oops"),
+    );
+  }
+
+  #[test]
+  fn test_library_files() {
+    let files = TestFiles::new(
+      "library",
+      &[
+        (
+          "our/util/list.sld",
+          "(define-library (our util list) (import (scheme base)) (export second) (include \"list-body.lisp\"))",
+        ),
+        (
+          "our/util/list-body.lisp",
+          "(define second (lambda (items) (car (cdr items))))",
+        ),
+        (
+          "our/util/pairs.lisp",
+          "(define-library (our util pairs) (import (scheme base) (our util list)) (export pair-second) (begin (define pair-second second)))",
+        ),
+      ],
+    );
+    crate::load::set_load_path(vec![PathBuf::from(files.path(""))]);
+    let ctx = TestContext::new();
+    ctx.exec("(import (our util pairs))");
+    ctx.assert_eq("(pair-second '(1 2 3))", int!(2));
+    ctx.assert_err(
+      "second",
+      EvaluationError::UndefinedSymbol("second".to_string()),
+    );
+    ctx.assert_err(
+      "(import (our util missing))",
+      EvaluationError::LibraryNotFound(Box::new(list!(
+        symbol!("our"),
+        symbol!("util"),
+        symbol!("missing")
+      ))),
+    );
+    crate::load::set_load_path(vec![]);
+  }
+}
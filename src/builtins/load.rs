@@ -0,0 +1,258 @@
+use super::*;
+
+fn _load(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let filename = match args.first().unwrap() {
+    Expression::String(filename) => filename.clone(),
+    non_string => {
+      return Err(EvaluationError::invalid_argument(
+        "load", "string", non_string,
+      ))
+    }
+  };
+  // Without an environment, files are loaded at the top level
+  let environment = match varargs.as_slice() {
+    [] => Scope::root(&scope),
+    [Expression::Environment(environment)] => environment.clone(),
+    [non_environment] => {
+      return Err(EvaluationError::invalid_argument(
+        "load",
+        "environment",
+        non_environment,
+      ))
+    }
+    _ => {
+      return Err(EvaluationError::WrongNumberOfArguments(
+        "load".to_string(),
+        2,
+        varargs.len() + 1,
+      ))
+    }
+  };
+  let path = crate::load::resolve(&filename)?;
+  let expressions = crate::load::read_file(&path)?;
+  // The file is only marked as being loaded while its expressions are being evaluated, however
+  // that is left and come back to
+  Ok(ProcedureValue::DynamicWind(
+    rust_thunk("load", move || crate::load::enter(&path)),
+    Box::new(load_from(Rc::new(expressions), 0, environment.clone())),
+    rust_thunk("load", || {
+      crate::load::leave();
+      Ok(())
+    }),
+    environment,
+  ))
+}
+pub const LOAD: Procedure = Procedure::BuiltinVariableArgumentForm("load", _load, 1);
+
+/// Evaluate the expressions of a file one after the other from index on, the way the top level
+/// of the file is
+fn load_from(
+  expressions: Rc<Vec<Expression>>,
+  index: usize,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureValue {
+  match expressions.get(index) {
+    None => ProcedureValue::Expression(void!()),
+    Some(expression) => ProcedureValue::Evaluate(
+      expression.clone(),
+      scope.clone(),
+      Continue::new(move |_| Ok(load_from(expressions.clone(), index + 1, scope.clone()))),
+    ),
+  }
+}
+
+fn _include(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let mut args = args;
+  args.extend(varargs);
+  let mut body = vec![];
+  for filename in crate::load::include_filenames(&args)? {
+    body.extend(crate::load::include(&filename)?);
+  }
+  if body.is_empty() {
+    return Ok(ProcedureValue::Expression(void!()));
+  }
+  _evaluate_procedure_body(&body, scope)
+}
+pub const INCLUDE: Procedure = Procedure::SpecialVariableArgumentForm("include", _include, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::parse::ParseError;
+  use crate::test::{TestContext, TestFiles};
+  use std::fs;
+
+  #[test]
+  fn test_load() {
+    let files = TestFiles::new(
+      "load",
+      &[
+        (
+          "main.lisp",
+          "(load \"lib/double.lisp\") (define result (double 21))",
+        ),
+        (
+          "lib/double.lisp",
+          "(load \"square.lisp\") (define double (lambda (x) (+ x x)))",
+        ),
+        ("lib/square.lisp", "(define square (lambda (x) (* x x)))"),
+      ],
+    );
+    let ctx = TestContext::new();
+    // Files load the files they refer to from their own directory
+    ctx.assert_eq(&format!("(load \"{}\")", files.path("main.lisp")), void!());
+    ctx.assert_eq("result", int!(42));
+    ctx.assert_eq("(square 3)", int!(9));
+    // Loading is done at the top level, even from inside of a procedure
+    ctx.exec(&format!(
+      "((lambda () (load \"{}\")))",
+      files.path("lib/square.lisp")
+    ));
+    ctx.exec("(define env (scheme-report-environment 5))");
+    ctx.exec(&format!("(load \"{}\" env)", files.path("lib/square.lisp")));
+    ctx.assert_eq("(eval '(square 4) env)", int!(16));
+    ctx.assert_err(
+      "(load \"does-not-exist.lisp\")",
+      EvaluationError::FileError(
+        "does-not-exist.lisp".to_string(),
+        "file not found".to_string(),
+      ),
+    );
+    ctx.assert_err(
+      "(load 'main)",
+      EvaluationError::invalid_argument("load", "string", &symbol!("main")),
+    );
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let files = TestFiles::new(
+      "load-errors",
+      &[
+        ("a.lisp", "(load \"b.lisp\")"),
+        ("b.lisp", "(load \"a.lisp\")"),
+        ("unbalanced.lisp", "(define x 1) (car"),
+        ("raise.lisp", "(raise 'loaded)"),
+        (
+          "escape.lisp",
+          "(define before 1) (escape 'escaped) (define after 2)",
+        ),
+      ],
+    );
+    let ctx = TestContext::new();
+    let canonical = |filename: &str| {
+      fs::canonicalize(files.path(filename))
+        .unwrap()
+        .display()
+        .to_string()
+    };
+    ctx.assert_err(
+      &format!("(load \"{}\")", files.path("a.lisp")),
+      EvaluationError::LoadCycle(vec![
+        canonical("a.lisp"),
+        canonical("b.lisp"),
+        canonical("a.lisp"),
+      ]),
+    );
+    // A failed load doesn't leave the file marked as being loaded
+    ctx.assert_err(
+      &format!("(load \"{}\")", files.path("b.lisp")),
+      EvaluationError::LoadCycle(vec![
+        canonical("b.lisp"),
+        canonical("a.lisp"),
+        canonical("b.lisp"),
+      ]),
+    );
+    ctx.assert_err(
+      &format!("(load \"{}\")", files.path("unbalanced.lisp")),
+      EvaluationError::ParseError(files.path("unbalanced.lisp"), ParseError::UnexpectedEOF),
+    );
+    ctx.assert_eq(
+      &format!("(guard (e (#t e)) (load \"{}\"))", files.path("raise.lisp")),
+      symbol!("loaded"),
+    );
+    // Continuations can escape from a file being loaded, which is then no longer being loaded
+    let escape = format!("(load \"{}\")", files.path("escape.lisp"));
+    ctx.assert_eq(
+      &format!(
+        "(call/cc (lambda (k) (eval (cons 'define (cons 'escape (cons k '())))) {}))",
+        escape
+      ),
+      symbol!("escaped"),
+    );
+    ctx.assert_eq("before", int!(1));
+    ctx.assert_err(
+      "after",
+      EvaluationError::UndefinedSymbol("after".to_string()),
+    );
+    ctx.assert_eq(&escape, symbol!("escaped"));
+  }
+
+  #[test]
+  fn test_include() {
+    let files = TestFiles::new(
+      "include",
+      &[
+        ("main.lisp", "(include \"parts.lisp\") (define c 3)"),
+        ("parts.lisp", "(define a 1) (define b 2)"),
+        ("self.lisp", "(include \"self.lisp\")"),
+        (
+          "lib/sum.lisp",
+          "(define sum (lambda () (include \"terms.lisp\")))",
+        ),
+        ("lib/terms.lisp", "(+ 1 2)"),
+      ],
+    );
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      &format!("(include \"{}\")", files.path("main.lisp")),
+      void!(),
+    );
+    ctx.assert_eq("(+ a b c)", int!(6));
+    // Included definitions go in the scope of the include, unlike load
+    ctx.assert_eq(
+      &format!(
+        "((lambda (a) (include \"{}\") (+ a b)) 10)",
+        files.path("parts.lisp")
+      ),
+      int!(3),
+    );
+    ctx.exec("(define a 5)");
+    ctx.assert_eq(
+      &format!("((lambda () (include \"{}\") a))", files.path("parts.lisp")),
+      int!(1),
+    );
+    ctx.assert_eq("a", int!(5));
+    let self_path = fs::canonicalize(files.path("self.lisp"))
+      .unwrap()
+      .display()
+      .to_string();
+    ctx.assert_err(
+      &format!("(include \"{}\")", files.path("self.lisp")),
+      EvaluationError::LoadCycle(vec![self_path.clone(), self_path]),
+    );
+    // Every time an include is compiled, the file is read again
+    let changing = files.path("changing.lisp");
+    fs::write(&changing, "1").unwrap();
+    let include = format!("(eval '(include \"{}\"))", changing);
+    ctx.assert_eq(&include, int!(1));
+    fs::write(&changing, "2").unwrap();
+    ctx.assert_eq(&include, int!(2));
+    // Includes are found next to the file they are in, and read when they are compiled
+    ctx.exec(&format!("(load \"{}\")", files.path("lib/sum.lisp")));
+    fs::remove_file(files.path("lib/terms.lisp")).unwrap();
+    ctx.assert_eq("(sum)", int!(3));
+    ctx.assert_err(
+      "(include 1)",
+      EvaluationError::invalid_argument("include", "string", &int!(1)),
+    );
+  }
+}
@@ -0,0 +1,230 @@
+use super::bytevector::{byte_arg, bytevector_arg};
+use super::*;
+use crate::port::{current_error_port, current_output_port, Port};
+
+/// The port given as the optional last argument of an output procedure, or the current output
+/// port if there isn't one
+fn output_port_arg(
+  procedure_name: &str,
+  argc: usize,
+  varargs: &[Expression],
+) -> Result<Rc<Port>, EvaluationError> {
+  match varargs {
+    [] => Ok(current_output_port()),
+    [Expression::Port(port)] if port.is_output() && !port.binary => Ok(port.clone()),
+    [non_port] => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "output port",
+      non_port,
+    )),
+    _ => Err(EvaluationError::WrongNumberOfArguments(
+      procedure_name.to_string(),
+      argc + 1,
+      argc + varargs.len(),
+    )),
+  }
+}
+
+/// A binary output port, which has to be given since the standard ports are textual
+fn binary_output_port_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<Rc<Port>, EvaluationError> {
+  match arg {
+    Expression::Port(port) if port.is_output() && port.binary => Ok(port.clone()),
+    non_port => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "binary output port",
+      non_port,
+    )),
+  }
+}
+
+fn _current_output_port(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Port(
+    current_output_port(),
+  )))
+}
+pub const CURRENT_OUTPUT_PORT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("current-output-port", _current_output_port, 0);
+
+fn _current_error_port(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Port(
+    current_error_port(),
+  )))
+}
+pub const CURRENT_ERROR_PORT: Procedure =
+  Procedure::BuiltinFixedArgumentForm("current-error-port", _current_error_port, 0);
+
+fn _display(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = output_port_arg("display", 1, &varargs)?;
+  port.write_str(&format!("{:#}", args.first().unwrap()))?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const DISPLAY: Procedure = Procedure::BuiltinVariableArgumentForm("display", _display, 1);
+
+fn _write(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = output_port_arg("write", 1, &varargs)?;
+  port.write_str(&format!("{}", args.first().unwrap()))?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const WRITE: Procedure = Procedure::BuiltinVariableArgumentForm("write", _write, 1);
+
+fn _write_string(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = output_port_arg("write-string", 1, &varargs)?;
+  match args.first().unwrap() {
+    Expression::String(string) => port.write_str(string)?,
+    non_string => {
+      return Err(EvaluationError::invalid_argument(
+        "write-string",
+        "string",
+        non_string,
+      ))
+    }
+  }
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const WRITE_STRING: Procedure =
+  Procedure::BuiltinVariableArgumentForm("write-string", _write_string, 1);
+
+fn _write_char(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = output_port_arg("write-char", 1, &varargs)?;
+  match args.first().unwrap() {
+    Expression::Char(c) => port.write_str(c.encode_utf8(&mut [0; 4]))?,
+    non_char => {
+      return Err(EvaluationError::invalid_argument(
+        "write-char",
+        "char",
+        non_char,
+      ))
+    }
+  }
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const WRITE_CHAR: Procedure =
+  Procedure::BuiltinVariableArgumentForm("write-char", _write_char, 1);
+
+fn _write_u8(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let byte = byte_arg("write-u8", args.first().unwrap())?;
+  let port = binary_output_port_arg("write-u8", args.get(1).unwrap())?;
+  port.write_bytes(&[byte])?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const WRITE_U8: Procedure = Procedure::BuiltinFixedArgumentForm("write-u8", _write_u8, 2);
+
+fn _write_bytevector(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let bytes = bytevector_arg("write-bytevector", args.first().unwrap())?;
+  let port = binary_output_port_arg("write-bytevector", args.get(1).unwrap())?;
+  port.write_bytes(&bytes.borrow())?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const WRITE_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("write-bytevector", _write_bytevector, 2);
+
+fn _newline(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let port = output_port_arg("newline", 0, &varargs)?;
+  port.write_str("\n")?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const NEWLINE: Procedure = Procedure::BuiltinVariableArgumentForm("newline", _newline, 0);
+
+fn _flush_output_port(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  // Binary ports can be flushed too
+  let port = match varargs.as_slice() {
+    [Expression::Port(port)] if port.is_output() => port.clone(),
+    _ => output_port_arg("flush-output-port", 0, &varargs)?,
+  };
+  port.flush()?;
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const FLUSH_OUTPUT_PORT: Procedure =
+  Procedure::BuiltinVariableArgumentForm("flush-output-port", _flush_output_port, 0);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::port::PortState;
+  use crate::test::TestContext;
+
+  fn written(port: &Port) -> String {
+    String::from_utf8(port.written().unwrap()).unwrap()
+  }
+
+  #[test]
+  fn test_output() {
+    let ctx = TestContext::new();
+    let port = Port::new("test", PortState::OutputBytes(vec![]));
+    ctx
+      .scope
+      .borrow_mut()
+      .define("out", Expression::Port(port.clone()));
+    ctx.exec("(display \"a \\\"b\\\"\" out)");
+    ctx.exec("(write-char #\\space out)");
+    ctx.exec("(write \"a \\\"b\\\"\" out)");
+    ctx.exec("(newline out)");
+    ctx.exec("(display '(1 \"x\" #\\y) out)");
+    ctx.exec("(write '(1 \"x\" #\\y #\\space) out)");
+    ctx.exec("(write-string \"done\" out)");
+    ctx.exec("(flush-output-port out)");
+    assert_eq!(
+      written(&port),
+      "a \"b\" \"a \\\"b\\\"\"\n(1 x y)(1 \"x\" #\\y #\\space)done"
+    );
+    ctx.assert_eq("(display 1 out)", void!());
+  }
+
+  #[test]
+  fn test_output_errors() {
+    let ctx = TestContext::new();
+    ctx.assert_err(
+      "(display 1 2)",
+      EvaluationError::invalid_argument("display", "output port", &int!(2)),
+    );
+    ctx.assert_err(
+      "(write-string 'a)",
+      EvaluationError::invalid_argument("write-string", "string", &symbol!("a")),
+    );
+    ctx.assert_err(
+      "(write-char \"a\")",
+      EvaluationError::invalid_argument("write-char", "char", &string!("a")),
+    );
+    let port = Port::new("test", PortState::OutputBytes(vec![]));
+    port.close().unwrap();
+    ctx
+      .scope
+      .borrow_mut()
+      .define("closed", Expression::Port(port.clone()));
+    ctx.assert_err(
+      "(newline closed)",
+      EvaluationError::invalid_argument("newline", "output port", &Expression::Port(port)),
+    );
+    ctx.assert_eq(
+      "(eq? (current-output-port) (current-output-port))",
+      boolean!(true),
+    );
+  }
+}
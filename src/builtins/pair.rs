@@ -1,14 +1,14 @@
 use super::*;
 
-fn _cons(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  let left = evaluate(args.get(0).unwrap(), scope.clone())?;
-  let right = evaluate(args.get(1).unwrap(), scope)?;
-  Ok(ProcedureValue::Expression(cons!(&left, &right)))
+fn _cons(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let left = args.first().unwrap();
+  let right = args.get(1).unwrap();
+  Ok(ProcedureValue::Expression(cons!(left, right)))
 }
 pub const CONS: Procedure = Procedure::BuiltinFixedArgumentForm("cons", _cons, 2);
 
-fn _car(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  let arg = evaluate(args.get(0).unwrap(), scope)?;
+fn _car(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let arg = args.first().unwrap().clone();
   if let Expression::Cons(cons) = arg {
     Ok(ProcedureValue::Expression(cons.car.as_ref().clone()))
   } else {
@@ -17,8 +17,8 @@ fn _car(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
 }
 pub const CAR: Procedure = Procedure::BuiltinFixedArgumentForm("car", _car, 1);
 
-fn _cdr(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  let arg = evaluate(args.get(0).unwrap(), scope)?;
+fn _cdr(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let arg = args.first().unwrap().clone();
   if let Expression::Cons(cons) = arg {
     Ok(ProcedureValue::Expression(cons.cdr.as_ref().clone()))
   } else {
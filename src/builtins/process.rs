@@ -0,0 +1,88 @@
+use super::*;
+
+thread_local! {
+  /// The name of the program being run, followed by the arguments given to it
+  static COMMAND_LINE_ARGS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+pub fn set_command_line(command_line: Vec<String>) {
+  COMMAND_LINE_ARGS.with(|line| *line.borrow_mut() = command_line);
+}
+
+fn _command_line(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let command_line = COMMAND_LINE_ARGS.with(|line| line.borrow().clone());
+  Ok(ProcedureValue::Expression(vec_arg(
+    command_line.iter().map(|arg| string!(arg)).collect(),
+  )?))
+}
+pub const COMMAND_LINE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("command-line", _command_line, 0);
+
+/// Exit with a status of 0 for #t or nothing, 1 for #f, or an integer status
+fn _exit(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let status = match varargs.as_slice() {
+    [] | [Expression::Boolean(true)] => 0,
+    [Expression::Boolean(false)] => 1,
+    [Expression::Number(Number::Integer(status))] => *status,
+    [status] => {
+      return Err(EvaluationError::invalid_argument(
+        "exit",
+        "boolean or integer",
+        status,
+      ))
+    }
+    _ => {
+      return Err(EvaluationError::WrongNumberOfArguments(
+        "exit".to_string(),
+        1,
+        varargs.len(),
+      ))
+    }
+  };
+  Err(EvaluationError::Exit(status))
+}
+pub const EXIT: Procedure = Procedure::BuiltinVariableArgumentForm("exit", _exit, 0);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_command_line() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(command-line)", list!());
+    set_command_line(vec!["program.lisp".to_string(), "-x".to_string()]);
+    ctx.assert_eq(
+      "(command-line)",
+      list!(string!("program.lisp"), string!("-x")),
+    );
+  }
+
+  #[test]
+  fn test_exit() {
+    let ctx = TestContext::new();
+    ctx.assert_err("(exit)", EvaluationError::Exit(0));
+    ctx.assert_err("(exit #f)", EvaluationError::Exit(1));
+    ctx.assert_err("(exit 3)", EvaluationError::Exit(3));
+    ctx.assert_err(
+      "(exit 'a)",
+      EvaluationError::invalid_argument("exit", "boolean or integer", &symbol!("a")),
+    );
+    // Exiting can't be handled, but dynamic-winds are left on the way out
+    ctx.exec("(define out (open-output-string))");
+    ctx.assert_err(
+      "(guard (e (#t (display \"handled \" out)))
+         (dynamic-wind
+           (lambda () (display \"in \" out))
+           (lambda () (exit 2))
+           (lambda () (display \"out\" out))))",
+      EvaluationError::Exit(2),
+    );
+    ctx.assert_eq("(get-output-string out)", string!("in out"));
+  }
+}
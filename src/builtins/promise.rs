@@ -0,0 +1,244 @@
+use super::*;
+
+fn _delay(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let expression = args.first().unwrap().clone();
+  Ok(ProcedureValue::Expression(Expression::Promise(
+    Promise::new(PromiseState::Delayed(expression, scope, false)),
+  )))
+}
+pub const DELAY: Procedure = Procedure::SpecialFixedArgumentForm("delay", _delay, 1);
+
+fn _delay_force(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let expression = args.first().unwrap().clone();
+  Ok(ProcedureValue::Expression(Expression::Promise(
+    Promise::new(PromiseState::Delayed(expression, scope, true)),
+  )))
+}
+pub const DELAY_FORCE: Procedure =
+  Procedure::SpecialFixedArgumentForm("delay-force", _delay_force, 1);
+
+fn _make_promise(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  match args.first().unwrap() {
+    promise @ Expression::Promise(_) => Ok(ProcedureValue::Expression(promise.clone())),
+    value => Ok(ProcedureValue::Expression(Expression::Promise(
+      Promise::new(PromiseState::Forced(value.clone())),
+    ))),
+  }
+}
+pub const MAKE_PROMISE: Procedure =
+  Procedure::BuiltinFixedArgumentForm("make-promise", _make_promise, 1);
+
+fn _is_promise(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(matches!(
+    args.first().unwrap(),
+    Expression::Promise(_)
+  ))))
+}
+pub const IS_PROMISE: Procedure = Procedure::BuiltinFixedArgumentForm("promise?", _is_promise, 1);
+
+/// Force a promise, remembering its value.
+/// A delay-force hands its promise over to the promise it evaluates to, and then that one is
+/// forced in its place, so a chain of delay-forces doesn't grow the stack.
+fn force(promise: &Rc<Promise>) -> ProcedureResult {
+  let promise = Promise::resolve(promise);
+  let (expression, scope, delay_force) = match &*promise.0.borrow() {
+    PromiseState::Forced(value) => return Ok(ProcedureValue::Expression(value.clone())),
+    PromiseState::Delayed(expression, scope, delay_force) => {
+      (expression.clone(), scope.clone(), *delay_force)
+    }
+    PromiseState::Forwarded(_) => unreachable!("resolved promises are never forwarded"),
+  };
+  Ok(ProcedureValue::Evaluate(
+    expression,
+    scope,
+    Continue::new(move |value| {
+      let promise = Promise::resolve(&promise);
+      // Evaluating the expression may have forced this promise already, and the first value wins
+      if let PromiseState::Forced(forced) = &*promise.0.borrow() {
+        return Ok(ProcedureValue::Expression(forced.clone()));
+      }
+      if !delay_force {
+        *promise.0.borrow_mut() = PromiseState::Forced(value.clone());
+        return Ok(ProcedureValue::Expression(value));
+      }
+      let next = match value {
+        Expression::Promise(next) => Promise::resolve(&next),
+        non_promise => {
+          return Err(EvaluationError::invalid_argument(
+            "delay-force",
+            "promise",
+            &non_promise,
+          ))
+        }
+      };
+      if next != promise {
+        let state = std::mem::replace(
+          &mut *next.0.borrow_mut(),
+          PromiseState::Forwarded(promise.clone()),
+        );
+        *promise.0.borrow_mut() = state;
+      }
+      force(&promise)
+    }),
+  ))
+}
+
+fn _force(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  match args.first().unwrap() {
+    Expression::Promise(promise) => force(promise),
+    // Forcing something which isn't a promise just gives it back
+    value => Ok(ProcedureValue::Expression(value.clone())),
+  }
+}
+pub const FORCE: Procedure = Procedure::BuiltinFixedArgumentForm("force", _force, 1);
+
+fn _cons_stream(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let rest = args.get(1).unwrap().clone();
+  let rest_scope = scope.clone();
+  Ok(ProcedureValue::Evaluate(
+    args.first().unwrap().clone(),
+    scope,
+    Continue::new(move |first| {
+      let rest = Promise::new(PromiseState::Delayed(
+        rest.clone(),
+        rest_scope.clone(),
+        false,
+      ));
+      Ok(ProcedureValue::Expression(cons!(
+        &first,
+        &Expression::Promise(rest)
+      )))
+    }),
+  ))
+}
+pub const CONS_STREAM: Procedure =
+  Procedure::SpecialFixedArgumentForm("cons-stream", _cons_stream, 2);
+
+/// The first item and the promise of the rest of a stream
+fn stream_arg(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<(Expression, Rc<Promise>), EvaluationError> {
+  if let Expression::Cons(cons) = arg {
+    if let Expression::Promise(rest) = cons.cdr.as_ref() {
+      return Ok((cons.car.as_ref().clone(), rest.clone()));
+    }
+  }
+  Err(EvaluationError::invalid_argument(
+    procedure_name,
+    "stream",
+    arg,
+  ))
+}
+
+fn _stream_car(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let (first, _) = stream_arg("stream-car", args.first().unwrap())?;
+  Ok(ProcedureValue::Expression(first))
+}
+pub const STREAM_CAR: Procedure = Procedure::BuiltinFixedArgumentForm("stream-car", _stream_car, 1);
+
+fn _stream_cdr(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let (_, rest) = stream_arg("stream-cdr", args.first().unwrap())?;
+  force(&rest)
+}
+pub const STREAM_CDR: Procedure = Procedure::BuiltinFixedArgumentForm("stream-cdr", _stream_cdr, 1);
+
+fn _is_stream_pair(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(stream_arg(
+    "stream-pair?",
+    args.first().unwrap()
+  )
+  .is_ok())))
+}
+pub const IS_STREAM_PAIR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("stream-pair?", _is_stream_pair, 1);
+
+fn _is_stream_null(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(boolean!(
+    args.first().unwrap() == &null!()
+  )))
+}
+pub const IS_STREAM_NULL: Procedure =
+  Procedure::BuiltinFixedArgumentForm("stream-null?", _is_stream_null, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  thread_local! {
+    static COUNT: RefCell<i32> = const { RefCell::new(0) };
+  }
+
+  /// Count how many times it is called
+  fn _tick(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+    COUNT.with(|count| *count.borrow_mut() += 1);
+    Ok(ProcedureValue::Expression(int!(
+      COUNT.with(|count| *count.borrow())
+    )))
+  }
+
+  #[test]
+  fn test_delay_force() {
+    let ctx = TestContext::new();
+    ctx.scope.borrow_mut().define(
+      "tick",
+      Expression::Procedure(Procedure::BuiltinFixedArgumentForm("tick", _tick, 0)),
+    );
+    ctx.exec("(define p (delay (cons (tick) 'forced)))");
+    ctx.assert_eq("(promise? p)", boolean!(true));
+    ctx.assert_eq("(force p)", cons!(&int!(1), &symbol!("forced")));
+    // The value is remembered rather than evaluated again
+    ctx.assert_eq("(force p)", cons!(&int!(1), &symbol!("forced")));
+    ctx.assert_eq("(force (delay-force (delay (tick))))", int!(2));
+    ctx.assert_eq("(force (make-promise 1))", int!(1));
+    ctx.assert_eq("(eq? (make-promise p) p)", boolean!(true));
+    ctx.assert_eq("(force 1)", int!(1));
+    ctx.assert_eq("(promise? 1)", boolean!(false));
+    ctx.assert_err(
+      "(force (delay-force 1))",
+      EvaluationError::invalid_argument("delay-force", "promise", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_delay_force_chain() {
+    let ctx = TestContext::new();
+    // Each delay-force hands over to the next promise instead of waiting on it
+    ctx.exec(
+      "
+(define count-down (lambda (n)
+  (delay-force (cond ((= n 0) (delay 'done)) (else (count-down (- n 1)))))))",
+    );
+    crate::evaluate::set_max_stack_depth(1000);
+    let result = evaluate(
+      &parse("(force (count-down 10000))").unwrap(),
+      ctx.scope.clone(),
+    );
+    crate::evaluate::set_max_stack_depth(crate::evaluate::DEFAULT_MAX_STACK_DEPTH);
+    assert_eq!(result, Ok(symbol!("done")));
+  }
+
+  #[test]
+  fn test_streams() {
+    let ctx = TestContext::new();
+    ctx.exec("(define integers-from (lambda (n) (cons-stream n (integers-from (+ n 1)))))");
+    ctx.exec(
+      "
+(define stream-ref (lambda (stream n)
+  (cond ((= n 0) (stream-car stream)) (else (stream-ref (stream-cdr stream) (- n 1))))))",
+    );
+    ctx.assert_eq("(stream-ref (integers-from 0) 10000)", int!(10000));
+    ctx.assert_eq("(stream-pair? (integers-from 0))", boolean!(true));
+    ctx.assert_eq("(stream-pair? (cons 1 2))", boolean!(false));
+    ctx.assert_eq("(stream-null? the-empty-stream)", boolean!(true));
+    ctx.assert_eq(
+      "(stream-null? (stream-cdr (cons-stream 1 the-empty-stream)))",
+      boolean!(true),
+    );
+    ctx.assert_err(
+      "(stream-cdr '(1 2))",
+      EvaluationError::invalid_argument("stream-cdr", "stream", &list!(int!(1), int!(2))),
+    );
+  }
+}
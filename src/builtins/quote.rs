@@ -1,10 +1,10 @@
 use super::*;
 
 fn _quote(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  Ok(ProcedureValue::Expression(args.get(0).unwrap().clone()))
+  Ok(ProcedureValue::Expression(args.first().unwrap().clone()))
 }
 
-pub const QUOTE: Procedure = Procedure::BuiltinFixedArgumentForm("quote", _quote, 1);
+pub const QUOTE: Procedure = Procedure::SpecialFixedArgumentForm("quote", _quote, 1);
 
 #[cfg(test)]
 mod test {
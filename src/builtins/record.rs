@@ -0,0 +1,237 @@
+use super::*;
+
+fn symbol_arg(arg: &Expression) -> Result<String, EvaluationError> {
+  match arg {
+    Expression::Symbol(symbol) => Ok(symbol.clone()),
+    non_symbol => Err(EvaluationError::invalid_argument(
+      "define-record-type",
+      "symbol",
+      non_symbol,
+    )),
+  }
+}
+
+/// Call a procedure made by define-record-type
+pub fn apply_record_procedure(
+  procedure_name: &str,
+  record_procedure: &RecordProcedure,
+  args: Vec<Expression>,
+) -> EvaluationResult {
+  let record_arg = |record_type: &Rc<RecordType>| match args.first().unwrap() {
+    Expression::Record(record) if &record.record_type == record_type => Ok(record.clone()),
+    non_record => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      &record_type.name,
+      non_record,
+    )),
+  };
+  match record_procedure {
+    RecordProcedure::Constructor(record_type, indices) => {
+      let mut fields = vec![void!(); record_type.fields.len()];
+      for (index, arg) in indices.iter().zip(args) {
+        fields[*index] = arg;
+      }
+      Ok(Expression::Record(Rc::new(Record {
+        record_type: record_type.clone(),
+        fields: RefCell::new(fields),
+      })))
+    }
+    RecordProcedure::Predicate(record_type) => Ok(boolean!(record_arg(record_type).is_ok())),
+    RecordProcedure::Accessor(record_type, index) => {
+      Ok(record_arg(record_type)?.fields.borrow()[*index].clone())
+    }
+    RecordProcedure::Modifier(record_type, index) => {
+      record_arg(record_type)?.fields.borrow_mut()[*index] = args.get(1).unwrap().clone();
+      Ok(void!())
+    }
+  }
+}
+
+fn define_record_procedure(
+  scope: &mut Scope,
+  procedure_name: &str,
+  record_procedure: RecordProcedure,
+) {
+  let procedure = Procedure::Record(procedure_name.to_string(), record_procedure);
+  scope.define(procedure_name, Expression::Procedure(procedure));
+}
+
+fn _define_record_type(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let type_name = symbol_arg(args.first().unwrap())?;
+  // <point> makes records named point
+  let name = type_name
+    .strip_prefix('<')
+    .and_then(|name| name.strip_suffix('>'))
+    .unwrap_or(&type_name)
+    .to_string();
+  // Each field is (field accessor) or (field accessor modifier)
+  let mut fields = vec![];
+  let mut field_procedures = vec![];
+  for field_spec in &varargs {
+    let field_spec_vec = arg_vec("define-record-type", field_spec)?;
+    match field_spec_vec.as_slice() {
+      [field, accessor] => {
+        fields.push(symbol_arg(field)?);
+        field_procedures.push((symbol_arg(accessor)?, None));
+      }
+      [field, accessor, modifier] => {
+        fields.push(symbol_arg(field)?);
+        field_procedures.push((symbol_arg(accessor)?, Some(symbol_arg(modifier)?)));
+      }
+      _ => {
+        return Err(EvaluationError::invalid_argument(
+          "define-record-type",
+          "(field accessor [modifier])",
+          field_spec,
+        ))
+      }
+    }
+  }
+  // The constructor is (constructor field ...), or just constructor to take every field in order
+  let constructor_spec = args.get(1).unwrap();
+  let (constructor, indices) = match constructor_spec {
+    Expression::Symbol(constructor) => (constructor.clone(), (0..fields.len()).collect()),
+    constructor_spec => {
+      let constructor_vec = arg_vec("define-record-type", constructor_spec)?;
+      let (constructor, constructor_fields) = match constructor_vec.split_first() {
+        Some((constructor, constructor_fields)) => (symbol_arg(constructor)?, constructor_fields),
+        None => {
+          return Err(EvaluationError::invalid_argument(
+            "define-record-type",
+            "(constructor field ...)",
+            constructor_spec,
+          ))
+        }
+      };
+      let mut indices = vec![];
+      for field in constructor_fields {
+        let field_name = symbol_arg(field)?;
+        match fields.iter().position(|name| name == &field_name) {
+          Some(index) => indices.push(index),
+          None => {
+            return Err(EvaluationError::invalid_argument(
+              "define-record-type",
+              "field name",
+              field,
+            ))
+          }
+        }
+      }
+      (constructor, indices)
+    }
+  };
+  let predicate = symbol_arg(args.get(2).unwrap())?;
+
+  let record_type = Rc::new(RecordType { name, fields });
+  let scope = &mut scope.borrow_mut();
+  define_record_procedure(
+    scope,
+    &constructor,
+    RecordProcedure::Constructor(record_type.clone(), Rc::new(indices)),
+  );
+  define_record_procedure(
+    scope,
+    &predicate,
+    RecordProcedure::Predicate(record_type.clone()),
+  );
+  for (index, (accessor, modifier)) in field_procedures.into_iter().enumerate() {
+    define_record_procedure(
+      scope,
+      &accessor,
+      RecordProcedure::Accessor(record_type.clone(), index),
+    );
+    if let Some(modifier) = modifier {
+      define_record_procedure(
+        scope,
+        &modifier,
+        RecordProcedure::Modifier(record_type.clone(), index),
+      );
+    }
+  }
+  Ok(ProcedureValue::Expression(void!()))
+}
+pub const DEFINE_RECORD_TYPE: Procedure =
+  Procedure::SpecialVariableArgumentForm("define-record-type", _define_record_type, 3);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_define_record_type() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(define-record-type <point> (make-point x y) point? (x point-x set-point-x!) (y point-y))",
+      void!(),
+    );
+    ctx.exec("(define p (make-point 1 2))");
+    ctx.assert_eq("(point? p)", boolean!(true));
+    ctx.assert_eq("(point? (cons 1 2))", boolean!(false));
+    ctx.assert_eq("(point-x p)", int!(1));
+    ctx.assert_eq("(point-y p)", int!(2));
+    ctx.assert_eq("(set-point-x! p 10)", void!());
+    ctx.assert_eq("(point-x p)", int!(10));
+    ctx.assert_eq("(eq? p p)", boolean!(true));
+    ctx.assert_eq("(eq? p (make-point 10 2))", boolean!(false));
+    assert_eq!(
+      format!(
+        "{}",
+        evaluate(&parse("p").unwrap(), ctx.scope.clone()).unwrap()
+      ),
+      "#<point 10 2>"
+    );
+    assert_eq!(
+      format!(
+        "{}",
+        evaluate(&parse("point-x").unwrap(), ctx.scope.clone()).unwrap()
+      ),
+      "#<procedure:point-x>"
+    );
+    // Records of different types with the same fields are still different
+    ctx.exec("(define-record-type <vector> (make-vector x y) vector? (x vector-x) (y vector-y))");
+    ctx.assert_eq("(vector? p)", boolean!(false));
+    ctx.assert_err(
+      "(vector-x p)",
+      EvaluationError::invalid_argument(
+        "vector-x",
+        "vector",
+        &evaluate(&parse("p").unwrap(), ctx.scope.clone()).unwrap(),
+      ),
+    );
+    ctx.assert_err(
+      "(make-point 1)",
+      EvaluationError::WrongNumberOfArguments("make-point".to_string(), 2, 1),
+    );
+  }
+
+  #[test]
+  fn test_define_record_type_constructors() {
+    let ctx = TestContext::new();
+    // Fields left out of the constructor start out void
+    ctx.exec("(define-record-type node (make-node value) node? (value node-value) (next node-next set-node-next!))");
+    ctx.assert_eq("(node-next (make-node 1))", void!());
+    ctx.exec("(define-record-type pair make-pair pair? (left pair-left) (right pair-right))");
+    ctx.assert_eq("(pair-right (make-pair 1 2))", int!(2));
+    ctx.assert_err(
+      "(define-record-type thing (make-thing y) thing? (x thing-x))",
+      EvaluationError::invalid_argument("define-record-type", "field name", &symbol!("y")),
+    );
+    ctx.assert_err(
+      "(define-record-type thing make-thing thing? (x))",
+      EvaluationError::invalid_argument(
+        "define-record-type",
+        "(field accessor [modifier])",
+        &list!(symbol!("x")),
+      ),
+    );
+    ctx.assert_err(
+      "(define-record-type 1 make-thing thing?)",
+      EvaluationError::invalid_argument("define-record-type", "symbol", &int!(1)),
+    );
+  }
+}
@@ -0,0 +1,200 @@
+use super::bytevector::bytevector_arg;
+use super::*;
+use crate::port::{set_current_output_port, Input, Port, PortState};
+use std::io::Cursor;
+
+/// A port which reads from a copy of some bytes
+fn input_port(bytes: Vec<u8>) -> PortState {
+  PortState::Input(Input::new(Box::new(Cursor::new(bytes)), false))
+}
+
+/// What has been written to a string or bytevector output port
+fn written_arg(
+  procedure_name: &str,
+  arg: &Expression,
+  binary: bool,
+) -> Result<Vec<u8>, EvaluationError> {
+  match arg {
+    Expression::Port(port) if port.binary == binary => {
+      if let Some(written) = port.written() {
+        return Ok(written);
+      }
+    }
+    _ => (),
+  }
+  Err(EvaluationError::invalid_argument(
+    procedure_name,
+    if binary {
+      "bytevector output port"
+    } else {
+      "string output port"
+    },
+    arg,
+  ))
+}
+
+fn _open_input_string(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  match args.first().unwrap() {
+    Expression::String(string) => Ok(ProcedureValue::Expression(Expression::Port(Port::new(
+      "string",
+      input_port(string.as_bytes().to_vec()),
+    )))),
+    non_string => Err(EvaluationError::invalid_argument(
+      "open-input-string",
+      "string",
+      non_string,
+    )),
+  }
+}
+pub const OPEN_INPUT_STRING: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-input-string", _open_input_string, 1);
+
+fn _open_output_string(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Port(Port::new(
+    "string",
+    PortState::OutputBytes(vec![]),
+  ))))
+}
+pub const OPEN_OUTPUT_STRING: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-output-string", _open_output_string, 0);
+
+fn _get_output_string(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let written = written_arg("get-output-string", args.first().unwrap(), false)?;
+  // Only strings are written to textual ports, so what was written is always UTF-8
+  Ok(ProcedureValue::Expression(Expression::String(
+    String::from_utf8_lossy(&written).into_owned(),
+  )))
+}
+pub const GET_OUTPUT_STRING: Procedure =
+  Procedure::BuiltinFixedArgumentForm("get-output-string", _get_output_string, 1);
+
+fn _open_input_bytevector(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let bytes = bytevector_arg("open-input-bytevector", args.first().unwrap())?;
+  let bytes = bytes.borrow().clone();
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::new_binary("bytevector", input_port(bytes)),
+  )))
+}
+pub const OPEN_INPUT_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-input-bytevector", _open_input_bytevector, 1);
+
+fn _open_output_bytevector(_args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(Expression::Port(
+    Port::new_binary("bytevector", PortState::OutputBytes(vec![])),
+  )))
+}
+pub const OPEN_OUTPUT_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("open-output-bytevector", _open_output_bytevector, 0);
+
+fn _get_output_bytevector(args: Vec<Expression>, _scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let written = written_arg("get-output-bytevector", args.first().unwrap(), true)?;
+  Ok(ProcedureValue::Expression(bytevector!(written)))
+}
+pub const GET_OUTPUT_BYTEVECTOR: Procedure =
+  Procedure::BuiltinFixedArgumentForm("get-output-bytevector", _get_output_bytevector, 1);
+
+/// Call a thunk with a string port as the current output port, and return everything it wrote.
+/// Like with-output-to-file, the thunk is evaluated to completion on its own, so the current
+/// output port is always put back.
+fn _with_output_to_string(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let thunk = procedure_arg("with-output-to-string", args.first().unwrap())?;
+  let port = Port::new("string", PortState::OutputBytes(vec![]));
+  // The string port is the current output port whenever the thunk is running, however it is
+  // left and come back to
+  let previous = Rc::new(RefCell::new(None));
+  let replaced = previous.clone();
+  let current = port.clone();
+  let inside = ProcedureValue::Evaluate(
+    list!(Expression::Procedure(thunk)),
+    scope.clone(),
+    Continue::new(move |_| {
+      Ok(ProcedureValue::Expression(Expression::String(
+        String::from_utf8_lossy(&port.written().unwrap_or_default()).into_owned(),
+      )))
+    }),
+  );
+  Ok(ProcedureValue::DynamicWind(
+    rust_thunk("with-output-to-string", move || {
+      *replaced.borrow_mut() = Some(set_current_output_port(current.clone()));
+      Ok(())
+    }),
+    Box::new(inside),
+    rust_thunk("with-output-to-string", move || {
+      if let Some(previous) = previous.borrow_mut().take() {
+        set_current_output_port(previous);
+      }
+      Ok(())
+    }),
+    scope,
+  ))
+}
+pub const WITH_OUTPUT_TO_STRING: Procedure =
+  Procedure::BuiltinFixedArgumentForm("with-output-to-string", _with_output_to_string, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_string_ports() {
+    let ctx = TestContext::new();
+    ctx.exec("(define in (open-input-string \"(a \\\"b\\\") 12 rest\"))");
+    ctx.assert_eq("(read in)", list!(symbol!("a"), string!("b")));
+    ctx.assert_eq("(read in)", int!(12));
+    ctx.assert_eq("(read-line in)", string!(" rest"));
+    ctx.assert_eq("(eof-object? (read in))", boolean!(true));
+    ctx.exec("(define out (open-output-string))");
+    ctx.exec("(write 'a out)");
+    ctx.exec("(write \"b\" out)");
+    ctx.assert_eq("(get-output-string out)", string!("a\"b\""));
+    ctx.exec("(display #\\c out)");
+    ctx.assert_eq("(get-output-string out)", string!("a\"b\"c"));
+    ctx.assert_err(
+      "(get-output-string (current-output-port))",
+      EvaluationError::invalid_argument(
+        "get-output-string",
+        "string output port",
+        &Expression::Port(crate::port::current_output_port()),
+      ),
+    );
+  }
+
+  #[test]
+  fn test_bytevector_ports() {
+    let ctx = TestContext::new();
+    ctx.exec("(define in (open-input-bytevector #u8(1 2 3)))");
+    ctx.assert_eq("(read-u8 in)", int!(1));
+    ctx.assert_eq("(read-bytevector 5 in)", bytevector!(vec![2, 3]));
+    ctx.exec("(define out (open-output-bytevector))");
+    ctx.exec("(write-u8 7 out)");
+    ctx.exec("(write-bytevector #u8(8 9) out)");
+    ctx.assert_eq("(get-output-bytevector out)", bytevector!(vec![7, 8, 9]));
+  }
+
+  #[test]
+  fn test_with_output_to_string() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(with-output-to-string (lambda () (display \"a\") (write \"b\") (newline)))",
+      string!("a\"b\"\n"),
+    );
+    ctx.exec("(define stdout (current-output-port))");
+    ctx.assert_err(
+      "(with-output-to-string (lambda () (car 1)))",
+      EvaluationError::invalid_argument("car", "list", &int!(1)),
+    );
+    ctx.assert_eq("(eq? stdout (current-output-port))", boolean!(true));
+    ctx.assert_eq(
+      "(+ 1 (call/cc (lambda (k) (with-output-to-string (lambda () (k 41))))))",
+      int!(42),
+    );
+    ctx.assert_eq("(eq? stdout (current-output-port))", boolean!(true));
+    // The thunk runs on the same stack as everything else, so it can recurse deeply
+    ctx.exec(
+      "(define nest (lambda (n) (cond ((= n 0) \"done\") (#t (with-output-to-string (lambda () (display (nest (- n 1)))))))))",
+    );
+    ctx.assert_eq("(nest 100000)", string!("done"));
+    ctx.assert_output("(display (+ 1 2)) (newline) (write #\\a)", "3\n#\\a");
+  }
+}
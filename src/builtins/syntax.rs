@@ -0,0 +1,323 @@
+use super::*;
+use crate::macros::SyntaxRules;
+
+fn _syntax_rules(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let mut spec = args;
+  spec.extend(varargs);
+  Ok(ProcedureValue::Expression(Expression::Macro(Rc::new(
+    SyntaxRules::new(&spec)?,
+  ))))
+}
+pub const SYNTAX_RULES: Procedure =
+  Procedure::SpecialVariableArgumentForm("syntax-rules", _syntax_rules, 1);
+
+/// Evaluate an expression which should produce a macro, like (syntax-rules ...), and then pass
+/// the macro on to continue_with
+fn evaluate_transformer(
+  procedure_name: &'static str,
+  transformer: &Expression,
+  scope: Rc<RefCell<Scope>>,
+  continue_with: impl Fn(Expression) -> ProcedureResult + 'static,
+) -> ProcedureResult {
+  Ok(ProcedureValue::Evaluate(
+    transformer.clone(),
+    scope,
+    Continue::new(move |transformer| match transformer {
+      Expression::Macro(_) => continue_with(transformer),
+      non_macro => Err(EvaluationError::invalid_argument(
+        procedure_name,
+        "syntax transformer",
+        &non_macro,
+      )),
+    }),
+  ))
+}
+
+fn _define_syntax(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let keyword = args.first().unwrap();
+  if let Expression::Symbol(keyword) = keyword {
+    let keyword = keyword.clone();
+    let transformer = args.get(1).unwrap();
+    evaluate_transformer(
+      "define-syntax",
+      transformer,
+      scope.clone(),
+      move |transformer| {
+        scope.borrow_mut().define(&keyword, transformer);
+        Ok(ProcedureValue::Expression(void!()))
+      },
+    )
+  } else {
+    Err(EvaluationError::invalid_argument(
+      "define-syntax",
+      "symbol",
+      keyword,
+    ))
+  }
+}
+pub const DEFINE_SYNTAX: Procedure =
+  Procedure::SpecialFixedArgumentForm("define-syntax", _define_syntax, 2);
+
+type SyntaxBindings = Rc<Vec<(String, Expression)>>;
+
+/// Bind the keywords of a let-syntax or letrec-syntax in block_scope one at a time, evaluating
+/// the transformers in transformer_scope, and then evaluate the body in block_scope.
+fn bind_syntax(
+  procedure_name: &'static str,
+  bindings: SyntaxBindings,
+  index: usize,
+  body: Rc<Vec<Expression>>,
+  transformer_scope: Rc<RefCell<Scope>>,
+  block_scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  match bindings.get(index).cloned() {
+    Some((keyword, transformer)) => evaluate_transformer(
+      procedure_name,
+      &transformer,
+      transformer_scope.clone(),
+      move |transformer| {
+        block_scope.borrow_mut().define(&keyword, transformer);
+        bind_syntax(
+          procedure_name,
+          bindings.clone(),
+          index + 1,
+          body.clone(),
+          transformer_scope.clone(),
+          block_scope.clone(),
+        )
+      },
+    ),
+    None => _evaluate_procedure_body(&body, block_scope),
+  }
+}
+
+/// Validate the bindings and body of a let-syntax or letrec-syntax
+fn syntax_bindings(
+  procedure_name: &str,
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+) -> Result<(SyntaxBindings, Rc<Vec<Expression>>), EvaluationError> {
+  let mut bindings = vec![];
+  for binding in arg_vec(procedure_name, args.first().unwrap())? {
+    match arg_vec(procedure_name, &binding)?.as_slice() {
+      [Expression::Symbol(keyword), transformer] => {
+        bindings.push((keyword.clone(), transformer.clone()))
+      }
+      _ => {
+        return Err(EvaluationError::invalid_argument(
+          procedure_name,
+          "(keyword transformer)",
+          &binding,
+        ))
+      }
+    }
+  }
+  let mut body = varargs;
+  body.insert(0, args.get(1).unwrap().clone());
+  Ok((Rc::new(bindings), Rc::new(body)))
+}
+
+fn _let_syntax(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let (bindings, body) = syntax_bindings("let-syntax", args, varargs)?;
+  let block_scope = Scope::child(scope.clone());
+  bind_syntax("let-syntax", bindings, 0, body, scope, block_scope)
+}
+pub const LET_SYNTAX: Procedure =
+  Procedure::SpecialVariableArgumentForm("let-syntax", _let_syntax, 2);
+
+fn _letrec_syntax(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let (bindings, body) = syntax_bindings("letrec-syntax", args, varargs)?;
+  let block_scope = Scope::child(scope);
+  bind_syntax(
+    "letrec-syntax",
+    bindings,
+    0,
+    body,
+    block_scope.clone(),
+    block_scope,
+  )
+}
+pub const LETREC_SYNTAX: Procedure =
+  Procedure::SpecialVariableArgumentForm("letrec-syntax", _letrec_syntax, 2);
+
+/// Expand a form once if it is a macro use, otherwise return it unchanged
+fn expand_once(
+  form: &Expression,
+  scope: &Rc<RefCell<Scope>>,
+) -> Result<Option<Expression>, EvaluationError> {
+  if let Expression::Cons(cons) = form {
+    if let Expression::Symbol(keyword) = cons.car.as_ref() {
+      if let Ok(Expression::Macro(syntax_rules)) = scope.borrow().lookup(keyword) {
+        return Ok(Some(syntax_rules.expand(form)?));
+      }
+    }
+  }
+  Ok(None)
+}
+
+fn _macroexpand_1(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let form = args.first().unwrap().clone();
+  let expansion = expand_once(&form, &scope)?;
+  Ok(ProcedureValue::Expression(expansion.unwrap_or(form)))
+}
+pub const MACROEXPAND_1: Procedure =
+  Procedure::BuiltinFixedArgumentForm("macroexpand-1", _macroexpand_1, 1);
+
+fn _macroexpand(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let mut form = args.first().unwrap().clone();
+  while let Some(expansion) = expand_once(&form, &scope)? {
+    form = expansion;
+  }
+  Ok(ProcedureValue::Expression(form))
+}
+pub const MACROEXPAND: Procedure =
+  Procedure::BuiltinFixedArgumentForm("macroexpand", _macroexpand, 1);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_define_syntax() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define-syntax my-if
+  (syntax-rules (then else)
+    ((_ test then consequent else alternative)
+      (cond (test consequent) (else alternative)))))",
+    );
+    ctx.assert_eq("(my-if #t then 1 else 2)", int!(1));
+    ctx.assert_eq("(my-if (eq? 1 2) then 1 else 2)", int!(2));
+    ctx.assert_err(
+      "(my-if #t 1 2)",
+      EvaluationError::NoMatchingSyntaxRule(Box::new(parse("(my-if #t 1 2)").unwrap())),
+    );
+    ctx.assert_err(
+      "(define-syntax 1 (syntax-rules ()))",
+      EvaluationError::invalid_argument("define-syntax", "symbol", &int!(1)),
+    );
+    ctx.assert_err(
+      "(define-syntax foo 1)",
+      EvaluationError::invalid_argument("define-syntax", "syntax transformer", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_define_syntax_ellipsis() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define-syntax let
+  (syntax-rules ()
+    ((_ ((name value) ...) body1 body2 ...)
+      ((lambda (name ...) body1 body2 ...) value ...))))",
+    );
+    ctx.assert_eq("(let ((a 1) (b 2)) (+ a b))", int!(3));
+    ctx.assert_eq("(let () 1 2)", int!(2));
+    ctx.exec(
+      "
+(define-syntax my-and
+  (syntax-rules ()
+    ((_) #t)
+    ((_ e) e)
+    ((_ e1 e2 ...) (cond (e1 (my-and e2 ...)) (else #f)))))",
+    );
+    ctx.assert_eq("(my-and)", boolean!(true));
+    ctx.assert_eq("(my-and 1 2 3)", int!(3));
+    ctx.assert_eq("(my-and 1 #f 3)", boolean!(false));
+    // The keyword isn't matched, so it can't be what an ellipsis repeats
+    ctx.assert_err(
+      "(define-syntax foo (syntax-rules () ((_ ...) 1)))",
+      EvaluationError::invalid_argument(
+        "syntax-rules",
+        "at most one ellipsis after a subpattern",
+        &list!(symbol!("_"), symbol!("...")),
+      ),
+    );
+    ctx.assert_err(
+      "(foo 1 2)",
+      EvaluationError::UndefinedSymbol("foo".to_string()),
+    );
+  }
+
+  #[test]
+  fn test_define_syntax_hygiene() {
+    let ctx = TestContext::new();
+    ctx.exec(
+      "
+(define-syntax my-or
+  (syntax-rules ()
+    ((_) #f)
+    ((_ e) e)
+    ((_ e1 e2 ...) ((lambda (t) (cond (t t) (else (my-or e2 ...)))) e1))))",
+    );
+    ctx.exec("(define t 5)");
+    ctx.assert_eq("(my-or #f t)", int!(5));
+    ctx.assert_eq("(my-or #f #f)", boolean!(false));
+    ctx.assert_eq("(my-or 1 t)", int!(1));
+  }
+
+  #[test]
+  fn test_let_syntax() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(let-syntax ((foo (syntax-rules () ((_ a) (+ a 1))))) (foo 1))",
+      int!(2),
+    );
+    ctx.assert_err("foo", EvaluationError::UndefinedSymbol("foo".to_string()));
+    ctx.exec("(define f (lambda (x) (let-syntax ((double (syntax-rules () ((_ a) (* 2 a))))) (double x))))");
+    ctx.assert_eq("(f 4)", int!(8));
+    ctx.assert_err(
+      "(let-syntax ((foo 1)) 1)",
+      EvaluationError::invalid_argument("let-syntax", "syntax transformer", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_letrec_syntax() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "
+(letrec-syntax
+  ((my-or (syntax-rules ()
+     ((_) #f)
+     ((_ e) e)
+     ((_ e r ...) (cond (e e) (else (my-or r ...)))))))
+  (my-or #f #f 7))",
+      int!(7),
+    );
+  }
+
+  #[test]
+  fn test_macroexpand() {
+    let ctx = TestContext::new();
+    ctx.exec("(define-syntax unless (syntax-rules () ((_ test body ...) (cond (test #f) (else body ...)))))");
+    ctx.exec(
+      "(define-syntax when-not (syntax-rules () ((_ test body ...) (unless test body ...))))",
+    );
+    ctx.assert_eq(
+      "(macroexpand-1 '(when-not #f 1 2))",
+      parse("(unless #f 1 2)").unwrap(),
+    );
+    ctx.assert_eq(
+      "(macroexpand '(when-not #f 1 2))",
+      parse("(cond (#f #f) (else 1 2))").unwrap(),
+    );
+    ctx.assert_eq("(macroexpand '(+ 1 2))", parse("(+ 1 2)").unwrap());
+    ctx.assert_eq("(macroexpand-1 'foo)", symbol!("foo"));
+  }
+}
@@ -0,0 +1,332 @@
+use super::*;
+
+fn _values(
+  _args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  _scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  Ok(ProcedureValue::Expression(vec_values(varargs)))
+}
+pub const VALUES: Procedure = Procedure::BuiltinVariableArgumentForm("values", _values, 0);
+
+fn _call_with_values(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let producer = procedure_arg("call-with-values", args.first().unwrap())?;
+  let consumer = procedure_arg("call-with-values", args.get(1).unwrap())?;
+  let consumer_scope = scope.clone();
+  Ok(ProcedureValue::Evaluate(
+    list!(Expression::Procedure(producer)),
+    scope,
+    Continue::new(move |values| {
+      Ok(ProcedureValue::TailApply(
+        consumer.clone(),
+        values_vec(values),
+        consumer_scope.clone(),
+      ))
+    }),
+  ))
+}
+pub const CALL_WITH_VALUES: Procedure =
+  Procedure::BuiltinFixedArgumentForm("call-with-values", _call_with_values, 2);
+
+/// Symbols to bind values to, in the same shape as the formals of a lambda
+struct Formals {
+  procedure_name: &'static str,
+  names: Vec<String>,
+  rest: Option<String>,
+}
+
+impl Formals {
+  fn new(procedure_name: &'static str, formals: &Expression) -> Result<Formals, EvaluationError> {
+    let mut names = vec![];
+    let mut sublist = formals;
+    while let Expression::Cons(cons) = sublist {
+      match cons.car.as_ref() {
+        Expression::Symbol(name) => names.push(name.clone()),
+        _ => {
+          return Err(EvaluationError::invalid_argument(
+            procedure_name,
+            "list of symbols",
+            formals,
+          ))
+        }
+      }
+      sublist = &cons.cdr;
+    }
+    let rest = match sublist {
+      Expression::Null => None,
+      Expression::Symbol(rest) => Some(rest.clone()),
+      _ => {
+        return Err(EvaluationError::invalid_argument(
+          procedure_name,
+          "symbol",
+          sublist,
+        ))
+      }
+    };
+    Ok(Formals {
+      procedure_name,
+      names,
+      rest,
+    })
+  }
+
+  /// Define each of the values in scope
+  fn bind(&self, values: Expression, scope: &Rc<RefCell<Scope>>) -> Result<(), EvaluationError> {
+    let mut values = values_vec(values);
+    match &self.rest {
+      None if values.len() != self.names.len() => {
+        return Err(EvaluationError::WrongNumberOfArguments(
+          self.procedure_name.to_string(),
+          self.names.len(),
+          values.len(),
+        ))
+      }
+      Some(_) if values.len() < self.names.len() => {
+        return Err(EvaluationError::WrongNumberOfVariableArguments(
+          self.procedure_name.to_string(),
+          self.names.len(),
+          values.len(),
+        ))
+      }
+      _ => (),
+    }
+    let rest = values.split_off(self.names.len());
+    let mut scope = scope.borrow_mut();
+    for (name, value) in self.names.iter().zip(values) {
+      scope.define(name, value);
+    }
+    if let Some(rest_name) = &self.rest {
+      scope.define(rest_name, vec_arg(rest)?);
+    }
+    Ok(())
+  }
+}
+
+type ValuesBindings = Rc<Vec<(Formals, Expression)>>;
+
+/// Validate the bindings of a let-values or let*-values, and the body after them
+fn values_bindings(
+  procedure_name: &'static str,
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+) -> Result<(ValuesBindings, Rc<Vec<Expression>>), EvaluationError> {
+  let mut bindings = vec![];
+  for binding in arg_vec(procedure_name, args.first().unwrap())? {
+    match arg_vec(procedure_name, &binding)?.as_slice() {
+      [formals, init] => bindings.push((Formals::new(procedure_name, formals)?, init.clone())),
+      _ => {
+        return Err(EvaluationError::invalid_argument(
+          procedure_name,
+          "(formals expression)",
+          &binding,
+        ))
+      }
+    }
+  }
+  let mut body = varargs;
+  body.insert(0, args.get(1).unwrap().clone());
+  Ok((Rc::new(bindings), Rc::new(body)))
+}
+
+/// Evaluate the expressions of the bindings starting at index in init_scope, binding the values
+/// in block_scope, and then evaluate the body in block_scope.
+/// If the bindings are sequential, each one is bound in a new scope that the rest of the
+/// bindings are evaluated in.
+fn bind_values(
+  bindings: ValuesBindings,
+  index: usize,
+  body: Rc<Vec<Expression>>,
+  init_scope: Rc<RefCell<Scope>>,
+  block_scope: Rc<RefCell<Scope>>,
+  sequential: bool,
+) -> ProcedureResult {
+  let init = match bindings.get(index) {
+    Some((_, init)) => init.clone(),
+    None => return _evaluate_procedure_body(&body, block_scope),
+  };
+  Ok(ProcedureValue::Evaluate(
+    init,
+    init_scope.clone(),
+    Continue::new(move |values| {
+      let (formals, _) = &bindings[index];
+      let scope = if sequential {
+        Scope::child(init_scope.clone())
+      } else {
+        block_scope.clone()
+      };
+      formals.bind(values, &scope)?;
+      let init_scope = if sequential {
+        scope.clone()
+      } else {
+        init_scope.clone()
+      };
+      bind_values(
+        bindings.clone(),
+        index + 1,
+        body.clone(),
+        init_scope,
+        scope,
+        sequential,
+      )
+    }),
+  ))
+}
+
+fn _let_values(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let (bindings, body) = values_bindings("let-values", args, varargs)?;
+  let block_scope = Scope::child(scope.clone());
+  bind_values(bindings, 0, body, scope, block_scope, false)
+}
+pub const LET_VALUES: Procedure =
+  Procedure::SpecialVariableArgumentForm("let-values", _let_values, 2);
+
+fn _let_star_values(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let (bindings, body) = values_bindings("let*-values", args, varargs)?;
+  let block_scope = Scope::child(scope.clone());
+  bind_values(bindings, 0, body, scope, block_scope, true)
+}
+pub const LET_STAR_VALUES: Procedure =
+  Procedure::SpecialVariableArgumentForm("let*-values", _let_star_values, 2);
+
+fn _define_values(args: Vec<Expression>, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  let formals = Formals::new("define-values", args.first().unwrap())?;
+  Ok(ProcedureValue::Evaluate(
+    args.get(1).unwrap().clone(),
+    scope.clone(),
+    Continue::new(move |values| {
+      formals.bind(values, &scope)?;
+      Ok(ProcedureValue::Expression(void!()))
+    }),
+  ))
+}
+pub const DEFINE_VALUES: Procedure =
+  Procedure::SpecialFixedArgumentForm("define-values", _define_values, 2);
+
+fn _receive(
+  args: Vec<Expression>,
+  varargs: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> ProcedureResult {
+  let formals = Formals::new("receive", args.first().unwrap())?;
+  let mut body = varargs;
+  body.insert(0, args.get(2).unwrap().clone());
+  let block_scope = scope.clone();
+  Ok(ProcedureValue::Evaluate(
+    args.get(1).unwrap().clone(),
+    scope,
+    Continue::new(move |values| {
+      let scope = Scope::child(block_scope.clone());
+      formals.bind(values, &scope)?;
+      _evaluate_procedure_body(&body, scope)
+    }),
+  ))
+}
+pub const RECEIVE: Procedure = Procedure::SpecialVariableArgumentForm("receive", _receive, 3);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_values() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(values 1)", int!(1));
+    ctx.assert_eq("(values 1 2)", Expression::Values(vec![int!(1), int!(2)]));
+    ctx.assert_eq("(values)", Expression::Values(vec![]));
+    ctx.assert_eq(
+      "(call-with-values (lambda () (values 1 2)) cons)",
+      cons!(&int!(1), &int!(2)),
+    );
+    ctx.assert_eq(
+      "(call-with-values (lambda () (values)) (lambda args args))",
+      null!(),
+    );
+    ctx.assert_eq("(call-with-values (lambda () 1) (lambda (x) x))", int!(1));
+    // Continuations take multiple values too
+    ctx.assert_eq(
+      "(call-with-values (lambda () (call/cc (lambda (k) (k 1 2)))) cons)",
+      cons!(&int!(1), &int!(2)),
+    );
+    ctx.assert_err(
+      "(call-with-values (lambda () (values 1 2)) car)",
+      EvaluationError::WrongNumberOfArguments("car".to_string(), 1, 2),
+    );
+    ctx.assert_err(
+      "(call-with-values 1 car)",
+      EvaluationError::invalid_argument("call-with-values", "procedure", &int!(1)),
+    );
+  }
+
+  #[test]
+  fn test_let_values() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(let-values (((a b) (values 1 2)) ((c . d) (values 3 4 5))) (cons (+ a b c) d))",
+      list!(int!(6), int!(4), int!(5)),
+    );
+    ctx.assert_eq(
+      "(let-values ((all (values 1 2))) all)",
+      list!(int!(1), int!(2)),
+    );
+    ctx.exec("(define a 10)");
+    // The expressions of let-values can't see its bindings, but let*-values can
+    ctx.assert_eq(
+      "(let-values (((a) (values 1)) ((b) (values a))) b)",
+      int!(10),
+    );
+    ctx.assert_eq(
+      "(let*-values (((a) (values 1)) ((b) (values a))) b)",
+      int!(1),
+    );
+    ctx.assert_eq("(let*-values () 1 2)", int!(2));
+    ctx.assert_err(
+      "(let-values (((a b) (values 1))) a)",
+      EvaluationError::WrongNumberOfArguments("let-values".to_string(), 2, 1),
+    );
+    ctx.assert_err(
+      "(let-values (((a b . c) 1)) a)",
+      EvaluationError::WrongNumberOfVariableArguments("let-values".to_string(), 2, 1),
+    );
+    ctx.assert_err(
+      "(let-values (((1) 1)) 1)",
+      EvaluationError::invalid_argument("let-values", "list of symbols", &list!(int!(1))),
+    );
+    ctx.assert_err(
+      "(let-values ((a)) 1)",
+      EvaluationError::invalid_argument("let-values", "(formals expression)", &list!(symbol!("a"))),
+    );
+  }
+
+  #[test]
+  fn test_define_values() {
+    let ctx = TestContext::new();
+    ctx.assert_eq("(define-values (a b . c) (values 1 2 3 4))", void!());
+    ctx.assert_eq("a", int!(1));
+    ctx.assert_eq("b", int!(2));
+    ctx.assert_eq("c", list!(int!(3), int!(4)));
+    ctx.assert_err(
+      "(define-values (d) (values))",
+      EvaluationError::WrongNumberOfArguments("define-values".to_string(), 1, 0),
+    );
+  }
+
+  #[test]
+  fn test_receive() {
+    let ctx = TestContext::new();
+    ctx.assert_eq(
+      "(receive (q r) (floor/ 7 2) (cons q r))",
+      cons!(&int!(3), &int!(1)),
+    );
+    ctx.assert_eq("(receive all (values 1 2) all)", list!(int!(1), int!(2)));
+    ctx.assert_err("q", EvaluationError::UndefinedSymbol("q".to_string()));
+  }
+}
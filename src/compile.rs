@@ -0,0 +1,680 @@
+use crate::evaluate::arg_vec;
+use crate::load::{enter, leave, read_included};
+use crate::macros::SyntaxRules;
+use crate::*;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+
+/// How many bodies compile_cached keeps the code for before it starts over, so that programs which
+/// evaluate code they generate don't make the cache grow forever
+const MAX_CACHED_BODIES: usize = 4096;
+
+/// Bodies that have been compiled, and the code they were compiled to
+type CachedBodies = Vec<(Vec<Expression>, Rc<Code>)>;
+
+thread_local! {
+  /// The code compile_cached compiled for each body, by a hash of the body
+  static CACHED_BODIES: RefCell<HashMap<u64, CachedBodies>> = RefCell::new(HashMap::new());
+}
+
+/// A single step of compiled code. Instructions push and pop values on a stack that belongs to
+/// the procedure call being executed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Instruction {
+  /// Push the constant at this index
+  Constant(usize),
+  /// Push the argument at this index of the procedure call this many scopes up. The symbol is
+  /// needed in case something defined it in one of the scopes in between.
+  Argument(usize, usize, usize),
+  /// Push the value of the symbol, which is not an argument of the procedure calls this many
+  /// scopes up
+  Definition(usize, usize),
+  /// Define the symbol as the popped value in the current scope, and push void
+  Define(usize),
+  /// Push a procedure made from the lambda at this index and the current scope
+  Lambda(usize),
+  /// Pop a value, and jump to the instruction if it is #f
+  JumpIfFalse(usize),
+  /// Jump to the instruction if the value is not #f, otherwise pop it
+  JumpIfTrue(usize),
+  Jump(usize),
+  Pop,
+  /// The value is the operator of the combination at this index.
+  /// Special forms and macros are handled here, leaving their value for the instruction after
+  /// the call. Anything else is left for the call once the operands have been evaluated.
+  Operator(usize),
+  /// Pop the operands and the operator and push the value of applying them
+  Call(usize),
+  /// Pop the operands and the operator and return the value of applying them
+  TailCall(usize),
+  /// Pop a value and return it
+  Return,
+}
+
+/// A combination whose operator is not known until it is evaluated
+pub struct Combination {
+  pub form: Expression,
+  /// The number of operands, if the operands are a list
+  pub argc: Option<usize>,
+  /// The instruction after the call
+  pub after: usize,
+  pub tail: bool,
+  /// The expansion from the last time the operator was a macro
+  pub expansion: RefCell<Option<(Rc<SyntaxRules>, Rc<Code>)>>,
+}
+impl fmt::Debug for Combination {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.form)
+  }
+}
+
+/// The names of the arguments of the compiled procedures that code is nested inside of,
+/// innermost first
+#[derive(Debug)]
+pub struct Arguments {
+  names: Rc<Vec<String>>,
+  parent: Environment,
+}
+pub type Environment = Option<Rc<Arguments>>;
+
+#[derive(Debug)]
+pub struct Code {
+  pub instructions: Vec<Instruction>,
+  pub constants: Vec<Expression>,
+  pub symbols: Vec<String>,
+  pub lambdas: Vec<Rc<Lambda>>,
+  pub combinations: Vec<Combination>,
+  /// The environment the code was compiled in, which macro expansions are compiled in as well
+  pub environment: Environment,
+  /// The keywords that were compiled directly, and the special forms they were bound to
+  pub special_forms: Vec<(String, &'static str)>,
+  /// Whether the code depends on nothing but the body it was compiled from and the special forms
+  /// its keywords were bound to, so that it can be used again wherever those are the same
+  pub cacheable: bool,
+}
+
+/// A compiled lambda
+#[derive(Debug)]
+pub struct Lambda {
+  /// The names of the arguments, with the variable argument last
+  pub arguments: Rc<Vec<String>>,
+  pub variable: bool,
+  /// The source of the body, for evaluators that do not use the compiled code
+  pub body: Vec<Expression>,
+  pub code: Rc<Code>,
+}
+// Lambdas are only equal to themselves
+impl PartialEq for Lambda {
+  fn eq(&self, other: &Lambda) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Lambda {}
+impl Lambda {
+  /// The number of arguments that must be given
+  pub fn argc(&self) -> usize {
+    self.arguments.len() - self.variable as usize
+  }
+}
+
+/// Compile an expression to be evaluated in scope.
+/// Combinations whose operator is bound to quote, define, lambda, cond or include in scope at
+/// compile time are compiled directly, so redefining one of those inside the same expression isn't
+/// noticed.
+pub fn compile(
+  expression: &Expression,
+  environment: &Environment,
+  scope: &Rc<RefCell<Scope>>,
+) -> Code {
+  compile_body(std::slice::from_ref(expression), environment, scope)
+}
+
+/// Compile a body to be evaluated in scope, reusing the code from the last time the same body was
+/// compiled as long as its keywords are still bound to the same special forms in scope.
+/// Builtins ask for the same bodies to be evaluated over and over, like the body of a let-values
+/// in a procedure that is called in a loop, and looking them up is much faster than compiling them.
+/// Only code that says it is cacheable is kept. Bodies with values made while the program runs in
+/// them, like the procedure in the call that call-with-values makes, are hardly ever the same
+/// twice, so they aren't kept either.
+pub fn compile_cached(body: &[Expression], scope: &Rc<RefCell<Scope>>) -> Rc<Code> {
+  let mut hasher = DefaultHasher::new();
+  let mut cacheable = true;
+  for expression in body {
+    cacheable &= hash_expression(expression, &mut hasher);
+  }
+  if !cacheable {
+    return Rc::new(compile_body(body, &None, scope));
+  }
+  let hash = hasher.finish();
+  let cached = CACHED_BODIES.with(|cached| {
+    cached
+      .borrow()
+      .get(&hash)?
+      .iter()
+      .find(|(cached_body, _)| cached_body.as_slice() == body)
+      .filter(|(_, code)| compiled_for(code, scope))
+      .map(|(_, code)| code.clone())
+  });
+  if let Some(code) = cached {
+    return code;
+  }
+  let code = Rc::new(compile_body(body, &None, scope));
+  if !code.cacheable {
+    return code;
+  }
+  CACHED_BODIES.with(|cached| {
+    let mut cached = cached.borrow_mut();
+    if cached.len() >= MAX_CACHED_BODIES {
+      cached.clear();
+    }
+    let bodies = cached.entry(hash).or_default();
+    bodies.retain(|(cached_body, _)| cached_body.as_slice() != body);
+    bodies.push((body.to_vec(), code.clone()));
+  });
+  code
+}
+
+/// Hash the parts of an expression that code is usually made of, and tell whether it is made of
+/// nothing but what can be read from source.
+/// Anything else is rare in code, so it is left for comparing bodies to tell apart.
+fn hash_expression(expression: &Expression, hasher: &mut DefaultHasher) -> bool {
+  let mut expression = expression;
+  let mut readable = true;
+  loop {
+    mem::discriminant(expression).hash(hasher);
+    match expression {
+      Expression::Cons(cons) => {
+        readable &= hash_expression(&cons.car, hasher);
+        expression = &cons.cdr;
+        continue;
+      }
+      Expression::Symbol(string) | Expression::String(string) => string.hash(hasher),
+      Expression::Char(c) => c.hash(hasher),
+      Expression::Number(Number::Integer(integer)) => integer.hash(hasher),
+      Expression::Boolean(boolean) => boolean.hash(hasher),
+      Expression::Null | Expression::Void | Expression::Eof => (),
+      _ => readable = false,
+    }
+    return readable;
+  }
+}
+
+/// Whether code compiled in one scope can be executed in another, which it can't if one of the
+/// keywords it compiled directly is bound to something else there
+fn compiled_for(code: &Code, scope: &Rc<RefCell<Scope>>) -> bool {
+  code
+    .special_forms
+    .iter()
+    .all(|(keyword, name)| special_form_name(scope, keyword) == Some(*name))
+}
+
+/// The name of the special form a keyword is bound to in scope, if it is bound to one
+fn special_form_name(scope: &Rc<RefCell<Scope>>, keyword: &str) -> Option<&'static str> {
+  match scope.borrow().lookup(keyword) {
+    Ok(Expression::Procedure(Procedure::SpecialFixedArgumentForm(name, _, _)))
+    | Ok(Expression::Procedure(Procedure::SpecialVariableArgumentForm(name, _, _))) => Some(name),
+    _ => None,
+  }
+}
+
+/// Compile a body to be evaluated in scope, with the value of the last line returned
+pub fn compile_body(
+  body: &[Expression],
+  environment: &Environment,
+  scope: &Rc<RefCell<Scope>>,
+) -> Code {
+  let mut compiler = Compiler {
+    code: Code {
+      instructions: vec![],
+      constants: vec![],
+      symbols: vec![],
+      lambdas: vec![],
+      combinations: vec![],
+      environment: environment.clone(),
+      special_forms: vec![],
+      cacheable: true,
+    },
+    scope,
+  };
+  compiler.sequence(body, true);
+  compiler.emit(Instruction::Return);
+  compiler.code
+}
+
+struct Compiler<'a> {
+  code: Code,
+  scope: &'a Rc<RefCell<Scope>>,
+}
+
+impl Compiler<'_> {
+  fn emit(&mut self, instruction: Instruction) -> usize {
+    self.code.instructions.push(instruction);
+    self.code.instructions.len() - 1
+  }
+
+  /// Point the jump at index to the next instruction
+  fn patch(&mut self, index: usize) {
+    let target = self.code.instructions.len();
+    self.code.instructions[index] = match self.code.instructions[index] {
+      Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+      Instruction::JumpIfTrue(_) => Instruction::JumpIfTrue(target),
+      Instruction::Jump(_) => Instruction::Jump(target),
+      instruction => unreachable!("{:?} is not a jump", instruction),
+    };
+  }
+
+  fn constant(&mut self, expression: &Expression) {
+    self.code.constants.push(expression.clone());
+    self.emit(Instruction::Constant(self.code.constants.len() - 1));
+  }
+
+  fn symbol(&mut self, symbol: &str) -> usize {
+    match self.code.symbols.iter().position(|s| s == symbol) {
+      Some(index) => index,
+      None => {
+        self.code.symbols.push(symbol.to_string());
+        self.code.symbols.len() - 1
+      }
+    }
+  }
+
+  /// Find the lexical address of an argument
+  fn resolve(&self, symbol: &str) -> Result<(usize, usize), usize> {
+    let mut depth = 0;
+    let mut environment = &self.code.environment;
+    while let Some(arguments) = environment {
+      if let Some(index) = arguments.names.iter().rposition(|name| name == symbol) {
+        return Ok((depth, index));
+      }
+      depth += 1;
+      environment = &arguments.parent;
+    }
+    Err(depth)
+  }
+
+  fn reference(&mut self, symbol: &str) {
+    let instruction = match self.resolve(symbol) {
+      Ok((depth, index)) => Instruction::Argument(depth, index, self.symbol(symbol)),
+      Err(depth) => Instruction::Definition(depth, self.symbol(symbol)),
+    };
+    self.emit(instruction);
+  }
+
+  /// Evaluate each line of the body in turn, leaving the value of the last one
+  fn sequence(&mut self, body: &[Expression], tail: bool) {
+    match body.split_last() {
+      Some((last, lines)) => {
+        for line in lines {
+          self.expression(line, false);
+          self.emit(Instruction::Pop);
+        }
+        self.expression(last, tail);
+      }
+      None => self.constant(&void!()),
+    }
+  }
+
+  fn expression(&mut self, expression: &Expression, tail: bool) {
+    match expression {
+      Expression::Symbol(symbol) => self.reference(symbol),
+      Expression::Cons(cons) => {
+        let operands = arg_vec("", &cons.cdr).ok();
+        if let (Expression::Symbol(keyword), Some(operands)) = (cons.car.as_ref(), &operands) {
+          if self.special_form(keyword, operands, tail) {
+            return;
+          }
+        }
+        self.combination(expression, operands, tail)
+      }
+      _ => self.constant(expression),
+    }
+  }
+
+  /// The name of the special form the keyword is bound to, if it isn't shadowed by an argument
+  fn special_form_name(&self, keyword: &str) -> Option<&'static str> {
+    if self.resolve(keyword).is_ok() {
+      return None;
+    }
+    special_form_name(self.scope, keyword)
+  }
+
+  /// Compile a use of a special form directly, if it is one that can be.
+  /// Malformed uses are left for the special form itself to report the error.
+  fn special_form(&mut self, keyword: &str, operands: &[Expression], tail: bool) -> bool {
+    let name = self.special_form_name(keyword);
+    let compiled = self.compile_special_form(name, operands, tail);
+    if let (true, Some(name)) = (compiled, name) {
+      self.bound(keyword, name);
+    }
+    compiled
+  }
+
+  /// Remember that the code relies on a keyword being bound to a special form
+  fn bound(&mut self, keyword: &str, name: &'static str) {
+    let special_form = (keyword.to_string(), name);
+    if !self.code.special_forms.contains(&special_form) {
+      self.code.special_forms.push(special_form);
+    }
+  }
+
+  fn compile_special_form(
+    &mut self,
+    name: Option<&'static str>,
+    operands: &[Expression],
+    tail: bool,
+  ) -> bool {
+    match (name, operands) {
+      (Some("quote"), [quoted]) => self.constant(quoted),
+      (Some("define"), [Expression::Symbol(symbol), value]) => {
+        self.expression(value, false);
+        let symbol = self.symbol(symbol);
+        self.emit(Instruction::Define(symbol));
+      }
+      (Some("lambda"), [formals, body @ ..]) if !body.is_empty() => {
+        let lambda = match self.lambda(formals, body) {
+          Some(lambda) => lambda,
+          None => return false,
+        };
+        for (keyword, name) in &lambda.code.special_forms {
+          self.bound(keyword, name);
+        }
+        self.code.cacheable &= lambda.code.cacheable;
+        self.code.lambdas.push(Rc::new(lambda));
+        self.emit(Instruction::Lambda(self.code.lambdas.len() - 1));
+      }
+      (Some("cond"), clauses) => return self.cond(clauses, tail),
+      (Some("include"), filenames) => return self.include(filenames, tail),
+      _ => return false,
+    }
+    true
+  }
+
+  fn lambda(&self, formals: &Expression, body: &[Expression]) -> Option<Lambda> {
+    let mut arguments = vec![];
+    let mut formals = formals;
+    while let Expression::Cons(cons) = formals {
+      match cons.car.as_ref() {
+        Expression::Symbol(symbol) => arguments.push(symbol.clone()),
+        _ => return None,
+      }
+      formals = &cons.cdr;
+    }
+    let variable = match formals {
+      Expression::Null => false,
+      Expression::Symbol(symbol) => {
+        arguments.push(symbol.clone());
+        true
+      }
+      _ => return None,
+    };
+    let arguments = Rc::new(arguments);
+    let environment = Some(Rc::new(Arguments {
+      names: arguments.clone(),
+      parent: self.code.environment.clone(),
+    }));
+    Some(Lambda {
+      arguments,
+      variable,
+      body: body.to_vec(),
+      code: Rc::new(compile_body(body, &environment, self.scope)),
+    })
+  }
+
+  fn cond(&mut self, clauses: &[Expression], tail: bool) -> bool {
+    let mut clauses = match clauses
+      .iter()
+      .map(|clause| arg_vec("cond", clause).ok())
+      .collect::<Option<Vec<Vec<Expression>>>>()
+    {
+      Some(clauses) => clauses,
+      None => return false,
+    };
+    if clauses.iter().any(|clause| clause.is_empty()) {
+      return false;
+    }
+    let else_body = match clauses.last() {
+      Some(clause) if clause[0] == symbol!("else") => {
+        if clause.len() < 2 {
+          return false;
+        }
+        Some(clauses.pop().unwrap().split_off(1))
+      }
+      _ => None,
+    };
+    let mut ends = vec![];
+    for clause in &clauses {
+      self.expression(&clause[0], false);
+      let body = if clause.get(1) == Some(&symbol!("=>")) {
+        &clause[2..]
+      } else {
+        &clause[1..]
+      };
+      if body.is_empty() {
+        // The value of the clause is the value of the test
+        ends.push(self.emit(Instruction::JumpIfTrue(0)));
+      } else {
+        let next_clause = self.emit(Instruction::JumpIfFalse(0));
+        self.sequence(body, tail);
+        ends.push(self.emit(Instruction::Jump(0)));
+        self.patch(next_clause);
+      }
+    }
+    match else_body {
+      Some(else_body) => self.sequence(&else_body, tail),
+      None => self.constant(&void!()),
+    }
+    for end in ends {
+      self.patch(end);
+    }
+    true
+  }
+
+  /// Splice the expressions of the files an include refers to in where the include is, so that
+  /// the files are read once when it is compiled instead of every time it is evaluated.
+  /// Each file is marked as being loaded while its expressions are compiled, so that files which
+  /// include themselves are left for the include to report at runtime.
+  fn include(&mut self, filenames: &[Expression], tail: bool) -> bool {
+    let files = match read_included(filenames) {
+      Ok(files) => files,
+      Err(_) => return false,
+    };
+    // The files may have changed by the next time the same include is compiled
+    self.code.cacheable = false;
+    let mut remaining: usize = files.iter().map(|(_, expressions)| expressions.len()).sum();
+    if remaining == 0 {
+      self.constant(&void!());
+    }
+    for (path, expressions) in files {
+      let entered = enter(&path).is_ok();
+      for expression in &expressions {
+        remaining -= 1;
+        if remaining == 0 {
+          self.expression(expression, tail);
+        } else {
+          self.expression(expression, false);
+          self.emit(Instruction::Pop);
+        }
+      }
+      if entered {
+        leave();
+      }
+    }
+    true
+  }
+
+  fn combination(&mut self, form: &Expression, operands: Option<Vec<Expression>>, tail: bool) {
+    if let Expression::Cons(cons) = form {
+      self.expression(&cons.car, false);
+    }
+    self.code.combinations.push(Combination {
+      form: form.clone(),
+      argc: operands.as_ref().map(|operands| operands.len()),
+      after: 0,
+      tail,
+      expansion: RefCell::new(None),
+    });
+    let combination = self.code.combinations.len() - 1;
+    self.emit(Instruction::Operator(combination));
+    if let Some(operands) = operands {
+      for operand in &operands {
+        self.expression(operand, false);
+      }
+      if tail {
+        self.emit(Instruction::TailCall(operands.len()));
+      } else {
+        self.emit(Instruction::Call(operands.len()));
+      }
+    }
+    self.code.combinations[combination].after = self.code.instructions.len();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use Instruction::*;
+
+  fn instructions(string: &str) -> Vec<Instruction> {
+    compile(&parse(string).unwrap(), &None, &Scope::builtins()).instructions
+  }
+
+  #[test]
+  fn test_compile_constants() {
+    assert_eq!(instructions("1"), vec![Constant(0), Return]);
+    assert_eq!(instructions("'(1 2)"), vec![Constant(0), Return]);
+    assert_eq!(instructions("foo"), vec![Definition(0, 0), Return]);
+  }
+
+  #[test]
+  fn test_compile_combination() {
+    assert_eq!(
+      instructions("(car (cdr x))"),
+      vec![
+        Definition(0, 0),
+        Operator(0),
+        Definition(0, 1),
+        Operator(1),
+        Definition(0, 2),
+        Call(1),
+        TailCall(1),
+        Return
+      ]
+    );
+  }
+
+  #[test]
+  fn test_compile_lambda() {
+    let scope = Scope::builtins();
+    let code = compile(
+      &parse("(lambda (x y) (lambda (z) (define w y) (+ z y w)))").unwrap(),
+      &None,
+      &scope,
+    );
+    assert_eq!(code.instructions, vec![Lambda(0), Return]);
+    let outer = &code.lambdas[0];
+    assert_eq!(outer.code.instructions, vec![Lambda(0), Return]);
+    assert_eq!(
+      outer.code.lambdas[0].code.instructions,
+      vec![
+        Argument(1, 1, 0),
+        Define(1),
+        Pop,
+        Definition(2, 2),
+        Operator(0),
+        Argument(0, 0, 3),
+        Argument(1, 1, 0),
+        Definition(2, 1),
+        TailCall(3),
+        Return
+      ]
+    );
+  }
+
+  #[test]
+  fn test_compile_cond() {
+    assert_eq!(
+      instructions("(cond (a) (b c) (else d))"),
+      vec![
+        Definition(0, 0),
+        JumpIfTrue(7),
+        Definition(0, 1),
+        JumpIfFalse(6),
+        Definition(0, 2),
+        Jump(7),
+        Definition(0, 3),
+        Return
+      ]
+    );
+  }
+
+  #[test]
+  fn test_compile_cached() {
+    let scope = Scope::builtins();
+    let body = vec![
+      parse("(lambda (x) (quote x))").unwrap(),
+      parse("y").unwrap(),
+    ];
+    let code = compile_cached(&body, &scope);
+    assert!(code.cacheable);
+    assert!(Rc::ptr_eq(&code, &compile_cached(&body.clone(), &scope)));
+    // The lambda is compiled before the instruction that makes it
+    assert_eq!(
+      code.special_forms,
+      vec![
+        ("quote".to_string(), "quote"),
+        ("lambda".to_string(), "lambda")
+      ]
+    );
+    // Where quote is something else, the code compiled for the body can't be used
+    let shadowed = Scope::child(scope.clone());
+    shadowed.borrow_mut().define("quote", symbol!("car"));
+    let shadowed_code = compile_cached(&body, &shadowed);
+    assert!(!Rc::ptr_eq(&code, &shadowed_code));
+    assert_eq!(
+      shadowed_code.lambdas[0].code.instructions,
+      vec![
+        Definition(1, 0),
+        Operator(0),
+        Argument(0, 0, 1),
+        TailCall(1),
+        Return
+      ]
+    );
+    // Bodies built around values made at runtime aren't kept
+    let built = vec![list!(scope.borrow().lookup("car").unwrap(), int!(1))];
+    assert!(!Rc::ptr_eq(
+      &compile_cached(&built, &scope),
+      &compile_cached(&built, &scope)
+    ));
+  }
+
+  #[test]
+  fn test_compile_shadowed_special_forms() {
+    // An argument named quote is just an argument
+    let code = compile(
+      &parse("(lambda (quote) (quote x))").unwrap(),
+      &None,
+      &Scope::builtins(),
+    );
+    assert_eq!(
+      code.lambdas[0].code.instructions,
+      vec![
+        Argument(0, 0, 0),
+        Operator(0),
+        Definition(1, 1),
+        TailCall(1),
+        Return
+      ]
+    );
+    // Malformed special forms are left to report their own errors
+    assert_eq!(
+      instructions("(quote)"),
+      vec![Definition(0, 0), Operator(0), TailCall(0), Return]
+    );
+  }
+}
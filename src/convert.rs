@@ -0,0 +1,370 @@
+use crate::evaluate::{arg_vec, vec_arg, EvaluationError, EvaluationResult};
+use crate::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// A Rust value which can be turned into a Lisp value.
+/// This can fail, like for integers too big for Lisp to hold.
+pub trait ToLisp {
+  fn to_lisp(self) -> EvaluationResult;
+}
+
+/// A Rust value which can be made from a Lisp value
+pub trait FromLisp: Sized {
+  /// The kind of Lisp value this is made from, which is what errors say was expected
+  fn expected() -> String;
+  /// Make this from a Lisp value, unless it is the wrong kind of value
+  fn from_lisp(expression: &Expression) -> Option<Self>;
+}
+
+/// Convert an argument of a procedure, failing with an InvalidArgument error if it is the wrong
+/// kind of value
+pub fn from_lisp_arg<T: FromLisp>(
+  procedure_name: &str,
+  arg: &Expression,
+) -> Result<T, EvaluationError> {
+  T::from_lisp(arg)
+    .ok_or_else(|| EvaluationError::invalid_argument(procedure_name, &T::expected(), arg))
+}
+
+impl ToLisp for Expression {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(self)
+  }
+}
+impl FromLisp for Expression {
+  fn expected() -> String {
+    "anything".to_string()
+  }
+  fn from_lisp(expression: &Expression) -> Option<Expression> {
+    Some(expression.clone())
+  }
+}
+
+/// Lisp integers are smaller than some Rust integers, and bigger than others
+macro_rules! convert_integer {
+  ($($integer:ty),*) => {
+    $(
+      impl ToLisp for $integer {
+        fn to_lisp(self) -> EvaluationResult {
+          match i32::try_from(self) {
+            Ok(integer) => Ok(int!(integer)),
+            Err(_) => Err(EvaluationError::Error(
+              "integer is too big".to_string(),
+              vec![string!(self.to_string())],
+            )),
+          }
+        }
+      }
+      impl FromLisp for $integer {
+        fn expected() -> String {
+          let min = (<$integer>::MIN as i128).max(i32::MIN as i128);
+          let max = (<$integer>::MAX as i128).min(i32::MAX as i128);
+          if (min, max) == (i32::MIN as i128, i32::MAX as i128) {
+            "integer".to_string()
+          } else {
+            format!("integer from {} to {}", min, max)
+          }
+        }
+        fn from_lisp(expression: &Expression) -> Option<$integer> {
+          match expression {
+            Expression::Number(Number::Integer(integer)) => <$integer>::try_from(*integer).ok(),
+            _ => None,
+          }
+        }
+      }
+    )*
+  };
+}
+convert_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl ToLisp for bool {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(boolean!(self))
+  }
+}
+impl FromLisp for bool {
+  fn expected() -> String {
+    "boolean".to_string()
+  }
+  fn from_lisp(expression: &Expression) -> Option<bool> {
+    match expression {
+      Expression::Boolean(boolean) => Some(*boolean),
+      _ => None,
+    }
+  }
+}
+
+impl ToLisp for char {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(Expression::Char(self))
+  }
+}
+impl FromLisp for char {
+  fn expected() -> String {
+    "char".to_string()
+  }
+  fn from_lisp(expression: &Expression) -> Option<char> {
+    match expression {
+      Expression::Char(c) => Some(*c),
+      _ => None,
+    }
+  }
+}
+
+impl ToLisp for String {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(Expression::String(self))
+  }
+}
+impl ToLisp for &str {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(string!(self))
+  }
+}
+impl FromLisp for String {
+  fn expected() -> String {
+    "string".to_string()
+  }
+  fn from_lisp(expression: &Expression) -> Option<String> {
+    match expression {
+      Expression::String(string) => Some(string.clone()),
+      _ => None,
+    }
+  }
+}
+
+/// Nothing is returned as void, which the REPL doesn't print
+impl ToLisp for () {
+  fn to_lisp(self) -> EvaluationResult {
+    Ok(void!())
+  }
+}
+
+/// Errors are raised, so that a Rust function can fail like any other procedure
+impl<T: ToLisp> ToLisp for Result<T, EvaluationError> {
+  fn to_lisp(self) -> EvaluationResult {
+    self.and_then(ToLisp::to_lisp)
+  }
+}
+
+/// Like in Scheme, #f stands for there being nothing, so Some(false) can't be told apart from None
+impl<T: ToLisp> ToLisp for Option<T> {
+  fn to_lisp(self) -> EvaluationResult {
+    match self {
+      Some(value) => value.to_lisp(),
+      None => Ok(boolean!(false)),
+    }
+  }
+}
+impl<T: FromLisp> FromLisp for Option<T> {
+  fn expected() -> String {
+    format!("{} or #f", T::expected())
+  }
+  fn from_lisp(expression: &Expression) -> Option<Option<T>> {
+    match expression {
+      Expression::Boolean(false) => Some(None),
+      expression => T::from_lisp(expression).map(Some),
+    }
+  }
+}
+
+impl<T: ToLisp> ToLisp for Vec<T> {
+  fn to_lisp(self) -> EvaluationResult {
+    vec_arg(
+      self
+        .into_iter()
+        .map(ToLisp::to_lisp)
+        .collect::<Result<_, _>>()?,
+    )
+  }
+}
+impl<T: FromLisp> FromLisp for Vec<T> {
+  fn expected() -> String {
+    format!("list of {}", T::expected())
+  }
+  fn from_lisp(expression: &Expression) -> Option<Vec<T>> {
+    arg_vec("", expression)
+      .ok()?
+      .iter()
+      .map(T::from_lisp)
+      .collect()
+  }
+}
+
+/// Tuples are lists with an element of each type
+macro_rules! convert_tuple {
+  ($(($($element:ident),+)),*) => {
+    $(
+      #[allow(non_snake_case)]
+      impl<$($element: ToLisp),+> ToLisp for ($($element,)+) {
+        fn to_lisp(self) -> EvaluationResult {
+          let ($($element,)+) = self;
+          vec_arg(vec![$($element.to_lisp()?),+])
+        }
+      }
+      #[allow(non_snake_case)]
+      impl<$($element: FromLisp),+> FromLisp for ($($element,)+) {
+        fn expected() -> String {
+          let elements: &[String] = &[$($element::expected()),+];
+          format!("({})", elements.join(" "))
+        }
+        fn from_lisp(expression: &Expression) -> Option<($($element,)+)> {
+          let elements = arg_vec("", expression).ok()?;
+          let mut elements = elements.iter();
+          let tuple = ($($element::from_lisp(elements.next()?)?,)+);
+          match elements.next() {
+            Some(_) => None,
+            None => Some(tuple),
+          }
+        }
+      }
+    )*
+  };
+}
+convert_tuple!(
+  (A),
+  (A, B),
+  (A, B, C),
+  (A, B, C, D),
+  (A, B, C, D, E),
+  (A, B, C, D, E, F)
+);
+
+/// Maps are association lists, which are lists of pairs of keys and values
+impl<K: ToLisp, V: ToLisp> ToLisp for HashMap<K, V> {
+  fn to_lisp(self) -> EvaluationResult {
+    vec_arg(
+      self
+        .into_iter()
+        .map(|(key, value)| Ok(cons!(&key.to_lisp()?, &value.to_lisp()?)))
+        .collect::<Result<_, EvaluationError>>()?,
+    )
+  }
+}
+impl<K: FromLisp + Eq + Hash, V: FromLisp> FromLisp for HashMap<K, V> {
+  fn expected() -> String {
+    format!("association list of {} to {}", K::expected(), V::expected())
+  }
+  fn from_lisp(expression: &Expression) -> Option<HashMap<K, V>> {
+    arg_vec("", expression)
+      .ok()?
+      .iter()
+      .map(|pair| match pair {
+        Expression::Cons(cons) => Some((K::from_lisp(&cons.car)?, V::from_lisp(&cons.cdr)?)),
+        _ => None,
+      })
+      .collect()
+  }
+}
+
+/// A Rust function whose arguments can be made from Lisp values, and whose result can be turned
+/// into one, which can be registered with Interpreter::register_typed_fn. Args is the tuple of
+/// its argument types.
+pub trait TypedFn<Args> {
+  /// How many arguments the function takes
+  fn argc() -> usize;
+  /// Call the function with Lisp values, converting them to the types it takes
+  fn call(&self, procedure_name: &str, args: Vec<Expression>) -> EvaluationResult;
+}
+
+macro_rules! typed_fn {
+  ($(($($arg:ident),*)),*) => {
+    $(
+      #[allow(non_snake_case)]
+      impl<Function, Output, $($arg),*> TypedFn<($($arg,)*)> for Function
+      where
+        Function: Fn($($arg),*) -> Output,
+        Output: ToLisp,
+        $($arg: FromLisp),*
+      {
+        fn argc() -> usize {
+          let args: &[&str] = &[$(stringify!($arg)),*];
+          args.len()
+        }
+        #[allow(unused_variables, unused_mut)]
+        fn call(&self, procedure_name: &str, args: Vec<Expression>) -> EvaluationResult {
+          // The arity has already been checked, so there is an argument for every type
+          let mut args = args.iter();
+          $(let $arg = from_lisp_arg::<$arg>(procedure_name, args.next().unwrap())?;)*
+          self($($arg),*).to_lisp()
+        }
+      }
+    )*
+  };
+}
+typed_fn!(
+  (),
+  (A),
+  (A, B),
+  (A, B, C),
+  (A, B, C, D),
+  (A, B, C, D, E),
+  (A, B, C, D, E, F)
+);
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_to_lisp() {
+    assert_eq!(3u8.to_lisp(), Ok(int!(3)));
+    assert_eq!(
+      5_000_000_000i64.to_lisp(),
+      Err(EvaluationError::Error(
+        "integer is too big".to_string(),
+        vec![string!("5000000000")]
+      ))
+    );
+    assert_eq!("a".to_lisp(), Ok(string!("a")));
+    assert_eq!(
+      vec![Some(true), None].to_lisp(),
+      Ok(list!(boolean!(true), boolean!(false)))
+    );
+    assert_eq!(
+      (1, "b".to_string(), 'c').to_lisp(),
+      Ok(list!(int!(1), string!("b"), Expression::Char('c')))
+    );
+    let map: HashMap<String, i32> = vec![("x".to_string(), 1)].into_iter().collect();
+    assert_eq!(map.to_lisp(), Ok(list!(cons!(&string!("x"), &int!(1)))));
+  }
+
+  #[test]
+  fn test_from_lisp() {
+    assert_eq!(i64::from_lisp(&int!(-3)), Some(-3));
+    assert_eq!(u8::from_lisp(&int!(256)), None);
+    assert_eq!(u8::expected(), "integer from 0 to 255");
+    assert_eq!(i64::expected(), "integer");
+    assert_eq!(
+      Vec::<String>::from_lisp(&list!(string!("a"), string!("b"))),
+      Some(vec!["a".to_string(), "b".to_string()])
+    );
+    assert_eq!(
+      Vec::<String>::from_lisp(&list!(string!("a"), int!(1))),
+      None
+    );
+    assert_eq!(Option::<i32>::from_lisp(&boolean!(false)), Some(None));
+    assert_eq!(
+      <(i32, bool)>::from_lisp(&list!(int!(1), boolean!(true))),
+      Some((1, true))
+    );
+    assert_eq!(<(i32, bool)>::from_lisp(&list!(int!(1))), None);
+    assert_eq!(
+      <(i32, bool)>::from_lisp(&list!(int!(1), boolean!(true), int!(2))),
+      None
+    );
+    assert_eq!(<(i32, Vec<bool>)>::expected(), "(integer list of boolean)");
+    let map = HashMap::<String, i32>::from_lisp(&list!(cons!(&string!("x"), &int!(1))));
+    assert_eq!(map.unwrap().get("x"), Some(&1));
+    assert_eq!(
+      from_lisp_arg::<Vec<String>>("f", &list!(int!(1))),
+      Err(EvaluationError::invalid_argument(
+        "f",
+        "list of string",
+        &list!(int!(1))
+      ))
+    );
+  }
+}
@@ -0,0 +1,133 @@
+use crate::Scope;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::{CmdKind, Highlighter, MatchingBracketHighlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::env::var_os;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// The line editor the REPL reads its input with
+pub type LispEditor = Editor<LispHelper, DefaultHistory>;
+
+/// Completes symbols and highlights matching parens as the REPL's input is typed
+pub struct LispHelper {
+  /// The scope the REPL evaluates its input in, which has the symbols that can be completed
+  pub scope: Rc<RefCell<Scope>>,
+  brackets: MatchingBracketHighlighter,
+}
+
+/// Whether a character ends a symbol
+fn is_delimiter(c: char) -> bool {
+  c.is_whitespace() || "()'\"".contains(c)
+}
+
+/// Where the symbol being typed at pos starts, and the symbols in scope that it could be
+pub fn completions(scope: &Scope, line: &str, pos: usize) -> (usize, Vec<String>) {
+  let start = line[..pos]
+    .char_indices()
+    .rev()
+    .find(|(_, c)| is_delimiter(*c))
+    .map(|(index, c)| index + c.len_utf8())
+    .unwrap_or(0);
+  let prefix = &line[start..pos];
+  let names = scope
+    .names()
+    .into_iter()
+    .filter(|name| name.starts_with(prefix))
+    .collect();
+  (start, names)
+}
+
+impl Completer for LispHelper {
+  type Candidate = Pair;
+
+  fn complete(
+    &self,
+    line: &str,
+    pos: usize,
+    _ctx: &Context<'_>,
+  ) -> rustyline::Result<(usize, Vec<Pair>)> {
+    let (start, names) = completions(&self.scope.borrow(), line, pos);
+    let candidates = names
+      .into_iter()
+      .map(|name| Pair {
+        display: name.clone(),
+        replacement: name,
+      })
+      .collect();
+    Ok((start, candidates))
+  }
+}
+
+impl Highlighter for LispHelper {
+  fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+    self.brackets.highlight(line, pos)
+  }
+
+  fn highlight_char(&self, line: &str, pos: usize, kind: CmdKind) -> bool {
+    self.brackets.highlight_char(line, pos, kind)
+  }
+}
+
+impl Hinter for LispHelper {
+  type Hint = String;
+}
+
+impl Validator for LispHelper {}
+
+impl Helper for LispHelper {}
+
+/// Where the REPL's history is kept between sessions, which is ~/.rust_lisp_history
+pub fn history_path() -> Option<PathBuf> {
+  var_os("HOME").map(|home| PathBuf::from(home).join(".rust_lisp_history"))
+}
+
+/// Make a line editor that completes the symbols in scope, with the history of earlier sessions
+pub fn editor(scope: Rc<RefCell<Scope>>) -> rustyline::Result<LispEditor> {
+  let mut editor = LispEditor::new()?;
+  editor.set_helper(Some(LispHelper {
+    scope,
+    brackets: MatchingBracketHighlighter::new(),
+  }));
+  if let Some(path) = history_path() {
+    // There is no history the first time the REPL is used
+    editor.load_history(&path).ok();
+  }
+  Ok(editor)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestContext;
+
+  #[test]
+  fn test_completions() {
+    let ctx = TestContext::new();
+    ctx.exec("(define my-variable 1)");
+    ctx.exec("(define my-other-variable 2)");
+    let scope = ctx.scope.borrow();
+    assert_eq!(
+      completions(&scope, "(+ my-", 6),
+      (
+        3,
+        vec!["my-other-variable".to_string(), "my-variable".to_string()]
+      )
+    );
+    assert_eq!(
+      completions(&scope, "(my-v 1)", 5),
+      (1, vec!["my-variable".to_string()])
+    );
+    assert_eq!(completions(&scope, "'nothing-like-this", 18), (1, vec![]));
+    let (start, names) = completions(&scope, "(call-with-output-f", 19);
+    assert_eq!(
+      (start, names),
+      (1, vec!["call-with-output-file".to_string()])
+    );
+  }
+}
@@ -1,16 +1,112 @@
+use crate::builtins::apply_record_procedure;
+use crate::compile::compile_cached;
+use crate::parse::ParseError;
+use crate::port::current_output_port;
+use crate::vm::{bind_arguments, Execution};
 use crate::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::iter::once;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-#[derive(Debug, Eq, PartialEq)]
+/// The default for how many frames deep the evaluation stack can grow before evaluation fails
+/// with a StackOverflow. This is roughly how deep non-tail recursion can go.
+pub const DEFAULT_MAX_STACK_DEPTH: usize = 1_000_000;
+
+thread_local! {
+  static MAX_STACK_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_STACK_DEPTH) };
+  /// The procedures whose calls are printed, by the names they were traced under
+  static TRACED: RefCell<Vec<(String, Procedure)>> = const { RefCell::new(vec![]) };
+  /// How many traced calls are in progress, which is how far their calls are indented
+  static TRACE_DEPTH: Cell<usize> = const { Cell::new(0) };
+  /// A flag which interrupts evaluations on this thread when it is set
+  static INTERRUPT: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Set how many frames deep the evaluation stack can grow for all evaluations on this thread
+pub fn set_max_stack_depth(max_stack_depth: usize) {
+  MAX_STACK_DEPTH.with(|depth| depth.set(max_stack_depth));
+}
+
+/// Interrupt evaluations on this thread whenever a flag is set, which can be done from any thread,
+/// like by a signal handler. The flag is cleared again once an evaluation has been interrupted.
+pub fn set_interrupt_flag(flag: Arc<AtomicBool>) {
+  INTERRUPT.with(|interrupt| *interrupt.borrow_mut() = Some(flag));
+}
+
+/// Print every call to a procedure, and the value it returns.
+/// Special forms can't be traced, since they aren't called with values.
+pub fn trace(name: &str, procedure: Procedure) -> Result<(), EvaluationError> {
+  if let Procedure::SpecialFixedArgumentForm(_, _, _)
+  | Procedure::SpecialVariableArgumentForm(_, _, _) = procedure
+  {
+    return Err(EvaluationError::invalid_argument(
+      "trace",
+      "procedure",
+      &Expression::Procedure(procedure),
+    ));
+  }
+  TRACED.with(|traced| {
+    let mut traced = traced.borrow_mut();
+    traced.retain(|(traced_name, _)| traced_name != name);
+    traced.push((name.to_string(), procedure));
+  });
+  Ok(())
+}
+
+/// Stop tracing a procedure, or every procedure if no name is given
+pub fn untrace(name: Option<&str>) {
+  TRACED.with(|traced| match name {
+    Some(name) => traced
+      .borrow_mut()
+      .retain(|(traced_name, _)| traced_name != name),
+    None => traced.borrow_mut().clear(),
+  });
+  TRACE_DEPTH.with(|depth| depth.set(0));
+}
+
+/// The name a procedure was traced under, if it is being traced
+pub fn traced_name(procedure: &Procedure) -> Option<String> {
+  TRACED.with(|traced| {
+    let traced = traced.borrow();
+    if traced.is_empty() {
+      return None;
+    }
+    traced
+      .iter()
+      .find(|(_, traced)| traced == procedure)
+      .map(|(name, _)| name.clone())
+  })
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EvaluationError {
   WrongNumberOfArguments(String, usize, usize),
   WrongNumberOfVariableArguments(String, usize, usize),
-  InvalidArgument(String, String, Expression),
+  InvalidArgument(String, String, Box<Expression>),
   UndefinedSymbol(String),
   DivideByZero(Number),
-  NotAProcedure(Expression),
+  NotAProcedure(Box<Expression>),
+  NoMatchingSyntaxRule(Box<Expression>),
+  StackOverflow(usize),
+  /// An error signalled by the error procedure, with a message and irritants
+  Error(String, Vec<Expression>),
+  /// Something that is not an error object was raised, and nothing handled it
+  Raised(Box<Expression>),
+  /// A file could not be found or read, with the reason why
+  FileError(String, String),
+  /// A file to be loaded could not be parsed
+  ParseError(String, ParseError),
+  /// A file ends up loading itself, through each of these files
+  LoadCycle(Vec<String>),
+  /// No file defines the library with this name
+  LibraryNotFound(Box<Expression>),
+  /// Evaluation was stopped before it finished, like by pressing Ctrl-C
+  Interrupted,
+  /// The program asked to exit with this status
+  Exit(i32),
 }
 impl EvaluationError {
   pub fn invalid_argument(
@@ -21,9 +117,74 @@ impl EvaluationError {
     EvaluationError::InvalidArgument(
       procedure_name.to_string(),
       expected.to_string(),
-      actual.clone(),
+      Box::new(actual.clone()),
     )
   }
+  /// The message of the error object for this error
+  pub fn message(&self) -> String {
+    match self {
+      EvaluationError::WrongNumberOfArguments(_, _, _)
+      | EvaluationError::WrongNumberOfVariableArguments(_, _, _) => "wrong number of arguments",
+      EvaluationError::InvalidArgument(_, _, _) => "invalid argument",
+      EvaluationError::UndefinedSymbol(_) => "undefined symbol",
+      EvaluationError::DivideByZero(_) => "divide by zero",
+      EvaluationError::NotAProcedure(_) => "not a procedure",
+      EvaluationError::NoMatchingSyntaxRule(_) => "no matching syntax rule",
+      EvaluationError::StackOverflow(_) => "stack overflow",
+      EvaluationError::Error(message, _) => message,
+      EvaluationError::Raised(_) => "uncaught exception",
+      EvaluationError::FileError(_, _) => "file error",
+      EvaluationError::ParseError(_, _) => "parse error",
+      EvaluationError::LoadCycle(_) => "load cycle",
+      EvaluationError::LibraryNotFound(_) => "library not found",
+      EvaluationError::Interrupted => "interrupted",
+      EvaluationError::Exit(_) => "exit",
+    }
+    .to_string()
+  }
+  /// The irritants of the error object for this error, which are the details of what went wrong
+  pub fn irritants(&self) -> Vec<Expression> {
+    match self {
+      EvaluationError::WrongNumberOfArguments(procedure_name, expected, actual)
+      | EvaluationError::WrongNumberOfVariableArguments(procedure_name, expected, actual) => vec![
+        string!(procedure_name),
+        int!(*expected as i32),
+        int!(*actual as i32),
+      ],
+      EvaluationError::InvalidArgument(procedure_name, expected, actual) => {
+        vec![
+          string!(procedure_name),
+          string!(expected),
+          actual.as_ref().clone(),
+        ]
+      }
+      EvaluationError::UndefinedSymbol(symbol) => vec![symbol!(symbol)],
+      EvaluationError::DivideByZero(quotient) => vec![Expression::Number(quotient.clone())],
+      EvaluationError::NotAProcedure(non_procedure) => vec![non_procedure.as_ref().clone()],
+      EvaluationError::NoMatchingSyntaxRule(form) => vec![form.as_ref().clone()],
+      EvaluationError::StackOverflow(max_stack_depth) => vec![int!(*max_stack_depth as i32)],
+      EvaluationError::Error(_, irritants) => irritants.clone(),
+      EvaluationError::Raised(raised) => vec![raised.as_ref().clone()],
+      EvaluationError::FileError(filename, reason) => vec![string!(filename), string!(reason)],
+      EvaluationError::ParseError(filename, err) => {
+        vec![string!(filename), string!(format!("{:?}", err))]
+      }
+      EvaluationError::LoadCycle(filenames) => {
+        filenames.iter().map(|filename| string!(filename)).collect()
+      }
+      EvaluationError::LibraryNotFound(name) => vec![name.as_ref().clone()],
+      EvaluationError::Interrupted => vec![],
+      EvaluationError::Exit(status) => vec![int!(*status)],
+    }
+  }
+}
+
+/// The error to report when something raised is never handled
+fn uncaught(raised: Expression) -> EvaluationError {
+  match raised {
+    Expression::Error(err) => err.as_ref().clone(),
+    raised => EvaluationError::Raised(Box::new(raised)),
+  }
 }
 impl fmt::Display for EvaluationError {
   fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -62,25 +223,95 @@ impl fmt::Display for EvaluationError {
           non_procedure.outer_representation()
         )
       }
+      EvaluationError::NoMatchingSyntaxRule(form) => {
+        write!(fmt, "no syntax rule matches {}", form)
+      }
+      EvaluationError::StackOverflow(max_stack_depth) => {
+        write!(
+          fmt,
+          "stack overflow: exceeded the maximum depth of {}",
+          max_stack_depth
+        )
+      }
+      EvaluationError::Error(message, irritants) => {
+        write!(fmt, "{}", message)?;
+        for irritant in irritants {
+          write!(fmt, " {}", irritant)?;
+        }
+        Ok(())
+      }
+      EvaluationError::Raised(raised) => {
+        write!(fmt, "uncaught exception: {}", raised)
+      }
+      EvaluationError::FileError(filename, reason) => {
+        write!(fmt, "file error in {}: {}", filename, reason)
+      }
+      EvaluationError::ParseError(filename, err) => {
+        write!(fmt, "could not parse {}: {:?}", filename, err)
+      }
+      EvaluationError::LoadCycle(filenames) => {
+        write!(fmt, "load cycle: {}", filenames.join(" -> "))
+      }
+      EvaluationError::LibraryNotFound(name) => {
+        write!(fmt, "library not found: {}", name)
+      }
+      EvaluationError::Interrupted => write!(fmt, "interrupted"),
+      EvaluationError::Exit(status) => write!(fmt, "exited with status {}", status),
     }
   }
 }
 pub type EvaluationResult = Result<Expression, EvaluationError>;
 
+/// The rest of a builtin's work, which receives the value of an expression the builtin asked the
+/// evaluator to evaluate. It may be called more than once if a continuation is re-entered.
+#[derive(Clone)]
+pub struct Continue(Rc<dyn Fn(Expression) -> ProcedureResult>);
+impl Continue {
+  pub fn new(continue_with: impl Fn(Expression) -> ProcedureResult + 'static) -> Continue {
+    Continue(Rc::new(continue_with))
+  }
+}
+impl fmt::Debug for Continue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#<continue>")
+  }
+}
+
+/// What a builtin asks the evaluator to do once it returns.
+/// Builtins never evaluate anything themselves, so that no evaluation state lives on the Rust
+/// stack where a continuation could not capture it.
 #[derive(Clone, Debug)]
 pub enum ProcedureValue {
+  /// The builtin is finished, and this is its value
   Expression(Expression),
-  TailCall(Procedure, Expression, Rc<RefCell<Scope>>),
-}
-impl ProcedureValue {
-  pub fn resolve(&self) -> EvaluationResult {
-    match self {
-      ProcedureValue::Expression(expression) => Ok(expression.clone()),
-      ProcedureValue::TailCall(procedure, expression, arg_scope) => {
-        evaluate_procedure(procedure, expression, arg_scope.clone())
-      }
-    }
-  }
+  /// The value of this expression is the value of the builtin
+  TailCall(Expression, Rc<RefCell<Scope>>),
+  /// The value of the last line of this body is the value of the builtin
+  TailBody(Vec<Expression>, Rc<RefCell<Scope>>),
+  /// The value of applying this procedure to these arguments is the value of the builtin
+  TailApply(Procedure, Vec<Expression>, Rc<RefCell<Scope>>),
+  /// Evaluate this expression and pass the value on to the rest of the builtin
+  Evaluate(Expression, Rc<RefCell<Scope>>, Continue),
+  /// Apply the procedure to the current continuation
+  CallWithCurrentContinuation(Procedure, Rc<RefCell<Scope>>),
+  /// Call the before thunk, do what the procedure value asks, and then call the after thunk,
+  /// making sure that the before and after thunks are called whenever a continuation enters or
+  /// leaves what was asked for, or an error abandons it
+  DynamicWind(
+    Procedure,
+    Box<ProcedureValue>,
+    Procedure,
+    Rc<RefCell<Scope>>,
+  ),
+  /// Call the thunk with the handler installed for anything raised while it runs
+  WithExceptionHandler(Procedure, Procedure, Rc<RefCell<Scope>>),
+  /// Raise the expression to the current handler, which must not return
+  Raise(Expression),
+  /// Raise the expression to the current handler, and return whatever the handler returns
+  RaiseContinuable(Expression),
+  /// Evaluate the body, and if anything is raised while it runs, abandon the body and pass what
+  /// was raised on to the rest of the builtin instead
+  Guard(Vec<Expression>, Rc<RefCell<Scope>>, Continue),
 }
 pub type ProcedureResult = Result<ProcedureValue, EvaluationError>;
 
@@ -112,132 +343,610 @@ pub fn vec_arg(mut args: Vec<Expression>) -> EvaluationResult {
   Ok(list)
 }
 
-/// Evaluate all the lines in the body, and return the last result
-fn _evaluate_procedure_body(body: &[Expression], scope: Rc<RefCell<Scope>>) -> ProcedureResult {
-  // let last_line = body.pop().unwrap();
+/// The values of an expression, which is usually just the expression itself
+pub fn values_vec(expression: Expression) -> Vec<Expression> {
+  match expression {
+    Expression::Values(values) => values,
+    value => vec![value],
+  }
+}
 
-  let mut body = body.iter();
-  let mut return_value = evaluate_in_tail_position(body.next().unwrap(), scope.clone())?;
-  for line in body {
-    return_value = evaluate_in_tail_position(line, scope.clone())?;
+/// The expression for some values, which is just the value if there is exactly one
+pub fn vec_values(mut values: Vec<Expression>) -> Expression {
+  if values.len() == 1 {
+    values.pop().unwrap()
+  } else {
+    Expression::Values(values)
   }
-  Ok(return_value)
 }
 
-fn _evaluate_procedure(
-  procedure: &Procedure,
-  args: &Expression,
+/// Evaluate all the lines in the body, and return the last result
+pub fn _evaluate_procedure_body(body: &[Expression], scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+  Ok(ProcedureValue::TailBody(body.to_vec(), scope))
+}
+
+/// An active dynamic-wind, and all the dynamic-winds it is nested inside of
+#[derive(Debug)]
+pub struct Winder {
+  before: Procedure,
+  after: Procedure,
   scope: Rc<RefCell<Scope>>,
-) -> ProcedureResult {
-  let args = arg_vec(&procedure.name(), args)?;
-  match &procedure {
-    Procedure::FixedArgumentForm(arg_names, body) => {
-      if args.len() != arg_names.len() {
-        return Err(EvaluationError::WrongNumberOfArguments(
-          "#<procedure>".to_string(),
-          arg_names.len(),
-          args.len(),
-        ));
-      }
-      // Bind the arguments to their symbols
-      let inner_scope = Scope::child(scope.clone());
-      for (arg_name, arg) in arg_names.iter().zip(args.iter()) {
-        inner_scope
-          .borrow_mut()
-          .define(arg_name, evaluate(&arg.clone(), scope.clone())?);
-      }
-      _evaluate_procedure_body(body, inner_scope)
-    }
-    Procedure::VariableArgumentForm(arg_names, vararg_name, body) => {
-      if args.len() < arg_names.len() {
-        return Err(EvaluationError::WrongNumberOfVariableArguments(
-          "#<procedure>".to_string(),
-          arg_names.len(),
-          args.len(),
-        ));
-      }
-      // Bind the arguments to their symbols
-      let inner_scope = Scope::child(scope.clone());
-      // Bind the named arguments
-      for (arg_name, arg) in arg_names.iter().zip(args.iter()) {
-        inner_scope
-          .borrow_mut()
-          .define(arg_name, evaluate(&arg.clone(), scope.clone())?);
+  depth: usize,
+  parent: Winders,
+}
+pub type Winders = Option<Rc<Winder>>;
+
+/// What to do with something that is raised
+#[derive(Debug)]
+enum Handle {
+  /// Call this procedure with it
+  Procedure(Procedure, Rc<RefCell<Scope>>),
+  /// Return to the continuation of a guard, and pass it on to the rest of the guard
+  Guard(Rc<Continuation>, Continue),
+}
+
+/// The current exception handler, and all the handlers that were installed before it
+#[derive(Debug)]
+pub struct Handler {
+  handle: Handle,
+  parent: Handlers,
+}
+pub type Handlers = Option<Rc<Handler>>;
+
+fn winders_depth(winders: &Winders) -> usize {
+  winders.as_ref().map_or(0, |winder| winder.depth)
+}
+
+/// Something the evaluator needs to do with a value once it has finished evaluating it
+#[derive(Clone, Debug)]
+pub enum Frame {
+  /// The value is the operator of this combination
+  Operator(Expression, Rc<RefCell<Scope>>),
+  /// The value is an argument to the procedure. The remaining operands are in reverse order.
+  Operands(
+    Procedure,
+    Vec<Expression>,
+    Vec<Expression>,
+    Rc<RefCell<Scope>>,
+  ),
+  /// The value is ignored, and the remaining lines of the body (in reverse order) are evaluated
+  Body(Vec<Expression>, Rc<RefCell<Scope>>),
+  /// The value is passed on to the rest of a builtin
+  Continue(Continue),
+  /// The value is the result of a before thunk, so what is inside the dynamic-wind can now be done
+  Wound(Rc<Winder>, Box<ProcedureValue>),
+  /// The value is the result of a dynamic-wind thunk, so the after thunk must be called
+  Wind(Rc<Winder>),
+  /// The value is ignored, and the thunk is called with the given winders active
+  Rewind(Procedure, Winders, Rc<RefCell<Scope>>),
+  /// The value is ignored, and this value is returned with the given winders active
+  Value(Expression, Winders),
+  /// The value is ignored, and this error is raised
+  Raise(EvaluationError),
+  /// The value is returned to compiled code
+  Execute(Execution),
+  /// The value is returned with the given exception handlers installed
+  Handlers(Handlers),
+}
+
+/// The state of an evaluation at the moment call/cc was called
+#[derive(Debug)]
+pub struct Continuation {
+  frames: Vec<Frame>,
+  winders: Winders,
+  handlers: Handlers,
+}
+// Continuations are only equal to themselves
+impl PartialEq for Continuation {
+  fn eq(&self, other: &Continuation) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Continuation {}
+
+pub fn check_arity(procedure: &Procedure, argc: usize) -> Result<(), EvaluationError> {
+  let (procedure_name, expected, variable) = match procedure {
+    Procedure::FixedArgumentForm(arg_names, _, _) => ("#<procedure>", arg_names.len(), false),
+    Procedure::VariableArgumentForm(arg_names, _, _, _) => ("#<procedure>", arg_names.len(), true),
+    Procedure::Compiled(lambda, _) => ("#<procedure>", lambda.argc(), lambda.variable),
+    Procedure::BuiltinFixedArgumentForm(procedure_name, _, argc)
+    | Procedure::SpecialFixedArgumentForm(procedure_name, _, argc) => {
+      (*procedure_name, *argc, false)
+    }
+    Procedure::BuiltinVariableArgumentForm(procedure_name, _, argc)
+    | Procedure::SpecialVariableArgumentForm(procedure_name, _, argc) => {
+      (*procedure_name, *argc, true)
+    }
+    Procedure::Continuation(_) => ("#<continuation>", 0, true),
+    Procedure::Record(procedure_name, record_procedure) => {
+      (procedure_name.as_str(), record_procedure.argc(), false)
+    }
+    Procedure::ClosureFixedArgumentForm(procedure_name, _, argc) => {
+      (procedure_name.as_str(), *argc, false)
+    }
+    Procedure::ClosureVariableArgumentForm(procedure_name, _, argc) => {
+      (procedure_name.as_str(), *argc, true)
+    }
+  };
+  if variable && argc < expected {
+    Err(EvaluationError::WrongNumberOfVariableArguments(
+      procedure_name.to_string(),
+      expected,
+      argc,
+    ))
+  } else if !variable && argc != expected {
+    Err(EvaluationError::WrongNumberOfArguments(
+      procedure_name.to_string(),
+      expected,
+      argc,
+    ))
+  } else {
+    Ok(())
+  }
+}
+
+/// Evaluates expressions using an explicit stack of frames instead of the Rust stack, so that
+/// the whole state of an evaluation can be captured by call/cc and resumed later, and so that
+/// deep recursion fails with a StackOverflow error instead of crashing.
+pub struct Machine {
+  pub stack: Vec<Frame>,
+  winders: Winders,
+  handlers: Handlers,
+  max_stack_depth: usize,
+  /// Set when the evaluation should stop
+  interrupt: Option<Arc<AtomicBool>>,
+  /// Whether expressions are compiled before they are evaluated, or evaluated by walking them
+  compiled: bool,
+}
+
+impl Machine {
+  fn new(compiled: bool) -> Machine {
+    Machine {
+      stack: vec![],
+      winders: None,
+      handlers: None,
+      max_stack_depth: MAX_STACK_DEPTH.with(|depth| depth.get()),
+      interrupt: INTERRUPT.with(|interrupt| interrupt.borrow().clone()),
+      compiled,
+    }
+  }
+
+  /// Fail with Interrupted if the interrupt flag has been set since the last time it was checked
+  pub fn check_interrupt(&self) -> Result<(), EvaluationError> {
+    match &self.interrupt {
+      Some(interrupt) if interrupt.swap(false, Ordering::Relaxed) => {
+        Err(EvaluationError::Interrupted)
       }
-      let varargs = args[arg_names.len()..].to_vec();
-      let mut evaluated_varargs = vec![];
-      for vararg in &varargs {
-        evaluated_varargs.push(evaluate(vararg, scope.clone())?);
+      _ => Ok(()),
+    }
+  }
+
+  pub fn push(&mut self, frame: Frame) -> Result<(), EvaluationError> {
+    if self.stack.len() >= self.max_stack_depth {
+      return Err(EvaluationError::StackOverflow(self.max_stack_depth));
+    }
+    self.stack.push(frame);
+    Ok(())
+  }
+
+  fn run(&mut self, mut procedure_value: ProcedureValue) -> EvaluationResult {
+    loop {
+      if self.stack.is_empty() {
+        if let ProcedureValue::Expression(value) = procedure_value {
+          return Ok(value);
+        }
       }
-      inner_scope
-        .borrow_mut()
-        .define(vararg_name, vec_arg(evaluated_varargs)?);
-      _evaluate_procedure_body(body, inner_scope)
+      let step = match self.check_interrupt() {
+        Ok(()) => self.step(procedure_value),
+        Err(err) => Err(err),
+      };
+      let step = match step {
+        Ok(_) if self.stack.len() > self.max_stack_depth => {
+          Err(EvaluationError::StackOverflow(self.max_stack_depth))
+        }
+        step => step,
+      };
+      procedure_value = match step {
+        Ok(procedure_value) => procedure_value,
+        // Interrupting and exiting abandon the evaluation, so they can't be handled like other
+        // errors, but the program still gets to clean up on the way out
+        Err(err @ EvaluationError::Interrupted) | Err(err @ EvaluationError::Exit(_)) => {
+          self.fail(err)?
+        }
+        // Errors are raised as error objects, so that they can be handled
+        Err(err) => self.raise(Expression::Error(Rc::new(err)), false)?,
+      };
     }
-    Procedure::BuiltinFixedArgumentForm(procedure_name, builtin, argc) => {
-      if args.len() != *argc {
-        return Err(EvaluationError::WrongNumberOfArguments(
-          procedure_name.to_string(),
-          *argc,
-          args.len(),
-        ));
+  }
+
+  /// Do one thing that a procedure value asks for
+  fn step(&mut self, procedure_value: ProcedureValue) -> ProcedureResult {
+    match procedure_value {
+      ProcedureValue::Expression(expression) => {
+        let frame = self
+          .stack
+          .pop()
+          .expect("values are returned once the stack is empty");
+        self.resume(frame, expression)
+      }
+      ProcedureValue::TailCall(expression, scope) if self.compiled => {
+        let code = compile_cached(std::slice::from_ref(&expression), &scope);
+        self.execute(Execution::new(code, scope))
+      }
+      ProcedureValue::TailCall(expression, scope) => self.evaluate(expression, scope),
+      ProcedureValue::TailBody(body, scope) if self.compiled => {
+        let code = compile_cached(&body, &scope);
+        self.execute(Execution::new(code, scope))
+      }
+      ProcedureValue::TailBody(mut body, scope) => {
+        body.reverse();
+        match body.pop() {
+          Some(line) => {
+            if !body.is_empty() {
+              self.stack.push(Frame::Body(body, scope.clone()));
+            }
+            Ok(ProcedureValue::TailCall(line, scope))
+          }
+          None => Ok(ProcedureValue::Expression(void!())),
+        }
+      }
+      ProcedureValue::TailApply(procedure, args, scope) => self.apply(procedure, args, scope),
+      ProcedureValue::Evaluate(expression, scope, continue_with) => {
+        self.stack.push(Frame::Continue(continue_with));
+        Ok(ProcedureValue::TailCall(expression, scope))
+      }
+      ProcedureValue::CallWithCurrentContinuation(procedure, scope) => {
+        let continuation = Rc::new(self.continuation());
+        let continuation = Expression::Procedure(Procedure::Continuation(continuation));
+        self.apply(procedure, vec![continuation], scope)
+      }
+      ProcedureValue::DynamicWind(before, inside, after, scope) => {
+        let winder = Rc::new(Winder {
+          before: before.clone(),
+          after,
+          scope: scope.clone(),
+          depth: winders_depth(&self.winders) + 1,
+          parent: self.winders.clone(),
+        });
+        self.stack.push(Frame::Wound(winder, inside));
+        self.apply(before, vec![], scope)
+      }
+      ProcedureValue::WithExceptionHandler(handler, thunk, scope) => {
+        self.stack.push(Frame::Handlers(self.handlers.clone()));
+        self.handlers = Some(Rc::new(Handler {
+          handle: Handle::Procedure(handler, scope.clone()),
+          parent: self.handlers.clone(),
+        }));
+        self.apply(thunk, vec![], scope)
+      }
+      ProcedureValue::Raise(raised) => self.raise(raised, false),
+      ProcedureValue::RaiseContinuable(raised) => self.raise(raised, true),
+      ProcedureValue::Guard(body, scope, continue_with) => {
+        let continuation = Rc::new(self.continuation());
+        self.stack.push(Frame::Handlers(self.handlers.clone()));
+        self.handlers = Some(Rc::new(Handler {
+          handle: Handle::Guard(continuation, continue_with),
+          parent: self.handlers.clone(),
+        }));
+        Ok(ProcedureValue::TailBody(body, scope))
       }
-      builtin(args, scope)
     }
-    Procedure::BuiltinVariableArgumentForm(procedure_name, builtin, argc) => {
-      if args.len() < *argc {
-        return Err(EvaluationError::WrongNumberOfVariableArguments(
-          procedure_name.to_string(),
-          *argc,
-          args.len(),
-        ));
+  }
+
+  fn evaluate(&mut self, expression: Expression, scope: Rc<RefCell<Scope>>) -> ProcedureResult {
+    match expression {
+      Expression::Symbol(symbol) => Ok(ProcedureValue::Expression(scope.borrow().lookup(&symbol)?)),
+      Expression::Cons(ref cons) => {
+        let operator = cons.car.as_ref().clone();
+        self.stack.push(Frame::Operator(expression, scope.clone()));
+        Ok(ProcedureValue::TailCall(operator, scope))
       }
-      let varargs = args[*argc..].to_vec();
-      let args = args[0..*argc].to_vec();
-      builtin(args, varargs, scope)
+      _ => Ok(ProcedureValue::Expression(expression)),
     }
   }
-}
-fn evaluate_procedure(
-  procedure: &Procedure,
-  args: &Expression,
-  scope: Rc<RefCell<Scope>>,
-) -> EvaluationResult {
-  let mut procedure_value = _evaluate_procedure(procedure, args, scope)?;
-  while let ProcedureValue::TailCall(child_procedure, args, arg_scope) = procedure_value {
-    procedure_value = _evaluate_procedure(&child_procedure, &args, arg_scope)?
+
+  pub fn resume(&mut self, frame: Frame, value: Expression) -> ProcedureResult {
+    match frame {
+      Frame::Operator(combination, scope) => match value {
+        Expression::Procedure(procedure) => {
+          let operands = match &combination {
+            Expression::Cons(cons) => arg_vec(&procedure.name(), &cons.cdr)?,
+            _ => unreachable!(),
+          };
+          check_arity(&procedure, operands.len())?;
+          match procedure {
+            // Special forms get their operands without evaluating them
+            Procedure::SpecialFixedArgumentForm(_, special_form, _) => {
+              special_form(operands, scope)
+            }
+            Procedure::SpecialVariableArgumentForm(_, special_form, argc) => {
+              let varargs = operands[argc..].to_vec();
+              let args = operands[0..argc].to_vec();
+              special_form(args, varargs, scope)
+            }
+            procedure => {
+              let mut operands = operands;
+              operands.reverse();
+              match operands.pop() {
+                Some(operand) => {
+                  self
+                    .stack
+                    .push(Frame::Operands(procedure, vec![], operands, scope.clone()));
+                  Ok(ProcedureValue::TailCall(operand, scope))
+                }
+                None => self.apply(procedure, vec![], scope),
+              }
+            }
+          }
+        }
+        // Macro uses are replaced by their expansion, which is then evaluated in their place
+        Expression::Macro(syntax_rules) => Ok(ProcedureValue::TailCall(
+          syntax_rules.expand(&combination)?,
+          scope,
+        )),
+        non_procedure => Err(EvaluationError::NotAProcedure(Box::new(non_procedure))),
+      },
+      Frame::Operands(procedure, mut args, mut operands, scope) => {
+        args.push(value);
+        match operands.pop() {
+          Some(operand) => {
+            self
+              .stack
+              .push(Frame::Operands(procedure, args, operands, scope.clone()));
+            Ok(ProcedureValue::TailCall(operand, scope))
+          }
+          None => self.apply(procedure, args, scope),
+        }
+      }
+      Frame::Body(mut body, scope) => {
+        let line = body.pop().unwrap();
+        if !body.is_empty() {
+          self.stack.push(Frame::Body(body, scope.clone()));
+        }
+        Ok(ProcedureValue::TailCall(line, scope))
+      }
+      Frame::Continue(continue_with) => (continue_with.0)(value),
+      Frame::Wound(winder, inside) => {
+        self.winders = Some(winder.clone());
+        self.stack.push(Frame::Wind(winder));
+        Ok(*inside)
+      }
+      Frame::Wind(winder) => {
+        self.winders = winder.parent.clone();
+        self.stack.push(Frame::Value(value, winder.parent.clone()));
+        self.apply(winder.after.clone(), vec![], winder.scope.clone())
+      }
+      Frame::Rewind(thunk, winders, scope) => {
+        self.winders = winders;
+        self.apply(thunk, vec![], scope)
+      }
+      Frame::Value(value, winders) => {
+        self.winders = winders;
+        Ok(ProcedureValue::Expression(value))
+      }
+      Frame::Raise(err) => Err(err),
+      Frame::Handlers(handlers) => {
+        self.handlers = handlers;
+        Ok(ProcedureValue::Expression(value))
+      }
+      Frame::Execute(mut execution) => {
+        execution.push(value);
+        self.execute(execution)
+      }
+    }
   }
-  match procedure_value {
-    ProcedureValue::Expression(expression) => Ok(expression),
-    ProcedureValue::TailCall(_, _, _) => panic!(),
+
+  pub fn apply(
+    &mut self,
+    procedure: Procedure,
+    args: Vec<Expression>,
+    scope: Rc<RefCell<Scope>>,
+  ) -> ProcedureResult {
+    check_arity(&procedure, args.len())?;
+    if let Some(name) = traced_name(&procedure) {
+      return self.apply_traced(name, procedure, args, scope);
+    }
+    self.apply_procedure(procedure, args, scope)
   }
-}
 
-/// This should behave identically to evaluate, but return a wrapper around a procedure call
-/// instead of calling it immediately. This allows evaluate_procedure to call the procedure in a
-/// loop instead of evaluating it recursively.
-/// This function should always be used to evaluate any expression in a tail position.
-pub fn evaluate_in_tail_position(
-  expression: &Expression,
-  scope: Rc<RefCell<Scope>>,
-) -> ProcedureResult {
-  match expression {
-    Expression::Symbol(symbol) => Ok(ProcedureValue::Expression(scope.borrow().lookup(symbol)?)),
-    Expression::Cons(cons) => match evaluate(cons.car.as_ref(), scope.clone())? {
-      Expression::Procedure(procedure) => {
-        // Save the procedure call in a TailCall rather than executing it immediately
-        Ok(ProcedureValue::TailCall(
-          procedure,
-          cons.cdr.as_ref().clone(),
-          scope,
-        ))
+  /// Apply a traced procedure, printing the call now and the value it returns once it returns
+  fn apply_traced(
+    &mut self,
+    name: String,
+    procedure: Procedure,
+    args: Vec<Expression>,
+    scope: Rc<RefCell<Scope>>,
+  ) -> ProcedureResult {
+    let depth = TRACE_DEPTH.with(|depth| depth.replace(depth.get() + 1));
+    let indent = " ".repeat(depth);
+    let call = once(name)
+      .chain(args.iter().map(|arg| arg.to_string()))
+      .collect::<Vec<_>>()
+      .join(" ");
+    let port = current_output_port();
+    port.write_str(&format!("{}({})\n", indent, call))?;
+    self.push(Frame::Continue(Continue::new(move |value| {
+      TRACE_DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+      port.write_str(&format!("{}=> {}\n", indent, value))?;
+      Ok(ProcedureValue::Expression(value))
+    })))?;
+    self.apply_procedure(procedure, args, scope)
+  }
+
+  fn apply_procedure(
+    &mut self,
+    procedure: Procedure,
+    mut args: Vec<Expression>,
+    scope: Rc<RefCell<Scope>>,
+  ) -> ProcedureResult {
+    match procedure {
+      Procedure::FixedArgumentForm(arg_names, body, procedure_scope) => {
+        // Bind the arguments to their symbols
+        let inner_scope = Scope::child(procedure_scope);
+        for (arg_name, arg) in arg_names.iter().zip(args) {
+          inner_scope.borrow_mut().define(arg_name, arg);
+        }
+        _evaluate_procedure_body(&body, inner_scope)
+      }
+      Procedure::VariableArgumentForm(arg_names, vararg_name, body, procedure_scope) => {
+        // Bind the arguments to their symbols
+        let inner_scope = Scope::child(procedure_scope);
+        let varargs = args.split_off(arg_names.len());
+        // Bind the named arguments
+        for (arg_name, arg) in arg_names.iter().zip(args) {
+          inner_scope.borrow_mut().define(arg_name, arg);
+        }
+        inner_scope
+          .borrow_mut()
+          .define(&vararg_name, vec_arg(varargs)?);
+        _evaluate_procedure_body(&body, inner_scope)
       }
-      non_procedure => Err(EvaluationError::NotAProcedure(non_procedure)),
-    },
-    _ => Ok(ProcedureValue::Expression(expression.clone())),
+      Procedure::Compiled(lambda, procedure_scope) => {
+        let inner_scope = bind_arguments(&lambda, procedure_scope, args)?;
+        if self.compiled {
+          self.execute(Execution::new(lambda.code.clone(), inner_scope))
+        } else {
+          _evaluate_procedure_body(&lambda.body, inner_scope)
+        }
+      }
+      Procedure::BuiltinFixedArgumentForm(_, builtin, _)
+      | Procedure::SpecialFixedArgumentForm(_, builtin, _) => builtin(args, scope),
+      Procedure::BuiltinVariableArgumentForm(_, builtin, argc)
+      | Procedure::SpecialVariableArgumentForm(_, builtin, argc) => {
+        let varargs = args.split_off(argc);
+        builtin(args, varargs, scope)
+      }
+      Procedure::Continuation(continuation) => {
+        let value = vec_values(args);
+        self.handlers = continuation.handlers.clone();
+        Ok(self.unwind(&continuation.winders, continuation.frames.clone(), value))
+      }
+      Procedure::Record(procedure_name, record_procedure) => Ok(ProcedureValue::Expression(
+        apply_record_procedure(&procedure_name, &record_procedure, args)?,
+      )),
+      Procedure::ClosureFixedArgumentForm(_, closure, _)
+      | Procedure::ClosureVariableArgumentForm(_, closure, _) => {
+        Ok(ProcedureValue::Expression((closure.0)(args)?))
+      }
+    }
+  }
+
+  /// Replace the stack with frames, calling the after thunks of every dynamic-wind being left and
+  /// the before thunks of every dynamic-wind being entered before value is returned to frames.
+  fn unwind(&mut self, winders: &Winders, frames: Vec<Frame>, value: Expression) -> ProcedureValue {
+    let mut from = self.winders.clone();
+    let mut to = winders.clone();
+    let mut leaving = vec![];
+    let mut entering = vec![];
+    while winders_depth(&from) > winders_depth(&to) {
+      let winder = from.unwrap();
+      from = winder.parent.clone();
+      leaving.push(winder);
+    }
+    while winders_depth(&to) > winders_depth(&from) {
+      let winder = to.unwrap();
+      to = winder.parent.clone();
+      entering.push(winder);
+    }
+    while let (Some(from_winder), Some(to_winder)) = (&from, &to) {
+      if Rc::ptr_eq(from_winder, to_winder) {
+        break;
+      }
+      let (from_winder, to_winder) = (from_winder.clone(), to_winder.clone());
+      from = from_winder.parent.clone();
+      to = to_winder.parent.clone();
+      leaving.push(from_winder);
+      entering.push(to_winder);
+    }
+    self.stack = frames;
+    self.stack.push(Frame::Value(value, winders.clone()));
+    // The stack is a stack, so the thunks are pushed in the reverse order they should be called:
+    // the before thunks from the innermost out, then the after thunks from the outermost in.
+    for winder in entering {
+      self.stack.push(Frame::Rewind(
+        winder.before.clone(),
+        winder.parent.clone(),
+        winder.scope.clone(),
+      ));
+    }
+    for winder in leaving.into_iter().rev() {
+      self.stack.push(Frame::Rewind(
+        winder.after.clone(),
+        winder.parent.clone(),
+        winder.scope.clone(),
+      ));
+    }
+    ProcedureValue::Expression(void!())
+  }
+
+  /// Capture the current state of the evaluation
+  fn continuation(&self) -> Continuation {
+    Continuation {
+      frames: self.stack.clone(),
+      winders: self.winders.clone(),
+      handlers: self.handlers.clone(),
+    }
+  }
+
+  /// Pass something that was raised to the current exception handler, with the handlers that
+  /// were installed before it in place while it runs.
+  /// If the handler returns from a raise that isn't continuable, what was raised is raised again
+  /// to the next handler out.
+  fn raise(&mut self, raised: Expression, continuable: bool) -> ProcedureResult {
+    let handler = match self.handlers.clone() {
+      Some(handler) => handler,
+      None => return self.fail(uncaught(raised)),
+    };
+    match &handler.handle {
+      Handle::Procedure(procedure, scope) => {
+        // These frames are pushed without checking the stack depth, so that a stack overflow
+        // can be handled too
+        self.stack.push(Frame::Handlers(self.handlers.clone()));
+        if !continuable {
+          self.stack.push(Frame::Raise(uncaught(raised.clone())));
+        }
+        self.handlers = handler.parent.clone();
+        self.apply(procedure.clone(), vec![raised], scope.clone())
+      }
+      Handle::Guard(continuation, continue_with) => {
+        let mut frames = continuation.frames.clone();
+        frames.push(Frame::Continue(continue_with.clone()));
+        self.handlers = continuation.handlers.clone();
+        Ok(self.unwind(&continuation.winders, frames, raised))
+      }
+    }
+  }
+
+  /// Abandon the evaluation because of an error, calling the after thunks of any active
+  /// dynamic-winds on the way out.
+  fn fail(&mut self, err: EvaluationError) -> ProcedureResult {
+    if self.winders.is_none() {
+      return Err(err);
+    }
+    let procedure_value = self.unwind(&None, vec![], void!());
+    // Replace the final value with the error
+    self.stack[0] = Frame::Raise(err);
+    Ok(procedure_value)
   }
 }
 
+/// Evaluate an expression by compiling it and executing the compiled code
 pub fn evaluate(expression: &Expression, scope: Rc<RefCell<Scope>>) -> EvaluationResult {
-  evaluate_in_tail_position(expression, scope)?.resolve()
+  Machine::new(true).run(ProcedureValue::TailCall(expression.clone(), scope))
+}
+
+/// Call a procedure with arguments which have already been evaluated
+pub fn apply(
+  procedure: Procedure,
+  args: Vec<Expression>,
+  scope: Rc<RefCell<Scope>>,
+) -> EvaluationResult {
+  Machine::new(true).run(ProcedureValue::TailApply(procedure, args, scope))
+}
+
+/// Evaluate an expression by walking the expression tree instead of compiling it.
+/// This is much slower, but simple enough to check the compiler against.
+#[cfg(test)]
+pub fn interpret(expression: &Expression, scope: Rc<RefCell<Scope>>) -> EvaluationResult {
+  Machine::new(false).run(ProcedureValue::TailCall(expression.clone(), scope))
 }
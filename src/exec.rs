@@ -1,79 +1,247 @@
-use crate::parse::parse_expression;
+use crate::editor::{editor, history_path};
+use crate::evaluate::{set_interrupt_flag, EvaluationError};
+use crate::meta::{Next, Session};
+use crate::parse::{parse_expression, skip_header, ParseError, ParseResult};
 use crate::token::pop_token;
 use crate::*;
-use crate::{define_builtins, evaluate, parse, Expression, Scope};
+use crate::{evaluate, Scope};
+use rustyline::error::ReadlineError;
 use std::cell::RefCell;
-use std::fs::File;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::io::prelude::*;
-use std::io::{self, Write};
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-pub fn exec_file(filename: &str) -> io::Result<()> {
-    let scope = Rc::new(RefCell::new(Scope::new()));
-    define_builtins(scope.clone());
+/// Why running a program failed
+#[derive(Debug)]
+pub enum ExecError {
+    /// The program couldn't be read, from the file or whatever else is named
+    Io(String, io::Error),
+    Parse(ParseError),
+    Evaluation(EvaluationError),
+}
+
+/// The status to exit with when a program can't be evaluated
+pub const EXIT_EVALUATION_ERROR: i32 = 1;
+/// The status to exit with when a program can't be parsed
+pub const EXIT_PARSE_ERROR: i32 = 3;
+/// The status to exit with when a program can't be read
+pub const EXIT_IO_ERROR: i32 = 4;
+
+impl ExecError {
+    /// The status the process should exit with because of this error
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            ExecError::Io(_, _) => EXIT_IO_ERROR,
+            ExecError::Parse(_) => EXIT_PARSE_ERROR,
+            ExecError::Evaluation(EvaluationError::Exit(status)) => *status,
+            ExecError::Evaluation(_) => EXIT_EVALUATION_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::Io(name, err) => write!(fmt, "Error reading {}: {}", name, err),
+            ExecError::Parse(err) => write!(fmt, "Error parsing input: {:?}", err),
+            ExecError::Evaluation(err) => write!(fmt, "Error evaluating input: {}", err),
+        }
+    }
+}
+
+/// Run the program in a file. Unless quiet, the value of every top-level expression is printed.
+pub fn exec_file(filename: &str, scope: Rc<RefCell<Scope>>, quiet: bool) -> Result<(), ExecError> {
+    let contents =
+        fs::read_to_string(filename).map_err(|err| ExecError::Io(filename.to_string(), err))?;
+    let contents = skip_header(&contents)
+        .map_err(ExecError::Parse)?
+        .to_string();
+
+    // Files that the program loads are found relative to it
+    crate::load::enter(Path::new(filename)).map_err(ExecError::Evaluation)?;
+    let result = exec_contents(contents, scope, quiet);
+    crate::load::leave();
+    result
+}
 
-    let mut file = File::open(filename)?;
+/// Run the program read from stdin
+pub fn exec_stdin(scope: Rc<RefCell<Scope>>, quiet: bool) -> Result<(), ExecError> {
     let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    io::stdin()
+        .read_to_string(&mut contents)
+        .map_err(|err| ExecError::Io("stdin".to_string(), err))?;
+    let contents = skip_header(&contents)
+        .map_err(ExecError::Parse)?
+        .to_string();
+    exec_contents(contents, scope, quiet)
+}
+
+/// Run the expressions given on the command line with -e
+pub fn exec_expression(
+    expression: &str,
+    scope: Rc<RefCell<Scope>>,
+    quiet: bool,
+) -> Result<(), ExecError> {
+    exec_contents(expression.to_string(), scope, quiet)
+}
 
+/// Evaluate every expression in a program, stopping at the first one that fails
+fn exec_contents(
+    mut contents: String,
+    scope: Rc<RefCell<Scope>>,
+    quiet: bool,
+) -> Result<(), ExecError> {
     while let (Some(_), _) = pop_token(&contents) {
         let (result, remainder) = parse_expression(&contents);
         contents = remainder;
-        match result {
-            Ok(input_expression) => {
-                if input_expression == void!() {
-                    return Ok(());
-                }
-                let evaluation = match evaluate(&input_expression, scope.clone()) {
-                    Ok(expression) => expression,
-                    Err(err) => {
-                        println!("Error evaluating input: {}", err);
-                        return Ok(());
-                    }
-                };
-                if evaluation != Expression::Void {
-                    println!("{}", evaluation.outer_representation());
-                }
-            }
-            Err(err) => {
-                println!("Error parsing input: {:?}", err);
-                return Ok(());
-            }
+        let input_expression = result.map_err(ExecError::Parse)?;
+        if input_expression == void!() {
+            return Ok(());
+        }
+        let evaluation =
+            evaluate(&input_expression, scope.clone()).map_err(ExecError::Evaluation)?;
+        if !quiet && !evaluation.is_empty_result() {
+            println!("{}", evaluation.outer_representation());
         }
     }
     Ok(())
 }
 
-pub fn repl() -> io::Result<()> {
-    let scope = Rc::new(RefCell::new(Scope::new()));
-    define_builtins(scope.clone());
-    loop {
-        let mut input = String::new();
-        print!("> ");
-        io::stdout().flush()?;
-        if io::stdin().read_line(&mut input)? == 0 {
-            return Ok(());
+/// Parse the complete expressions at the start of some input.
+/// Whatever is left after them is the start of an expression which continues on the next line,
+/// unless it can't be parsed at all, in which case the error ends the results instead.
+fn read_forms(input: &str) -> (Vec<ParseResult>, String) {
+    let mut forms = vec![];
+    let mut input = input.to_string();
+    while let (Some(_), _) = pop_token(&input) {
+        match parse_expression(&input) {
+            (Err(ParseError::UnexpectedEOF), _) => return (forms, input),
+            (Err(err), _) => {
+                forms.push(Err(err));
+                return (forms, String::new());
+            }
+            (Ok(form), remainder) => {
+                forms.push(Ok(form));
+                input = remainder;
+            }
         }
+    }
+    (forms, String::new())
+}
+
+/// Turn an error from the line editor into the kind of error the REPL returns
+fn editor_error(err: ReadlineError) -> ExecError {
+    let err = match err {
+        ReadlineError::Io(err) => err,
+        err => io::Error::other(err),
+    };
+    ExecError::Io("input".to_string(), err)
+}
 
-        let input_expression = match parse(&input) {
-            Ok(expression) => expression,
-            Err(err) => {
-                println!("Error parsing input: {:?}", err);
+/// Make Ctrl-C interrupt whatever is being evaluated, instead of ending the process.
+/// The line editor handles Ctrl-C itself while input is being typed.
+fn handle_interrupts() -> io::Result<Arc<AtomicBool>> {
+    let interrupt = Arc::new(AtomicBool::new(false));
+    set_interrupt_flag(interrupt.clone());
+    let handler_interrupt = interrupt.clone();
+    ctrlc::set_handler(move || handler_interrupt.store(true, Ordering::Relaxed))
+        .map_err(io::Error::other)?;
+    Ok(interrupt)
+}
+
+pub fn repl(scope: Rc<RefCell<Scope>>) -> Result<(), ExecError> {
+    let interrupt =
+        handle_interrupts().map_err(|err| ExecError::Io("interrupts".to_string(), err))?;
+    let mut session = Session::with_scope(scope);
+    let mut editor = editor(session.scope.clone()).map_err(editor_error)?;
+    // The start of an expression that hasn't been finished yet
+    let mut pending = String::new();
+    // Set when the program exits, which ends the REPL with its status
+    let mut result = Ok(());
+    'repl: loop {
+        let prompt = if pending.is_empty() { "> " } else { ". " };
+        let input = match editor.readline(prompt) {
+            Ok(input) => input,
+            // Ctrl-C throws away whatever has been typed so far
+            Err(ReadlineError::Interrupted) => {
+                pending.clear();
                 continue;
             }
+            Err(ReadlineError::Eof) => {
+                if !pending.is_empty() {
+                    eprintln!("{}", ExecError::Parse(ParseError::UnexpectedEOF));
+                }
+                break;
+            }
+            Err(err) => return Err(editor_error(err)),
         };
+        if !input.trim().is_empty() {
+            editor.add_history_entry(&input).map_err(editor_error)?;
+        }
+        // Ctrl-C pressed after the last evaluation finished shouldn't interrupt this one
+        interrupt.store(false, Ordering::Relaxed);
+        // Meta-commands like :load file start with a colon, which expressions never do
+        if pending.is_empty() && input.trim_start().starts_with(':') {
+            match session.run(&input) {
+                Ok(Next::Continue) => (),
+                Ok(Next::Quit) => break,
+                Err(err @ EvaluationError::Exit(_)) => {
+                    result = Err(ExecError::Evaluation(err));
+                    break;
+                }
+                Err(err) => eprintln!("Error running command: {}", err),
+            }
+            // The session may have started over with a new scope
+            if let Some(helper) = editor.helper_mut() {
+                helper.scope = session.scope.clone();
+            }
+            continue;
+        }
+        pending.push_str(&input);
+        pending.push('\n');
 
-        let evaluation = match evaluate(&input_expression, scope.clone()) {
-            Ok(expression) => expression,
-            Err(err) => {
-                println!("Error evaluating input: {}", err);
-                continue;
+        let (forms, rest) = read_forms(&pending);
+        pending = rest;
+        for form in forms {
+            let input_expression = match form {
+                Ok(expression) => expression,
+                Err(err) => {
+                    eprintln!("{}", ExecError::Parse(err));
+                    break;
+                }
+            };
+
+            let evaluation = match session.evaluate(&input_expression) {
+                Ok(expression) => expression,
+                // The rest of the line is abandoned along with the interrupted expression
+                Err(EvaluationError::Interrupted) => {
+                    eprintln!("Interrupted");
+                    break;
+                }
+                Err(err @ EvaluationError::Exit(_)) => {
+                    result = Err(ExecError::Evaluation(err));
+                    break 'repl;
+                }
+                Err(err) => {
+                    eprintln!("{}", ExecError::Evaluation(err));
+                    continue;
+                }
+            };
+            if !evaluation.is_empty_result() {
+                println!("{}", evaluation.outer_representation());
             }
-        };
-        if evaluation != Expression::Void {
-            println!("{}", evaluation.outer_representation());
         }
     }
+    if let Some(path) = history_path() {
+        // Losing the history isn't worth failing over
+        editor.save_history(&path).ok();
+    }
+    result
 }
 
 #[cfg(test)]
@@ -82,6 +250,43 @@ mod test {
 
     #[test]
     fn test_exec_file() {
-        exec_file("test/src/mergesort.lisp").unwrap();
+        exec_file("test/src/mergesort.lisp", Scope::builtins(), false).unwrap();
+        // A program that is already being loaded can't be run again from inside of itself
+        let path = Path::new("test/src/mergesort.lisp");
+        crate::load::enter(path).unwrap();
+        let result = exec_file("test/src/mergesort.lisp", Scope::builtins(), true);
+        crate::load::leave();
+        assert!(matches!(
+            result,
+            Err(ExecError::Evaluation(EvaluationError::LoadCycle(_)))
+        ));
+    }
+
+    #[test]
+    fn test_read_forms() {
+        assert_eq!(read_forms("  \n"), (vec![], String::new()));
+        assert_eq!(
+            read_forms("(define x 1) x\n"),
+            (
+                vec![
+                    Ok(list!(symbol!("define"), symbol!("x"), int!(1))),
+                    Ok(symbol!("x"))
+                ],
+                String::new()
+            )
+        );
+        // An unfinished expression is kept for the next line
+        assert_eq!(
+            read_forms("x (define (f)\n"),
+            (vec![Ok(symbol!("x"))], " (define (f)\n".to_string())
+        );
+        assert_eq!(read_forms("\"a\n"), (vec![], "\"a\n".to_string()));
+        assert_eq!(
+            read_forms("x ) y\n"),
+            (
+                vec![Ok(symbol!("x")), Err(ParseError::UnexpectedClosingParen)],
+                String::new()
+            )
+        );
     }
 }
@@ -0,0 +1,317 @@
+use crate::convert::TypedFn;
+use crate::evaluate::{apply, EvaluationError, EvaluationResult};
+use crate::load::{enter, leave, read_file};
+use crate::parse::{parse_all, skip_header};
+use crate::*;
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+/// An interpreter with its own global scope, for running Lisp from inside another program.
+/// Everything evaluated by the same interpreter sees what the rest of it defined.
+///
+/// Only the global scope is the interpreter's own. The current ports, the files being loaded, the
+/// load path, the command line, traced procedures, the interrupt flag and the stack limit belong
+/// to the thread, so interpreters on the same thread share them.
+pub struct Interpreter {
+  scope: Rc<RefCell<Scope>>,
+}
+
+impl Default for Interpreter {
+  fn default() -> Interpreter {
+    Interpreter::new()
+  }
+}
+
+impl Interpreter {
+  /// An interpreter with nothing but the builtins defined
+  pub fn new() -> Interpreter {
+    Interpreter {
+      scope: Scope::builtins(),
+    }
+  }
+
+  /// The global scope that everything is evaluated in
+  pub fn scope(&self) -> Rc<RefCell<Scope>> {
+    self.scope.clone()
+  }
+
+  fn eval_all(&self, expressions: Vec<Expression>) -> EvaluationResult {
+    let mut value = void!();
+    for expression in expressions {
+      value = evaluate(&expression, self.scope.clone())?;
+    }
+    Ok(value)
+  }
+
+  /// Evaluate every expression in some source code, and return the value of the last one
+  pub fn eval_str(&self, source: &str) -> EvaluationResult {
+    let expressions = skip_header(source)
+      .and_then(parse_all)
+      .map_err(|err| EvaluationError::ParseError("input".to_string(), err))?;
+    self.eval_all(expressions)
+  }
+
+  /// Evaluate every expression in a file, and return the value of the last one.
+  /// Files it loads are found relative to it, like with load.
+  pub fn eval_file<P: AsRef<Path>>(&self, path: P) -> EvaluationResult {
+    let path = path.as_ref();
+    enter(path)?;
+    let result = read_file(path).and_then(|expressions| self.eval_all(expressions));
+    leave();
+    result
+  }
+
+  /// Define a name in the global scope, like (define name value)
+  pub fn define_value(&self, name: &str, value: Expression) {
+    self.scope.borrow_mut().define(name, value);
+  }
+
+  /// Define a procedure which calls a Rust closure with exactly argc arguments.
+  /// The closure can capture whatever state it needs, like a handle to something in the program.
+  pub fn register_fn<F>(&self, name: &str, argc: usize, function: F)
+  where
+    F: Fn(Vec<Expression>) -> EvaluationResult + 'static,
+  {
+    let closure = BuiltinClosure(Rc::new(function));
+    self.define_value(
+      name,
+      Expression::Procedure(Procedure::ClosureFixedArgumentForm(
+        name.to_string(),
+        closure,
+        argc,
+      )),
+    );
+  }
+
+  /// Define a procedure which calls a Rust closure with argc or more arguments
+  pub fn register_variadic_fn<F>(&self, name: &str, argc: usize, function: F)
+  where
+    F: Fn(Vec<Expression>) -> EvaluationResult + 'static,
+  {
+    let closure = BuiltinClosure(Rc::new(function));
+    self.define_value(
+      name,
+      Expression::Procedure(Procedure::ClosureVariableArgumentForm(
+        name.to_string(),
+        closure,
+        argc,
+      )),
+    );
+  }
+
+  /// Define a procedure which calls a Rust function, like `fn(i64, Vec<String>) -> bool`, with
+  /// its arguments converted from Lisp values and its result converted back into one.
+  /// Arguments of the wrong type are reported like they are for builtins.
+  pub fn register_typed_fn<Args, F>(&self, name: &str, function: F)
+  where
+    F: TypedFn<Args> + 'static,
+  {
+    let procedure_name = name.to_string();
+    self.register_fn(name, F::argc(), move |args| {
+      function.call(&procedure_name, args)
+    });
+  }
+
+  /// What a name is defined as in the global scope, if it is defined at all
+  pub fn get(&self, name: &str) -> Option<Expression> {
+    self.scope.borrow().lookup(name).ok()
+  }
+
+  /// Call the procedure a name is defined as with some arguments, which aren't evaluated first
+  pub fn call(&self, name: &str, args: Vec<Expression>) -> EvaluationResult {
+    // The scope mustn't still be borrowed while the procedure runs, since it may define things
+    let value = self.scope.borrow().lookup(name)?;
+    match value {
+      Expression::Procedure(procedure) => apply(procedure, args, self.scope.clone()),
+      non_procedure => Err(EvaluationError::NotAProcedure(Box::new(non_procedure))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::test::TestFiles;
+
+  #[test]
+  fn test_eval_str() {
+    let interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval_str("(define x 2) (+ x 1)"), Ok(int!(3)));
+    assert_eq!(interpreter.eval_str(""), Ok(void!()));
+    assert_eq!(
+      interpreter.eval_str("(+ x"),
+      Err(EvaluationError::ParseError(
+        "input".to_string(),
+        ParseError::UnexpectedEOF
+      ))
+    );
+    assert_eq!(
+      interpreter.eval_str("(car x)"),
+      Err(EvaluationError::invalid_argument("car", "list", &int!(2)))
+    );
+  }
+
+  #[test]
+  fn test_eval_file() {
+    let files = TestFiles::new(
+      "interpreter",
+      &[
+        ("main.lisp", "#lang racket\n(load \"util.lisp\") (double 4)"),
+        ("util.lisp", "(define double (lambda (x) (* 2 x)))"),
+      ],
+    );
+    let interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval_file(files.path("main.lisp")), Ok(int!(8)));
+    assert!(interpreter.get("double").is_some());
+  }
+
+  #[test]
+  fn test_register_fn() {
+    use std::cell::Cell;
+
+    let interpreter = Interpreter::new();
+    let count = Rc::new(Cell::new(0));
+    let counter = count.clone();
+    interpreter.register_fn("count!", 1, move |args| match args.first().unwrap() {
+      Expression::Number(Number::Integer(n)) => {
+        counter.set(counter.get() + n);
+        Ok(int!(counter.get()))
+      }
+      non_number => Err(EvaluationError::invalid_argument(
+        "count!", "number", non_number,
+      )),
+    });
+    assert_eq!(
+      interpreter.eval_str("(count! 2) (count! (+ 1 2))"),
+      Ok(int!(5))
+    );
+    assert_eq!(count.get(), 5);
+    assert_eq!(
+      interpreter.eval_str("(count! 1 2)"),
+      Err(EvaluationError::WrongNumberOfArguments(
+        "count!".to_string(),
+        1,
+        2
+      ))
+    );
+    // Errors from the closure can be handled like any other
+    assert_eq!(
+      interpreter.eval_str("(guard (e (#t (error-object-message e))) (count! 'a))"),
+      Ok(string!("invalid argument"))
+    );
+    assert_eq!(
+      interpreter.eval_str("((lambda (f) (f 1)) count!)"),
+      Ok(int!(6))
+    );
+    assert_eq!(
+      interpreter.get("count!").map(|count| count.to_string()),
+      Some("#<procedure:count!>".to_string())
+    );
+
+    interpreter.register_variadic_fn("count-args", 1, |args| Ok(int!(args.len() as i32)));
+    assert_eq!(
+      interpreter.call("count-args", vec![int!(1); 3]),
+      Ok(int!(3))
+    );
+    assert_eq!(
+      interpreter.eval_str("(count-args)"),
+      Err(EvaluationError::WrongNumberOfVariableArguments(
+        "count-args".to_string(),
+        1,
+        0
+      ))
+    );
+  }
+
+  #[test]
+  fn test_register_typed_fn() {
+    let interpreter = Interpreter::new();
+    interpreter.register_typed_fn("long-enough?", |length: i64, words: Vec<String>| {
+      words.iter().map(String::len).sum::<usize>() as i64 >= length
+    });
+    assert_eq!(
+      interpreter.eval_str("(long-enough? 4 '(\"ab\" \"cd\"))"),
+      Ok(boolean!(true))
+    );
+    assert_eq!(
+      interpreter.eval_str("(long-enough? 5 '(\"ab\" \"cd\"))"),
+      Ok(boolean!(false))
+    );
+    assert_eq!(
+      interpreter.eval_str("(long-enough? 4 '(\"ab\" cd))"),
+      Err(EvaluationError::invalid_argument(
+        "long-enough?",
+        "list of string",
+        &list!(string!("ab"), symbol!("cd"))
+      ))
+    );
+    assert_eq!(
+      interpreter.eval_str("(long-enough? 4)"),
+      Err(EvaluationError::WrongNumberOfArguments(
+        "long-enough?".to_string(),
+        2,
+        1
+      ))
+    );
+    interpreter.register_typed_fn("lookup", |key: String| match key.as_str() {
+      "known" => Ok(Some(1)),
+      "unknown" => Ok(None),
+      _ => Err(EvaluationError::Error(
+        "bad key".to_string(),
+        vec![string!(key)],
+      )),
+    });
+    assert_eq!(interpreter.eval_str("(lookup \"known\")"), Ok(int!(1)));
+    assert_eq!(
+      interpreter.eval_str("(lookup \"unknown\")"),
+      Ok(boolean!(false))
+    );
+    assert_eq!(
+      interpreter.eval_str("(lookup \"other\")"),
+      Err(EvaluationError::Error(
+        "bad key".to_string(),
+        vec![string!("other")]
+      ))
+    );
+    interpreter.register_typed_fn("nothing", || ());
+    assert_eq!(interpreter.call("nothing", vec![]), Ok(void!()));
+  }
+
+  #[test]
+  fn test_define_get_and_call() {
+    let interpreter = Interpreter::new();
+    interpreter.define_value("greeting", string!("hello"));
+    assert_eq!(interpreter.get("greeting"), Some(string!("hello")));
+    assert_eq!(interpreter.get("nothing"), None);
+    interpreter
+      .eval_str("(define greet (lambda (name) (cons greeting name)))")
+      .unwrap();
+    assert_eq!(
+      interpreter.call("greet", vec![symbol!("world")]),
+      Ok(cons!(&string!("hello"), &symbol!("world")))
+    );
+    interpreter
+      .eval_str("(define g (lambda () (eval '(define y 1) (interaction-environment)) 5))")
+      .unwrap();
+    assert_eq!(interpreter.call("g", vec![]), Ok(int!(5)));
+    assert_eq!(interpreter.get("y"), Some(int!(1)));
+    // The arguments are values, so they aren't evaluated again
+    assert_eq!(
+      interpreter.call("car", vec![list!(symbol!("a"), symbol!("b"))]),
+      Ok(symbol!("a"))
+    );
+    assert_eq!(
+      interpreter.call("greeting", vec![]),
+      Err(EvaluationError::NotAProcedure(Box::new(string!("hello"))))
+    );
+    assert_eq!(
+      interpreter.call("car", vec![]),
+      Err(EvaluationError::WrongNumberOfArguments(
+        "car".to_string(),
+        1,
+        0
+      ))
+    );
+  }
+}
@@ -0,0 +1,40 @@
+//! A toy Scheme implementation, which can be run on its own or embedded in other programs.
+//!
+//! ```
+//! use rust_lisp::{int, Interpreter};
+//!
+//! let interpreter = Interpreter::new();
+//! interpreter.eval_str("(define square (lambda (x) (* x x)))").unwrap();
+//! let squared = interpreter.call("square", vec![int!(4)]).unwrap();
+//! assert_eq!(squared, int!(16));
+//! ```
+
+mod builtins;
+mod compile;
+mod convert;
+mod editor;
+mod evaluate;
+mod exec;
+mod interpreter;
+mod library;
+mod load;
+mod macros;
+mod meta;
+mod parse;
+mod port;
+mod scope;
+mod test;
+mod token;
+mod types;
+mod vm;
+
+pub use crate::builtins::{define_builtins, set_command_line};
+pub use crate::convert::{from_lisp_arg, FromLisp, ToLisp, TypedFn};
+pub use crate::evaluate::{evaluate, set_max_stack_depth, EvaluationError, EvaluationResult};
+pub use crate::exec::{exec_expression, exec_file, exec_stdin, repl, ExecError};
+pub use crate::interpreter::Interpreter;
+pub use crate::load::set_load_path;
+pub use crate::parse::{parse, ParseError};
+pub use crate::scope::Scope;
+pub use crate::types::{BuiltinClosure, Cons, Expression, Number, Procedure};
+pub(crate) use crate::types::*;
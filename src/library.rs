@@ -0,0 +1,399 @@
+use crate::evaluate::{arg_vec, EvaluationError};
+use crate::load::{include, include_filenames, load, resolve};
+use crate::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// What a library made with define-library gives to the programs that import it
+#[derive(Debug)]
+pub struct Library {
+  /// The values of everything the library exports, under the names it exports them as
+  pub exports: Vec<(String, Expression)>,
+}
+
+/// Every library that a program has defined, by the display of its name
+pub type Libraries = Rc<RefCell<HashMap<String, Rc<Library>>>>;
+
+/// The libraries of the report that are built in, and the builtins each of them exports
+const SCHEME_LIBRARIES: &[(&str, &[&str])] = &[
+  (
+    "base",
+    &[
+      "*",
+      "+",
+      "-",
+      "/",
+      "<",
+      "<=",
+      "=",
+      ">",
+      ">=",
+      "bytevector",
+      "bytevector-length",
+      "bytevector-u8-ref",
+      "bytevector-u8-set!",
+      "bytevector?",
+      "call-with-current-continuation",
+      "call/cc",
+      "call-with-values",
+      "car",
+      "cdr",
+      "char-ready?",
+      "close-input-port",
+      "close-output-port",
+      "close-port",
+      "cond",
+      "cons",
+      "current-error-port",
+      "current-input-port",
+      "current-output-port",
+      "define",
+      "define-record-type",
+      "define-syntax",
+      "define-values",
+      "dynamic-wind",
+      "eof-object",
+      "eof-object?",
+      "eq?",
+      "error",
+      "error-object-irritants",
+      "error-object-message",
+      "error-object?",
+      "exact-integer-sqrt",
+      "floor/",
+      "flush-output-port",
+      "get-output-bytevector",
+      "get-output-string",
+      "guard",
+      "include",
+      "lambda",
+      "let*-values",
+      "let-syntax",
+      "let-values",
+      "letrec-syntax",
+      "make-bytevector",
+      "newline",
+      "open-input-bytevector",
+      "open-input-string",
+      "open-output-bytevector",
+      "open-output-string",
+      "peek-char",
+      "peek-u8",
+      "quote",
+      "raise",
+      "raise-continuable",
+      "read-bytevector",
+      "read-char",
+      "read-line",
+      "read-string",
+      "read-u8",
+      "string->utf8",
+      "syntax-rules",
+      "truncate/",
+      "u8-ready?",
+      "utf8->string",
+      "values",
+      "with-exception-handler",
+      "write-bytevector",
+      "write-char",
+      "write-string",
+      "write-u8",
+    ],
+  ),
+  ("eval", &["eval"]),
+  (
+    "file",
+    &[
+      "call-with-input-file",
+      "call-with-output-file",
+      "delete-file",
+      "file-exists?",
+      "open-binary-input-file",
+      "open-binary-output-file",
+      "open-input-file",
+      "open-output-file",
+      "with-input-from-file",
+      "with-output-to-file",
+    ],
+  ),
+  (
+    "lazy",
+    &["delay", "delay-force", "force", "make-promise", "promise?"],
+  ),
+  ("load", &["load"]),
+  ("process-context", &["command-line", "exit"]),
+  ("read", &["read"]),
+  ("repl", &["interaction-environment"]),
+  ("write", &["display", "write"]),
+];
+
+/// The bindings of one of the built in (scheme ...) libraries
+fn scheme_library(parts: &[String]) -> Option<Library> {
+  let names = match parts {
+    [_, name] => {
+      SCHEME_LIBRARIES
+        .iter()
+        .find(|(library, _)| library == name)?
+        .1
+    }
+    _ => return None,
+  };
+  let builtins = Scope::builtins_named(names);
+  let builtins = builtins.borrow();
+  let exports = names
+    .iter()
+    .filter_map(|name| Some((name.to_string(), builtins.lookup(name).ok()?)))
+    .collect();
+  Some(Library { exports })
+}
+
+fn name_parts(name: &Expression) -> Result<Vec<String>, EvaluationError> {
+  let parts = arg_vec("define-library", name)?;
+  if parts.is_empty() {
+    return Err(EvaluationError::invalid_argument(
+      "define-library",
+      "library name",
+      name,
+    ));
+  }
+  parts
+    .iter()
+    .map(|part| match part {
+      Expression::Symbol(part) => Ok(part.clone()),
+      Expression::Number(Number::Integer(part)) => Ok(part.to_string()),
+      _ => Err(EvaluationError::invalid_argument(
+        "define-library",
+        "library name",
+        name,
+      )),
+    })
+    .collect()
+}
+
+/// Find a library by its name, loading it if it hasn't been defined yet.
+/// (our util list) is defined by our/util/list.sld or our/util/list.lisp, which are looked for
+/// like any other file that is loaded. The libraries of the report, like (scheme base), are
+/// built in, and no other (scheme ...) library can be defined.
+pub fn find_library(
+  name: &Expression,
+  libraries: &Libraries,
+) -> Result<Rc<Library>, EvaluationError> {
+  let parts = name_parts(name)?;
+  let key = name.to_string();
+  if let Some(library) = libraries.borrow().get(&key).cloned() {
+    return Ok(library);
+  }
+  if parts[0] == "scheme" {
+    return scheme_library(&parts)
+      .map(Rc::new)
+      .ok_or_else(|| EvaluationError::LibraryNotFound(Box::new(name.clone())));
+  }
+  let path = parts.join("/");
+  let filename = ["sld", "lisp"]
+    .iter()
+    .map(|extension| format!("{}.{}", path, extension))
+    .find(|filename| resolve(filename).is_ok())
+    .ok_or_else(|| EvaluationError::LibraryNotFound(Box::new(name.clone())))?;
+  // The file gets a scope of its own, so nothing but the library it defines comes out of it
+  let scope = Scope::builtins();
+  scope.borrow_mut().share_libraries(libraries.clone());
+  load(&filename, scope)?;
+  let library = libraries.borrow().get(&key).cloned();
+  library.ok_or_else(|| EvaluationError::LibraryNotFound(Box::new(name.clone())))
+}
+
+fn symbol_arg(procedure_name: &str, arg: &Expression) -> Result<String, EvaluationError> {
+  match arg {
+    Expression::Symbol(symbol) => Ok(symbol.clone()),
+    non_symbol => Err(EvaluationError::invalid_argument(
+      procedure_name,
+      "symbol",
+      non_symbol,
+    )),
+  }
+}
+
+/// Make sure that every name is one of the bindings of an import set
+fn check_imported(
+  bindings: &[(String, Expression)],
+  names: &[Expression],
+) -> Result<Vec<String>, EvaluationError> {
+  names
+    .iter()
+    .map(|name| {
+      let symbol = symbol_arg("import", name)?;
+      if bindings.iter().any(|(imported, _)| imported == &symbol) {
+        Ok(symbol)
+      } else {
+        Err(EvaluationError::invalid_argument(
+          "import",
+          "imported name",
+          name,
+        ))
+      }
+    })
+    .collect()
+}
+
+/// The bindings an import set brings in.
+/// An import set is a library name, or one of (only set name ...), (except set name ...),
+/// (prefix set prefix) and (rename set (name new-name) ...).
+pub fn import_set(
+  spec: &Expression,
+  libraries: &Libraries,
+) -> Result<Vec<(String, Expression)>, EvaluationError> {
+  let items = arg_vec("import", spec)?;
+  let (modifier, set, rest) = match items.as_slice() {
+    [Expression::Symbol(modifier), set, rest @ ..]
+      if ["only", "except", "prefix", "rename"].contains(&modifier.as_str()) =>
+    {
+      (modifier.as_str(), set, rest)
+    }
+    _ => return Ok(find_library(spec, libraries)?.exports.clone()),
+  };
+  let bindings = import_set(set, libraries)?;
+  match (modifier, rest) {
+    ("only", names) => {
+      let names = check_imported(&bindings, names)?;
+      Ok(
+        bindings
+          .into_iter()
+          .filter(|(name, _)| names.contains(name))
+          .collect(),
+      )
+    }
+    ("except", names) => {
+      let names = check_imported(&bindings, names)?;
+      Ok(
+        bindings
+          .into_iter()
+          .filter(|(name, _)| !names.contains(name))
+          .collect(),
+      )
+    }
+    ("prefix", [prefix]) => {
+      let prefix = symbol_arg("import", prefix)?;
+      Ok(
+        bindings
+          .into_iter()
+          .map(|(name, value)| (format!("{}{}", prefix, name), value))
+          .collect(),
+      )
+    }
+    ("rename", renames) => {
+      let mut bindings = bindings;
+      for rename in renames {
+        match arg_vec("import", rename)?.as_slice() {
+          [from, to] => {
+            let from = check_imported(&bindings, std::slice::from_ref(from))?.remove(0);
+            let to = symbol_arg("import", to)?;
+            for (name, _) in bindings.iter_mut() {
+              if name == &from {
+                *name = to.clone();
+              }
+            }
+          }
+          _ => {
+            return Err(EvaluationError::invalid_argument(
+              "import",
+              "(name new-name)",
+              rename,
+            ))
+          }
+        }
+      }
+      Ok(bindings)
+    }
+    _ => Err(EvaluationError::invalid_argument(
+      "import",
+      "import set",
+      spec,
+    )),
+  }
+}
+
+/// Define the bindings of an import set in scope.
+/// Imported bindings are copies, so redefining them on either side doesn't affect the other.
+pub fn import(spec: &Expression, scope: &Rc<RefCell<Scope>>) -> Result<(), EvaluationError> {
+  for (name, value) in import_set(spec, &Scope::libraries(scope))? {
+    scope.borrow_mut().define(&name, value);
+  }
+  Ok(())
+}
+
+/// The name a binding is exported under, and its name inside the library
+fn export_spec(spec: &Expression) -> Result<(String, String), EvaluationError> {
+  match spec {
+    Expression::Symbol(name) => Ok((name.clone(), name.clone())),
+    spec => match arg_vec("export", spec)?.as_slice() {
+      [Expression::Symbol(rename), internal, external] if rename == "rename" => Ok((
+        symbol_arg("export", external)?,
+        symbol_arg("export", internal)?,
+      )),
+      _ => Err(EvaluationError::invalid_argument(
+        "export",
+        "name or (rename name new-name)",
+        spec,
+      )),
+    },
+  }
+}
+
+/// Define a library from its declarations, which are (export spec ...), (import set ...),
+/// (begin expression ...) and (include filename ...). Each library is defined in a scope of its
+/// own with nothing in it but what it imports, so only what it exports can be seen from outside
+/// of it. It is defined for the program that scope is a part of.
+pub fn define_library(
+  name: &Expression,
+  declarations: &[Expression],
+  scope: &Rc<RefCell<Scope>>,
+) -> Result<(), EvaluationError> {
+  name_parts(name)?;
+  let libraries = Scope::libraries(scope);
+  let scope = Rc::new(RefCell::new(Scope::new()));
+  scope.borrow_mut().share_libraries(libraries.clone());
+  let mut export_specs = vec![];
+  for declaration in declarations {
+    let items = arg_vec("define-library", declaration)?;
+    match items.split_first() {
+      Some((Expression::Symbol(keyword), rest)) if keyword == "export" => {
+        for spec in rest {
+          export_specs.push(export_spec(spec)?);
+        }
+      }
+      Some((Expression::Symbol(keyword), rest)) if keyword == "import" => {
+        for spec in rest {
+          import(spec, &scope)?;
+        }
+      }
+      Some((Expression::Symbol(keyword), rest)) if keyword == "begin" => {
+        for expression in rest {
+          evaluate(expression, scope.clone())?;
+        }
+      }
+      Some((Expression::Symbol(keyword), rest)) if keyword == "include" => {
+        for filename in include_filenames(rest)? {
+          for expression in include(&filename)? {
+            evaluate(&expression, scope.clone())?;
+          }
+        }
+      }
+      _ => {
+        return Err(EvaluationError::invalid_argument(
+          "define-library",
+          "library declaration",
+          declaration,
+        ))
+      }
+    }
+  }
+  let exports = export_specs
+    .into_iter()
+    .map(|(external, internal)| Ok((external, scope.borrow().lookup(&internal)?)))
+    .collect::<Result<_, EvaluationError>>()?;
+  libraries
+    .borrow_mut()
+    .insert(name.to_string(), Rc::new(Library { exports }));
+  Ok(())
+}
@@ -0,0 +1,180 @@
+use crate::evaluate::{arg_vec, EvaluationError, EvaluationResult};
+use crate::parse::{parse_all, skip_header};
+use crate::*;
+use std::cell::RefCell;
+use std::fs;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+thread_local! {
+  /// The directories to look for files in when they aren't next to the file loading them
+  static LOAD_PATH: RefCell<Vec<PathBuf>> = const { RefCell::new(vec![]) };
+  /// The files which are being loaded, innermost last
+  static LOADING: RefCell<Vec<PathBuf>> = const { RefCell::new(vec![]) };
+}
+
+pub fn set_load_path(load_path: Vec<PathBuf>) {
+  LOAD_PATH.with(|path| *path.borrow_mut() = load_path);
+}
+
+/// Find the file that a program refers to by filename.
+/// Relative filenames are looked for next to the file being loaded, or in the current directory
+/// if nothing is, and then in each directory of the load path.
+pub fn resolve(filename: &str) -> Result<PathBuf, EvaluationError> {
+  let path = Path::new(filename);
+  if path.is_absolute() {
+    return Ok(path.to_path_buf());
+  }
+  let current_directory = LOADING.with(|loading| {
+    loading
+      .borrow()
+      .last()
+      .and_then(|file| file.parent())
+      .map(Path::to_path_buf)
+      .unwrap_or_default()
+  });
+  let load_path = LOAD_PATH.with(|load_path| load_path.borrow().clone());
+  once(current_directory)
+    .chain(load_path)
+    .map(|directory| directory.join(path))
+    .find(|candidate| candidate.is_file())
+    .ok_or_else(|| EvaluationError::FileError(filename.to_string(), "file not found".to_string()))
+}
+
+/// Read every expression in a file
+pub fn read_file(path: &Path) -> Result<Vec<Expression>, EvaluationError> {
+  let filename = path.display().to_string();
+  let contents = fs::read_to_string(path)
+    .map_err(|err| EvaluationError::FileError(filename.clone(), err.to_string()))?;
+  let mut expressions = skip_header(&contents)
+    .and_then(parse_all)
+    .map_err(|err| EvaluationError::ParseError(filename, err))?;
+  let directory = path.parent().unwrap_or_else(|| Path::new(""));
+  for expression in &mut expressions {
+    anchor_includes(expression, directory);
+  }
+  Ok(expressions)
+}
+
+/// Replace the filenames given to includes in an expression read from a file in directory with
+/// the full paths of the files they refer to there, so that they are found next to the file that
+/// includes them however much later the includes are expanded.
+/// Filenames which aren't found there are left to be looked for in the load path.
+fn anchor_includes(expression: &mut Expression, directory: &Path) {
+  let mut sublist = expression;
+  let include = match sublist {
+    Expression::Cons(cons) => match cons.car.as_ref() {
+      Expression::Symbol(keyword) if keyword == "quote" => return,
+      Expression::Symbol(keyword) => keyword == "include",
+      _ => false,
+    },
+    _ => return,
+  };
+  while let Expression::Cons(cons) = sublist {
+    match cons.car.as_mut() {
+      Expression::String(filename) if include && Path::new(filename).is_relative() => {
+        if let Ok(path) = directory.join(&filename).canonicalize() {
+          if path.is_file() {
+            *filename = path.display().to_string();
+          }
+        }
+      }
+      car => anchor_includes(car, directory),
+    }
+    sublist = cons.cdr.as_mut();
+  }
+}
+
+/// Mark a file as being loaded until the matching call to leave, failing if it is already being
+/// loaded, since then it would end up loading itself forever
+pub fn enter(path: &Path) -> Result<(), EvaluationError> {
+  let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  LOADING.with(|loading| {
+    let mut loading = loading.borrow_mut();
+    if let Some(start) = loading.iter().position(|file| file == &path) {
+      let cycle = loading[start..]
+        .iter()
+        .chain(once(&path))
+        .map(|file| file.display().to_string())
+        .collect();
+      return Err(EvaluationError::LoadCycle(cycle));
+    }
+    loading.push(path);
+    Ok(())
+  })
+}
+
+pub fn leave() {
+  LOADING.with(|loading| loading.borrow_mut().pop());
+}
+
+/// Evaluate every expression in a file in scope, like a file that defines a library.
+/// Each expression is evaluated to completion on its own, so continuations captured while
+/// loading a file can't escape from it.
+pub fn load(filename: &str, scope: Rc<RefCell<Scope>>) -> EvaluationResult {
+  let path = resolve(filename)?;
+  enter(&path)?;
+  let result = read_file(&path).and_then(|expressions| {
+    for expression in expressions {
+      evaluate(&expression, scope.clone())?;
+    }
+    Ok(void!())
+  });
+  leave();
+  result
+}
+
+/// The filenames given to an include
+pub fn include_filenames(args: &[Expression]) -> Result<Vec<String>, EvaluationError> {
+  args
+    .iter()
+    .map(|filename| match filename {
+      Expression::String(filename) => Ok(filename.clone()),
+      non_string => Err(EvaluationError::invalid_argument(
+        "include", "string", non_string,
+      )),
+    })
+    .collect()
+}
+
+/// The files that an include refers to, and the expressions in each of them.
+/// Fails if any of them is already being loaded or included, since then it would end up
+/// including itself forever.
+pub fn read_included(
+  args: &[Expression],
+) -> Result<Vec<(PathBuf, Vec<Expression>)>, EvaluationError> {
+  include_filenames(args)?
+    .iter()
+    .map(|filename| {
+      let path = resolve(filename)?;
+      enter(&path)?;
+      leave();
+      let expressions = read_file(&path)?;
+      Ok((path, expressions))
+    })
+    .collect()
+}
+
+/// The expressions in a file, with the expressions of the files that includes at the top level
+/// of it include spliced in where the includes were
+pub fn include(filename: &str) -> Result<Vec<Expression>, EvaluationError> {
+  let path = resolve(filename)?;
+  enter(&path)?;
+  let result = read_file(&path).and_then(|expressions| {
+    let mut included = vec![];
+    for expression in expressions {
+      match &expression {
+        Expression::Cons(cons) if cons.car.as_ref() == &symbol!("include") => {
+          for filename in include_filenames(&arg_vec("include", &cons.cdr)?)? {
+            included.extend(include(&filename)?);
+          }
+        }
+        _ => included.push(expression),
+      }
+    }
+    Ok(included)
+  });
+  leave();
+  result
+}
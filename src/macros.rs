@@ -0,0 +1,548 @@
+use crate::evaluate::{arg_vec, EvaluationError, EvaluationResult};
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Every expansion gets a fresh number so that the bindings it introduces can't collide with
+/// bindings from the macro use site, or with bindings from other expansions.
+static EXPANSION_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The forms whose bindings are renamed when they are introduced by a template.
+const BINDING_FORMS: [&str; 9] = [
+  "lambda",
+  "define",
+  "define-syntax",
+  "let",
+  "let*",
+  "letrec",
+  "letrec*",
+  "let-syntax",
+  "letrec-syntax",
+];
+
+/// The parts of a form captured by a pattern variable.
+/// Pattern variables that are followed by an ellipsis capture one binding per repetition.
+#[derive(Clone, Debug)]
+enum Binding {
+  Fragment(Expression),
+  Repetition(Vec<Binding>),
+}
+type Bindings = HashMap<String, Binding>;
+
+/// A macro transformer, as constructed by syntax-rules
+#[derive(Debug, Eq, PartialEq)]
+pub struct SyntaxRules {
+  ellipsis: String,
+  literals: Vec<String>,
+  rules: Vec<(Expression, Expression)>,
+}
+
+/// Split a (possibly improper) list into its elements and its terminator
+fn list_items(list: &Expression) -> (Vec<Expression>, Expression) {
+  let mut items = vec![];
+  let mut sublist = list;
+  while let Expression::Cons(cons) = sublist {
+    items.push(cons.car.as_ref().clone());
+    sublist = cons.cdr.as_ref();
+  }
+  (items, sublist.clone())
+}
+
+/// The inverse of list_items
+fn items_list(items: Vec<Expression>, terminator: Expression) -> Expression {
+  items
+    .iter()
+    .rev()
+    .fold(terminator, |list, item| cons!(item, &list))
+}
+
+fn invalid_syntax(expected: &str, actual: &Expression) -> EvaluationError {
+  EvaluationError::invalid_argument("syntax-rules", expected, actual)
+}
+
+impl SyntaxRules {
+  /// Build a transformer from the arguments of a syntax-rules form, which are either
+  /// `(literal ...) (pattern template) ...` or `ellipsis (literal ...) (pattern template) ...`
+  pub fn new(spec: &[Expression]) -> Result<SyntaxRules, EvaluationError> {
+    let (ellipsis, spec) = match spec.first() {
+      Some(Expression::Symbol(ellipsis)) => (ellipsis.clone(), &spec[1..]),
+      _ => ("...".to_string(), spec),
+    };
+    let literals = match spec.first() {
+      Some(literals) => arg_vec("syntax-rules", literals)?,
+      None => return Err(invalid_syntax("list of literals", &null!())),
+    };
+    let literals = literals
+      .into_iter()
+      .map(|literal| match literal {
+        Expression::Symbol(symbol) => Ok(symbol),
+        _ => Err(invalid_syntax("symbol", &literal)),
+      })
+      .collect::<Result<Vec<String>, EvaluationError>>()?;
+    let mut syntax_rules = SyntaxRules {
+      ellipsis,
+      literals,
+      rules: vec![],
+    };
+    for rule in &spec[1..] {
+      match arg_vec("syntax-rules", rule)?.as_slice() {
+        [pattern @ Expression::Cons(cons), template] => {
+          syntax_rules.validate_pattern(pattern)?;
+          // Only what follows the keyword is matched, so an ellipsis right after the keyword has
+          // no subpattern to repeat
+          if let Expression::Cons(rest) = cons.cdr.as_ref() {
+            if syntax_rules.is_ellipsis(&rest.car) {
+              return Err(invalid_syntax(
+                "at most one ellipsis after a subpattern",
+                pattern,
+              ));
+            }
+          }
+          syntax_rules.rules.push((pattern.clone(), template.clone()));
+        }
+        _ => return Err(invalid_syntax("(pattern template)", rule)),
+      }
+    }
+    Ok(syntax_rules)
+  }
+
+  fn is_ellipsis(&self, expression: &Expression) -> bool {
+    matches!(expression, Expression::Symbol(symbol) if symbol == &self.ellipsis)
+  }
+
+  /// Patterns may contain at most one ellipsis per list, and it must follow a subpattern
+  fn validate_pattern(&self, pattern: &Expression) -> Result<(), EvaluationError> {
+    if let Expression::Cons(_) = pattern {
+      let (items, terminator) = list_items(pattern);
+      let ellipses = items.iter().filter(|item| self.is_ellipsis(item)).count();
+      if ellipses > 1 || self.is_ellipsis(&items[0]) || self.is_ellipsis(&terminator) {
+        return Err(invalid_syntax(
+          "at most one ellipsis after a subpattern",
+          pattern,
+        ));
+      }
+      for item in items.iter().filter(|item| !self.is_ellipsis(item)) {
+        self.validate_pattern(item)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// The names of all the pattern variables that appear in a pattern
+  fn pattern_variables(&self, pattern: &Expression, variables: &mut Vec<String>) {
+    match pattern {
+      Expression::Symbol(symbol)
+        if symbol != "_" && !self.literals.contains(symbol) && !self.is_ellipsis(pattern) =>
+      {
+        variables.push(symbol.clone());
+      }
+      Expression::Cons(cons) => {
+        self.pattern_variables(&cons.car, variables);
+        self.pattern_variables(&cons.cdr, variables);
+      }
+      _ => (),
+    }
+  }
+
+  fn match_pattern(
+    &self,
+    pattern: &Expression,
+    form: &Expression,
+    bindings: &mut Bindings,
+  ) -> bool {
+    match pattern {
+      Expression::Symbol(symbol) => {
+        if symbol == "_" {
+          true
+        } else if self.literals.contains(symbol) {
+          form == pattern
+        } else {
+          bindings.insert(symbol.clone(), Binding::Fragment(form.clone()));
+          true
+        }
+      }
+      Expression::Cons(cons) => {
+        let (patterns, pattern_terminator) = list_items(pattern);
+        if let Some(index) = patterns.iter().position(|item| self.is_ellipsis(item)) {
+          self.match_ellipsis(&patterns, index, &pattern_terminator, form, bindings)
+        } else if let Expression::Cons(form_cons) = form {
+          self.match_pattern(&cons.car, &form_cons.car, bindings)
+            && self.match_pattern(&cons.cdr, &form_cons.cdr, bindings)
+        } else {
+          false
+        }
+      }
+      _ => pattern == form,
+    }
+  }
+
+  /// Match a list pattern of the form (before ... repeated ellipsis after ... . terminator)
+  fn match_ellipsis(
+    &self,
+    patterns: &[Expression],
+    index: usize,
+    pattern_terminator: &Expression,
+    form: &Expression,
+    bindings: &mut Bindings,
+  ) -> bool {
+    let before = &patterns[..index - 1];
+    let repeated = &patterns[index - 1];
+    let after = &patterns[index + 1..];
+    let (items, form_terminator) = list_items(form);
+    if items.len() < before.len() + after.len() {
+      return false;
+    }
+    let repetitions = items.len() - before.len() - after.len();
+    for (pattern, item) in before.iter().zip(items.iter()) {
+      if !self.match_pattern(pattern, item, bindings) {
+        return false;
+      }
+    }
+    let mut repeated_bindings = vec![];
+    for item in &items[before.len()..before.len() + repetitions] {
+      let mut repetition = Bindings::new();
+      if !self.match_pattern(repeated, item, &mut repetition) {
+        return false;
+      }
+      repeated_bindings.push(repetition);
+    }
+    let mut variables = vec![];
+    self.pattern_variables(repeated, &mut variables);
+    for variable in variables {
+      let repetition = repeated_bindings
+        .iter_mut()
+        .map(|repetition| repetition.remove(&variable).unwrap())
+        .collect();
+      bindings.insert(variable, Binding::Repetition(repetition));
+    }
+    for (pattern, item) in after.iter().zip(items[before.len() + repetitions..].iter()) {
+      if !self.match_pattern(pattern, item, bindings) {
+        return false;
+      }
+    }
+    self.match_pattern(pattern_terminator, &form_terminator, bindings)
+  }
+
+  /// Fill in a template with the bindings from a successful match.
+  /// Symbols introduced by the template are replaced with fresh aliases, which are recorded in
+  /// aliases so that they can be resolved once the whole expansion is known.
+  fn expand_template(
+    &self,
+    template: &Expression,
+    bindings: &Bindings,
+    aliases: &mut HashMap<String, String>,
+    expansion: usize,
+    escaped: bool,
+  ) -> EvaluationResult {
+    match template {
+      Expression::Symbol(symbol) => match bindings.get(symbol) {
+        Some(Binding::Fragment(fragment)) => Ok(fragment.clone()),
+        Some(Binding::Repetition(_)) => Err(invalid_syntax(
+          "pattern variable followed by an ellipsis",
+          template,
+        )),
+        None => {
+          let alias = aliases
+            .entry(symbol.clone())
+            .or_insert_with(|| format!("{}#{}", symbol, expansion));
+          Ok(symbol!(alias.clone()))
+        }
+      },
+      Expression::Cons(cons) => {
+        let (items, terminator) = list_items(template);
+        // (... template) escapes any ellipses inside of template
+        if !escaped && items.len() == 2 && self.is_ellipsis(&cons.car) && terminator == null!() {
+          return self.expand_template(&items[1], bindings, aliases, expansion, true);
+        }
+        let mut expanded = vec![];
+        let mut items = items.iter().peekable();
+        while let Some(item) = items.next() {
+          let mut depth = 0;
+          while !escaped && items.peek().map(|next| self.is_ellipsis(next)) == Some(true) {
+            items.next();
+            depth += 1;
+          }
+          if depth == 0 {
+            expanded.push(self.expand_template(item, bindings, aliases, expansion, escaped)?);
+          } else {
+            self.expand_repetition(item, depth, bindings, aliases, expansion, &mut expanded)?;
+          }
+        }
+        let terminator =
+          self.expand_template(&terminator, bindings, aliases, expansion, escaped)?;
+        Ok(items_list(expanded, terminator))
+      }
+      _ => Ok(template.clone()),
+    }
+  }
+
+  /// Expand a subtemplate that is followed by depth ellipses, once for every repetition of the
+  /// pattern variables it contains.
+  fn expand_repetition(
+    &self,
+    template: &Expression,
+    depth: usize,
+    bindings: &Bindings,
+    aliases: &mut HashMap<String, String>,
+    expansion: usize,
+    expanded: &mut Vec<Expression>,
+  ) -> Result<(), EvaluationError> {
+    let mut variables = vec![];
+    self.pattern_variables(template, &mut variables);
+    let repeated: Vec<(&String, &Vec<Binding>)> = variables
+      .iter()
+      .filter_map(|variable| match bindings.get(variable) {
+        Some(Binding::Repetition(repetition)) => Some((variable, repetition)),
+        _ => None,
+      })
+      .collect();
+    let repetitions = match repeated.first() {
+      Some((_, repetition)) => repetition.len(),
+      None => {
+        return Err(invalid_syntax(
+          "pattern variable before an ellipsis",
+          template,
+        ))
+      }
+    };
+    if repeated
+      .iter()
+      .any(|(_, repetition)| repetition.len() != repetitions)
+    {
+      return Err(invalid_syntax("repetitions of equal length", template));
+    }
+    for index in 0..repetitions {
+      let mut repetition_bindings = bindings.clone();
+      for (variable, repetition) in &repeated {
+        repetition_bindings.insert(variable.to_string(), repetition[index].clone());
+      }
+      if depth > 1 {
+        self.expand_repetition(
+          template,
+          depth - 1,
+          &repetition_bindings,
+          aliases,
+          expansion,
+          expanded,
+        )?;
+      } else {
+        expanded.push(self.expand_template(
+          template,
+          &repetition_bindings,
+          aliases,
+          expansion,
+          false,
+        )?);
+      }
+    }
+    Ok(())
+  }
+
+  /// Expand a macro use once
+  pub fn expand(&self, form: &Expression) -> EvaluationResult {
+    for (pattern, template) in &self.rules {
+      let mut bindings = Bindings::new();
+      // The keyword position of the pattern is ignored
+      let (Expression::Cons(pattern), Expression::Cons(form_cons)) = (pattern, form) else {
+        continue;
+      };
+      if self.match_pattern(&pattern.cdr, &form_cons.cdr, &mut bindings) {
+        let expansion = EXPANSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut aliases = HashMap::new();
+        let expanded = self.expand_template(template, &bindings, &mut aliases, expansion, false)?;
+        let originals: HashMap<String, String> = aliases
+          .into_iter()
+          .map(|(original, alias)| (alias, original))
+          .collect();
+        let mut bound = HashSet::new();
+        find_bound_aliases(&expanded, &originals, &mut bound);
+        return Ok(resolve_aliases(&expanded, &originals, &bound));
+      }
+    }
+    Err(EvaluationError::NoMatchingSyntaxRule(Box::new(
+      form.clone(),
+    )))
+  }
+}
+
+/// Find all of the aliases that are bound by a binding form somewhere in an expansion.
+/// These aliases keep their fresh names so that they cannot capture or shadow anything from the
+/// macro use site.
+fn find_bound_aliases(
+  expression: &Expression,
+  originals: &HashMap<String, String>,
+  bound: &mut HashSet<String>,
+) {
+  let mut bind = |expression: &Expression| {
+    if let Expression::Symbol(symbol) = expression {
+      if originals.contains_key(symbol) {
+        bound.insert(symbol.clone());
+      }
+    }
+  };
+  if let Expression::Cons(cons) = expression {
+    let (items, _) = list_items(expression);
+    let keyword = match cons.car.as_ref() {
+      Expression::Symbol(symbol) => originals.get(symbol).unwrap_or(symbol).as_str(),
+      _ => "",
+    };
+    if BINDING_FORMS.contains(&keyword) && items.len() > 1 {
+      match keyword {
+        "lambda" | "define" | "define-syntax" => {
+          // Formals may be a symbol, a list of symbols, or an improper list of symbols
+          let (mut formals, rest) = list_items(&items[1]);
+          formals.push(rest);
+          formals.iter().for_each(&mut bind);
+        }
+        _ => {
+          // Named let has an extra name before the bindings
+          let bindings = match (&items[1], items.get(2)) {
+            (Expression::Symbol(_), Some(bindings)) => {
+              bind(&items[1]);
+              bindings
+            }
+            (bindings, _) => bindings,
+          };
+          for binding in list_items(bindings).0 {
+            if let Expression::Cons(binding) = binding {
+              bind(&binding.car);
+            }
+          }
+        }
+      }
+    }
+    if keyword != "quote" {
+      find_bound_aliases(&cons.car, originals, bound);
+      find_bound_aliases(&cons.cdr, originals, bound);
+    }
+  }
+}
+
+/// Replace every alias that isn't bound within the expansion with the symbol it was introduced
+/// as, so that free references in a template refer to whatever is in scope where it is used.
+fn resolve_aliases(
+  expression: &Expression,
+  originals: &HashMap<String, String>,
+  bound: &HashSet<String>,
+) -> Expression {
+  match expression {
+    Expression::Symbol(symbol) => match originals.get(symbol) {
+      Some(original) if !bound.contains(symbol) => symbol!(original),
+      _ => expression.clone(),
+    },
+    Expression::Cons(cons) => {
+      let quoted = HashSet::new();
+      let bound = match cons.car.as_ref() {
+        // Quoted data is never renamed
+        Expression::Symbol(symbol) if originals.get(symbol).unwrap_or(symbol) == "quote" => &quoted,
+        _ => bound,
+      };
+      cons!(
+        &resolve_aliases(&cons.car, originals, bound),
+        &resolve_aliases(&cons.cdr, originals, bound)
+      )
+    }
+    _ => expression.clone(),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn syntax_rules(spec: &str) -> SyntaxRules {
+    let (spec, _) = list_items(&parse(spec).unwrap());
+    SyntaxRules::new(&spec).unwrap()
+  }
+
+  #[test]
+  fn test_expand() {
+    let rules = syntax_rules("((else) ((_ else e) e) ((_ a b) (b a)))");
+    assert_eq!(rules.expand(&parse("(m else 1)").unwrap()), Ok(int!(1)));
+    assert_eq!(
+      rules.expand(&parse("(m 1 f)").unwrap()),
+      Ok(list!(symbol!("f"), int!(1)))
+    );
+    assert_eq!(
+      rules.expand(&parse("(m 1)").unwrap()),
+      Err(EvaluationError::NoMatchingSyntaxRule(Box::new(
+        parse("(m 1)").unwrap()
+      )))
+    );
+  }
+
+  #[test]
+  fn test_expand_ellipsis() {
+    let rules = syntax_rules("(() ((_ (a b ...) ... c) (c (b ... a) ...)))");
+    assert_eq!(
+      rules.expand(&parse("(m (1 2 3) (4) 5)").unwrap()),
+      Ok(parse("(5 (2 3 1) (4))").unwrap())
+    );
+    let rules = syntax_rules("(() ((_ a ... b c) (c b a ...)))");
+    assert_eq!(
+      rules.expand(&parse("(m 1 2 3 4)").unwrap()),
+      Ok(parse("(4 3 1 2)").unwrap())
+    );
+    assert_eq!(
+      rules.expand(&parse("(m 3 4)").unwrap()),
+      Ok(parse("(4 3)").unwrap())
+    );
+    let rules = syntax_rules("(() ((_ (a ...) ...) (a ... ...)))");
+    assert_eq!(
+      rules.expand(&parse("(m (1 2) () (3))").unwrap()),
+      Ok(parse("(1 2 3)").unwrap())
+    );
+    let rules = syntax_rules("(::: () ((_ a :::) '(a ::: ...)))");
+    assert_eq!(
+      rules.expand(&parse("(m 1 2)").unwrap()),
+      Ok(parse("'(1 2 ...)").unwrap())
+    );
+    let rules = syntax_rules("(() ((_ a) '(... (a ...))))");
+    assert_eq!(
+      rules.expand(&parse("(m 1)").unwrap()),
+      Ok(parse("'(1 ...)").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_expand_renames_introduced_bindings() {
+    let rules = syntax_rules("(() ((_ a) (lambda (x) (+ x a))))");
+    let expansion = rules.expand(&parse("(m x)").unwrap()).unwrap();
+    let (items, _) = list_items(&expansion);
+    let (formals, _) = list_items(&items[1]);
+    assert_eq!(items[0], symbol!("lambda"));
+    assert_ne!(formals[0], symbol!("x"));
+    assert_eq!(
+      items[2],
+      list!(symbol!("+"), formals[0].clone(), symbol!("x"))
+    );
+  }
+
+  #[test]
+  fn test_invalid_syntax_rules() {
+    let (spec, _) = list_items(&parse("((a) (_ 1))").unwrap());
+    assert_eq!(
+      SyntaxRules::new(&spec),
+      Err(invalid_syntax(
+        "(pattern template)",
+        &parse("(_ 1)").unwrap()
+      ))
+    );
+    let (spec, _) = list_items(&parse("(() ((_ a ... b ...) a))").unwrap());
+    assert_eq!(
+      SyntaxRules::new(&spec),
+      Err(invalid_syntax(
+        "at most one ellipsis after a subpattern",
+        &parse("(_ a ... b ...)").unwrap()
+      ))
+    );
+    let (spec, _) = list_items(&parse("(() ((_ ...) 1))").unwrap());
+    assert_eq!(
+      SyntaxRules::new(&spec),
+      Err(invalid_syntax(
+        "at most one ellipsis after a subpattern",
+        &parse("(_ ...)").unwrap()
+      ))
+    );
+  }
+}
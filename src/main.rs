@@ -1,27 +1,147 @@
-mod builtins;
-mod evaluate;
-mod exec;
-mod parse;
-mod scope;
-mod test;
-mod token;
-mod types;
-
-pub use crate::builtins::define_builtins;
-pub use crate::evaluate::evaluate;
-pub use crate::parse::parse;
-pub use crate::scope::Scope;
-pub use crate::types::*;
-use std::env::args;
+use rust_lisp::{
+    exec_expression, exec_file, exec_stdin, repl, set_command_line, set_load_path,
+    set_max_stack_depth, EvaluationError, ExecError, Scope,
+};
+use std::env::{args, split_paths, var, var_os};
 use std::io;
+use std::io::Write;
+use std::iter::once;
+use std::path::PathBuf;
+use std::process::exit;
 
-fn main() -> io::Result<()> {
+const USAGE: &str = "\
+Usage: rust-lisp [options] [file | -] [--] [arguments...]
+
+Runs the program in file, or read from stdin if it is -, and passes the arguments after it to
+the program as (command-line). Without a program or any expressions to evaluate, starts a REPL.
+
+Options:
+  -e, --eval EXPR           evaluate an expression before running the program
+  -i, --interactive         start a REPL after running the program
+  -q, --quiet               don't print the value of every top-level expression
+  -I, --load-path DIRECTORY look for loaded files in a directory
+  -h, --help                show this message
+      --version             show the version
+
+Environment:
+  LISP_LOAD_PATH            more directories to look for loaded files in
+  LISP_MAX_STACK_DEPTH      how deep the evaluation stack can grow
+
+Exit status:
+  0 if the program finished, or the status it gave to (exit)
+  1 if the program could not be evaluated
+  2 if the options could not be understood
+  3 if the program could not be parsed
+  4 if the program could not be read
+";
+
+/// The value of an option like -e EXPR, which is the next argument
+fn option_value(option: &str, args: &mut impl Iterator<Item = String>, what: &str) -> String {
+    match args.next() {
+        Some(value) => value,
+        None => {
+            eprintln!("{} needs {}", option, what);
+            exit(2);
+        }
+    }
+}
+
+/// Evaluate the expressions given with -e, then the program, then start a REPL if asked to or
+/// if there was nothing else to do
+fn run(
+    expressions: &[String],
+    program: Option<&str>,
+    interactive: bool,
+    quiet: bool,
+) -> Result<(), ExecError> {
+    let scope = Scope::builtins();
+    for expression in expressions {
+        exec_expression(expression, scope.clone(), quiet)?;
+    }
+    match program {
+        Some("-") => exec_stdin(scope.clone(), quiet)?,
+        Some(filename) => exec_file(filename, scope.clone(), quiet)?,
+        None => (),
+    }
+    if interactive || (program.is_none() && expressions.is_empty()) {
+        repl(scope)?;
+    }
+    Ok(())
+}
+
+fn main() {
     let mut args = args();
-    // Who cares about the first arg
-    args.next();
-    if let Some(filename) = args.next() {
-        crate::exec::exec_file(&filename)
-    } else {
-        crate::exec::repl()
+    // The program is named after the interpreter when it isn't in a file
+    let interpreter = args.next().unwrap_or_else(|| "rust-lisp".to_string());
+    if let Some(max_stack_depth) = var("LISP_MAX_STACK_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse().ok())
+    {
+        set_max_stack_depth(max_stack_depth);
+    }
+    // Directories given with -I are searched before the ones in LISP_LOAD_PATH
+    let mut load_path: Vec<PathBuf> = vec![];
+    let mut expressions = vec![];
+    let mut program = None;
+    let mut program_args = vec![];
+    let mut interactive = false;
+    let mut quiet = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-I" | "--load-path" => {
+                load_path.push(option_value(&arg, &mut args, "a directory").into())
+            }
+            "-e" | "--eval" => expressions.push(option_value(&arg, &mut args, "an expression")),
+            "-i" | "--interactive" => interactive = true,
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return;
+            }
+            "--version" => {
+                println!("rust-lisp {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "--" => {
+                program_args.extend(args.by_ref());
+                break;
+            }
+            option if option.starts_with('-') && option != "-" => {
+                eprintln!("Unknown option {}\n\n{}", option, USAGE);
+                exit(2);
+            }
+            _ => {
+                program = Some(arg);
+                // Everything after the program is for the program, even if it looks like an option
+                program_args.extend(args.by_ref());
+                if program_args.first().map(String::as_str) == Some("--") {
+                    program_args.remove(0);
+                }
+                break;
+            }
+        }
     }
+    if let Some(directories) = var_os("LISP_LOAD_PATH") {
+        load_path.extend(split_paths(&directories));
+    }
+    set_load_path(load_path);
+    set_command_line(
+        once(program.clone().unwrap_or(interpreter))
+            .chain(program_args)
+            .collect(),
+    );
+
+    let result = run(&expressions, program.as_deref(), interactive, quiet);
+    // Exiting doesn't write out what is still buffered, and errors should come after the output
+    io::stdout().flush().ok();
+    let status = match result {
+        Ok(()) => 0,
+        // Programs that exit on purpose have already said whatever they wanted to
+        Err(ExecError::Evaluation(EvaluationError::Exit(status))) => status,
+        Err(err) => {
+            eprintln!("{}", err);
+            err.exit_status()
+        }
+    };
+    exit(status);
 }
@@ -0,0 +1,500 @@
+use crate::evaluate::{trace, untrace, EvaluationError, EvaluationResult};
+use crate::parse::parse_all;
+use crate::port::current_output_port;
+use crate::*;
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+use std::time::Instant;
+
+const HELP: &str = "\
+:load file      evaluate every expression in a file
+:reload         load the last file loaded with :load again
+:env            list everything defined in this session
+:describe name  describe what a name is bound to
+:time expr      evaluate an expression and show how long it took
+:trace name     print every call to a procedure and what it returns
+:untrace [name] stop tracing a procedure, or every procedure
+:expand expr    expand the macros in an expression
+:reset          start over with nothing but the builtins
+:save file      write the definitions made in this session to a file
+:quit           leave the REPL
+:help           show this message
+";
+
+/// The state of a REPL session, which meta-commands can look at and change
+pub struct Session {
+  pub scope: Rc<RefCell<Scope>>,
+  /// The last file loaded with :load, which :reload loads again
+  loaded: Option<String>,
+  /// The definitions evaluated in this session, in order, which :save writes out
+  definitions: Vec<Expression>,
+}
+
+/// What the REPL should do after a meta-command
+#[derive(Debug, Eq, PartialEq)]
+pub enum Next {
+  Continue,
+  Quit,
+}
+
+fn print(output: &str) -> Result<(), EvaluationError> {
+  current_output_port().write_str(output)
+}
+
+/// The special forms that define things when they are used at the top level
+const DEFINING_FORMS: &[&str] = &[
+  "define",
+  "define-values",
+  "define-record-type",
+  "define-syntax",
+  "define-library",
+  "import",
+];
+
+/// Whether an expression defines something at the top level, like (define x 1), which it does if
+/// its operator is bound to one of the special forms that define things in scope
+fn is_definition(expression: &Expression, scope: &Scope) -> bool {
+  let keyword = match expression {
+    Expression::Cons(cons) => match cons.car.as_ref() {
+      Expression::Symbol(keyword) => keyword,
+      _ => return false,
+    },
+    _ => return false,
+  };
+  match scope.lookup(keyword) {
+    Ok(Expression::Procedure(Procedure::SpecialFixedArgumentForm(name, _, _)))
+    | Ok(Expression::Procedure(Procedure::SpecialVariableArgumentForm(name, _, _))) => {
+      DEFINING_FORMS.contains(&name)
+    }
+    _ => false,
+  }
+}
+
+/// Write an expression the way it would be written in source code, so that reading it back gives
+/// the same expression
+fn write_source(output: &mut String, expression: &Expression) {
+  match expression {
+    Expression::Null => output.push_str("()"),
+    Expression::Cons(cons) => {
+      output.push('(');
+      let mut cons = cons;
+      loop {
+        write_source(output, &cons.car);
+        match cons.cdr.as_ref() {
+          Expression::Cons(next) => {
+            output.push(' ');
+            cons = next;
+          }
+          Expression::Null => break,
+          cdr => {
+            output.push_str(" . ");
+            write_source(output, cdr);
+            break;
+          }
+        }
+      }
+      output.push(')');
+    }
+    expression => output.push_str(&expression.to_string()),
+  }
+}
+
+/// A description of what a name is bound to, for :describe
+fn describe(name: &str, value: &Expression) -> String {
+  let arguments = |argc: usize, variable: bool| {
+    let plural = if argc == 1 { "" } else { "s" };
+    if variable {
+      format!("at least {} argument{}", argc, plural)
+    } else {
+      format!("{} argument{}", argc, plural)
+    }
+  };
+  let description = match value {
+    Expression::Procedure(procedure) => match procedure {
+      Procedure::FixedArgumentForm(names, _, _) => {
+        format!("a procedure taking ({})", names.join(" "))
+      }
+      Procedure::VariableArgumentForm(names, rest, _, _) => {
+        format!("a procedure taking ({} . {})", names.join(" "), rest)
+      }
+      Procedure::Compiled(lambda, _) => {
+        let names = lambda.arguments.as_ref();
+        match (lambda.variable, names.split_last()) {
+          (true, Some((rest, []))) => format!("a procedure taking {}", rest),
+          (true, Some((rest, names))) => {
+            format!("a procedure taking ({} . {})", names.join(" "), rest)
+          }
+          _ => format!("a procedure taking ({})", names.join(" ")),
+        }
+      }
+      Procedure::BuiltinFixedArgumentForm(_, _, argc) => {
+        format!("a builtin procedure taking {}", arguments(*argc, false))
+      }
+      Procedure::BuiltinVariableArgumentForm(_, _, argc) => {
+        format!("a builtin procedure taking {}", arguments(*argc, true))
+      }
+      Procedure::SpecialFixedArgumentForm(_, _, argc) => {
+        format!("a special form taking {}", arguments(*argc, false))
+      }
+      Procedure::SpecialVariableArgumentForm(_, _, argc) => {
+        format!("a special form taking {}", arguments(*argc, true))
+      }
+      Procedure::Continuation(_) => "a continuation".to_string(),
+      Procedure::Record(_, record_procedure) => format!(
+        "a record procedure taking {}",
+        arguments(record_procedure.argc(), false)
+      ),
+      Procedure::ClosureFixedArgumentForm(_, _, argc) => {
+        format!("a Rust procedure taking {}", arguments(*argc, false))
+      }
+      Procedure::ClosureVariableArgumentForm(_, _, argc) => {
+        format!("a Rust procedure taking {}", arguments(*argc, true))
+      }
+    },
+    Expression::Macro(_) => "a macro".to_string(),
+    value => format!("bound to {}", value),
+  };
+  format!("{} is {}\n", name, description)
+}
+
+impl Session {
+  pub fn new() -> Session {
+    Session::with_scope(Scope::builtins())
+  }
+
+  /// A session which carries on in a scope that may already have things defined in it
+  pub fn with_scope(scope: Rc<RefCell<Scope>>) -> Session {
+    Session {
+      scope,
+      loaded: None,
+      definitions: vec![],
+    }
+  }
+
+  /// Evaluate an expression typed into the REPL, remembering it if it is a definition
+  pub fn evaluate(&mut self, expression: &Expression) -> EvaluationResult {
+    let definition = is_definition(expression, &self.scope.borrow());
+    let value = evaluate(expression, self.scope.clone())?;
+    if definition {
+      self.definitions.push(expression.clone());
+    }
+    Ok(value)
+  }
+
+  /// Evaluate every expression in the arguments of a meta-command
+  fn evaluate_all(&mut self, source: &str) -> EvaluationResult {
+    let expressions =
+      parse_all(source).map_err(|err| EvaluationError::ParseError("input".to_string(), err))?;
+    let mut value = void!();
+    for expression in expressions {
+      value = self.evaluate(&expression)?;
+    }
+    Ok(value)
+  }
+
+  /// Load a file, and return the full path it was found at
+  fn load(&mut self, filename: &str) -> Result<String, EvaluationError> {
+    // Filenames can be given with or without quotes
+    let filename = filename.trim_matches('"');
+    let path = crate::load::resolve(filename)?;
+    let path = path.canonicalize().unwrap_or(path).display().to_string();
+    crate::load::load(&path, self.scope.clone())?;
+    self.loaded = Some(filename.to_string());
+    Ok(path)
+  }
+
+  /// Everything bound in the session's scope which isn't a builtin, or has been redefined
+  fn bindings(&self) -> Vec<(String, Expression)> {
+    let builtins = Scope::builtins();
+    let builtins = builtins.borrow();
+    let scope = self.scope.borrow();
+    scope
+      .names()
+      .into_iter()
+      .filter_map(|name| {
+        let value = scope.lookup(&name).ok()?;
+        match builtins.lookup(&name) {
+          Ok(builtin) if builtin == value => None,
+          _ => Some((name, value)),
+        }
+      })
+      .collect()
+  }
+
+  /// Run a meta-command, which is a line starting with a colon like :load file
+  pub fn run(&mut self, line: &str) -> Result<Next, EvaluationError> {
+    let line = line.trim().trim_start_matches(':');
+    let (command, argument) = match line.split_once(char::is_whitespace) {
+      Some((command, argument)) => (command, argument.trim()),
+      None => (line, ""),
+    };
+    match (command, argument) {
+      ("load", filename) if !filename.is_empty() => {
+        // Loading a file is saved as loading it again, from wherever the saved file is loaded
+        let path = self.load(filename)?;
+        self
+          .definitions
+          .push(list!(symbol!("load"), Expression::String(path)));
+      }
+      ("reload", "") => match self.loaded.clone() {
+        Some(filename) => {
+          self.load(&filename)?;
+        }
+        None => print("Nothing has been loaded yet\n")?,
+      },
+      ("env", "") => {
+        for (name, value) in self.bindings() {
+          print(&format!("{} = {}\n", name, value))?;
+        }
+      }
+      ("describe", name) if !name.is_empty() => {
+        let value = self.scope.borrow().lookup(name)?;
+        print(&describe(name, &value))?;
+      }
+      ("time", source) if !source.is_empty() => {
+        let start = Instant::now();
+        let value = self.evaluate_all(source)?;
+        let elapsed = start.elapsed();
+        if !value.is_empty_result() {
+          print(&format!("{}\n", value.outer_representation()))?;
+        }
+        print(&format!("Elapsed time: {:?}\n", elapsed))?;
+      }
+      ("trace", names) if !names.is_empty() => {
+        for name in names.split_whitespace() {
+          match self.scope.borrow().lookup(name)? {
+            Expression::Procedure(procedure) => trace(name, procedure)?,
+            non_procedure => {
+              return Err(EvaluationError::invalid_argument(
+                "trace",
+                "procedure",
+                &non_procedure,
+              ))
+            }
+          }
+        }
+      }
+      ("untrace", "") => untrace(None),
+      ("untrace", names) => {
+        for name in names.split_whitespace() {
+          untrace(Some(name));
+        }
+      }
+      ("expand", source) if !source.is_empty() => {
+        for expression in
+          parse_all(source).map_err(|err| EvaluationError::ParseError("input".to_string(), err))?
+        {
+          let expansion = evaluate(
+            &list!(symbol!("macroexpand"), list!(symbol!("quote"), expression)),
+            self.scope.clone(),
+          )?;
+          print(&format!("{}\n", expansion))?;
+        }
+      }
+      ("reset", "") => {
+        untrace(None);
+        *self = Session::new();
+      }
+      ("save", filename) if !filename.is_empty() => {
+        let filename = filename.trim_matches('"');
+        let mut contents = String::new();
+        for definition in &self.definitions {
+          write_source(&mut contents, definition);
+          contents.push('\n');
+        }
+        fs::write(filename, contents)
+          .map_err(|err| EvaluationError::FileError(filename.to_string(), err.to_string()))?;
+      }
+      ("quit", "") | ("q", "") => return Ok(Next::Quit),
+      ("help", "") => print(HELP)?,
+      _ => print(&format!("Unknown command :{}, try :help\n", line))?,
+    }
+    Ok(Next::Continue)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::port::{set_current_output_port, Port, PortState};
+  use crate::test::TestFiles;
+
+  /// Run a meta-command, and return what it printed
+  fn run(session: &mut Session, line: &str) -> String {
+    let port = Port::new("test", PortState::OutputBytes(vec![]));
+    let previous = set_current_output_port(port.clone());
+    let result = session.run(line);
+    set_current_output_port(previous);
+    assert_eq!(result, Ok(Next::Continue));
+    String::from_utf8(port.written().unwrap()).unwrap()
+  }
+
+  fn exec(session: &mut Session, source: &str) {
+    for expression in parse_all(source).unwrap() {
+      session.evaluate(&expression).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_env_and_describe() {
+    let mut session = Session::new();
+    exec(
+      &mut session,
+      "(define x \"a\") (define f (lambda (a b) a)) (define car 1)",
+    );
+    assert_eq!(
+      run(&mut session, ":env"),
+      "car = 1\nf = #<procedure>\nx = \"a\"\n"
+    );
+    assert_eq!(
+      run(&mut session, ":describe f"),
+      "f is a procedure taking (a b)\n"
+    );
+    assert_eq!(
+      run(&mut session, ":describe cons"),
+      "cons is a builtin procedure taking 2 arguments\n"
+    );
+    assert_eq!(
+      run(&mut session, ":describe display"),
+      "display is a builtin procedure taking at least 1 argument\n"
+    );
+    assert_eq!(run(&mut session, ":describe x"), "x is bound to \"a\"\n");
+    assert_eq!(
+      session.run(":describe nothing"),
+      Err(EvaluationError::UndefinedSymbol("nothing".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_trace() {
+    let mut session = Session::new();
+    exec(
+      &mut session,
+      "(define fact (lambda (n) (cond ((= n 0) 1) (#t (* n (fact (- n 1)))))))",
+    );
+    run(&mut session, ":trace fact");
+    assert_eq!(
+      run(&mut session, ":time (fact 2)")
+        .lines()
+        .take(7)
+        .collect::<Vec<_>>(),
+      vec![
+        "(fact 2)",
+        " (fact 1)",
+        "  (fact 0)",
+        "  => 1",
+        " => 1",
+        "=> 2",
+        "2"
+      ]
+    );
+    run(&mut session, ":untrace");
+    assert!(run(&mut session, ":time (fact 2)").starts_with("2\nElapsed time: "));
+  }
+
+  #[test]
+  fn test_failed_load() {
+    let files = TestFiles::new(
+      "meta-failed-load",
+      &[
+        ("defs.lisp", "(define y 2)"),
+        ("broken.lisp", "(define x 1) (car"),
+      ],
+    );
+    let mut session = Session::new();
+    assert!(session
+      .run(&format!(":load {}", files.path("missing.lisp")))
+      .is_err());
+    assert_eq!(
+      run(&mut session, ":reload"),
+      "Nothing has been loaded yet\n"
+    );
+    // A file that fails to load doesn't replace the one :reload loads, and isn't saved
+    run(&mut session, &format!(":load {}", files.path("defs.lisp")));
+    assert!(session
+      .run(&format!(":load {}", files.path("broken.lisp")))
+      .is_err());
+    exec(&mut session, "(define y 5)");
+    run(&mut session, ":reload");
+    assert_eq!(session.scope.borrow().lookup("y"), Ok(int!(2)));
+    run(
+      &mut session,
+      &format!(":save {}", files.path("session.lisp")),
+    );
+    let defs = fs::canonicalize(files.path("defs.lisp")).unwrap();
+    assert_eq!(
+      fs::read_to_string(files.path("session.lisp")).unwrap(),
+      format!("(load {:?})\n(define y 5)\n", defs.display().to_string())
+    );
+  }
+
+  #[test]
+  fn test_load_save_and_reset() {
+    let files = TestFiles::new("meta", &[("defs.lisp", "(define y 2)")]);
+    let mut session = Session::new();
+    run(&mut session, &format!(":load {}", files.path("defs.lisp")));
+    assert_eq!(session.scope.borrow().lookup("y"), Ok(int!(2)));
+    exec(
+      &mut session,
+      "(define z (+ y 1)) (+ z 1) (define e '()) (define-values (p q) (values '(1 . 2) \"a\\\"b\"))",
+    );
+    // Only uses of the special forms that define things are definitions
+    exec(
+      &mut session,
+      "(define definitely (lambda () 1)) (definitely) (define-syntax define-zero (syntax-rules () ((_ name) (define name 0))))",
+    );
+    run(
+      &mut session,
+      &format!(":save {}", files.path("session.lisp")),
+    );
+    let defs = fs::canonicalize(files.path("defs.lisp")).unwrap();
+    assert_eq!(
+      fs::read_to_string(files.path("session.lisp")).unwrap(),
+      format!(
+        "(load {:?})
+(define z (+ y 1))
+(define e (quote ()))
+(define-values (p q) (values (quote (1 . 2)) \"a\\\"b\"))
+(define definitely (lambda () 1))
+(define-syntax define-zero (syntax-rules () ((_ name) (define name 0))))
+",
+        defs.display().to_string()
+      )
+    );
+    // Loading the saved file defines everything again
+    run(&mut session, ":reset");
+    assert!(session.scope.borrow().lookup("y").is_err());
+    run(
+      &mut session,
+      &format!(":load {}", files.path("session.lisp")),
+    );
+    for (name, value) in [
+      ("y", int!(2)),
+      ("z", int!(3)),
+      ("e", null!()),
+      ("p", cons!(&int!(1), &int!(2))),
+      ("q", string!("a\"b")),
+    ] {
+      assert_eq!(session.scope.borrow().lookup(name), Ok(value));
+    }
+    run(&mut session, ":reset");
+    assert_eq!(
+      run(&mut session, ":reload"),
+      "Nothing has been loaded yet\n"
+    );
+    exec(
+      &mut session,
+      "(define-syntax swap-args (syntax-rules () ((_ (f a b)) (f b a))))",
+    );
+    assert_eq!(
+      run(&mut session, ":expand (swap-args (- 1 2))"),
+      "(- 2 1)\n"
+    );
+    assert_eq!(
+      run(&mut session, ":nope"),
+      "Unknown command :nope, try :help\n"
+    );
+    assert_eq!(session.run(":quit"), Ok(Next::Quit));
+  }
+}
@@ -1,11 +1,15 @@
 use crate::token::pop_token;
 use crate::*;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ParseError {
   UnexpectedEOF,
   UnexpectedClosingParen,
   IllegalUseOfDot,
+  UnknownCharacterName(String),
+  InvalidBytevector,
+  /// A #lang line names a dialect that programs can't be written in
+  UnknownDialect(String),
 }
 pub type ParseResult = Result<Expression, ParseError>;
 
@@ -30,9 +34,15 @@ pub fn parse_expression(string: &str) -> (ParseResult, String) {
         (Err(err), remainder) => (Err(err), remainder),
         (Ok(quoted_value), remainder) => (Ok(list!(symbol!("quote"), quoted_value)), remainder),
       },
+      token if token.starts_with('"') => (parse_string(token), remainder),
+      token if token.starts_with("#\\") => (parse_char(&token[2..]), remainder),
+      "#u8" => match parse_expression(&remainder) {
+        (Ok(list), remainder) => (parse_bytevector(&list), remainder),
+        (Err(err), remainder) => (Err(err), remainder),
+      },
       token => {
         fn is_digit(c: char) -> bool {
-          c.is_digit(10)
+          c.is_ascii_digit()
         }
         if (token.chars().all(is_digit))
           || (token.starts_with('-') && token.len() > 1 && token.chars().skip(1).all(is_digit))
@@ -52,6 +62,72 @@ pub fn parse_expression(string: &str) -> (ParseResult, String) {
   }
 }
 
+/// Parses a string token, including the quotes
+fn parse_string(token: &str) -> ParseResult {
+  let mut string = String::new();
+  let mut chars = token.chars().skip(1);
+  while let Some(c) = chars.next() {
+    match c {
+      '"' => return Ok(Expression::String(string)),
+      '\\' => match chars.next() {
+        Some('n') => string.push('\n'),
+        Some('t') => string.push('\t'),
+        Some(escaped) => string.push(escaped),
+        None => break,
+      },
+      c => string.push(c),
+    }
+  }
+  Err(ParseError::UnexpectedEOF)
+}
+
+/// Parses the name of a character, from after the #\
+fn parse_char(name: &str) -> ParseResult {
+  let mut chars = name.chars();
+  if let (Some(c), None) = (chars.next(), chars.next()) {
+    return Ok(Expression::Char(c));
+  }
+  let c = match name {
+    "space" => ' ',
+    "newline" => '\n',
+    "tab" => '\t',
+    "return" => '\r',
+    "null" => '\0',
+    "alarm" => '\u{7}',
+    "backspace" => '\u{8}',
+    "delete" => '\u{7f}',
+    "escape" => '\u{1b}',
+    _ => {
+      return name
+        .strip_prefix('x')
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(char::from_u32)
+        .map(Expression::Char)
+        .ok_or_else(|| ParseError::UnknownCharacterName(name.to_string()))
+    }
+  };
+  Ok(Expression::Char(c))
+}
+
+/// Parses the list of bytes after a #u8
+fn parse_bytevector(list: &Expression) -> ParseResult {
+  let mut bytes = vec![];
+  let mut list = list;
+  while let Expression::Cons(cons) = list {
+    match cons.car.as_ref() {
+      Expression::Number(Number::Integer(byte)) if (0..=255).contains(byte) => {
+        bytes.push(*byte as u8)
+      }
+      _ => return Err(ParseError::InvalidBytevector),
+    }
+    list = &cons.cdr;
+  }
+  match list {
+    Expression::Null => Ok(bytevector!(bytes)),
+    _ => Err(ParseError::InvalidBytevector),
+  }
+}
+
 /// Parses a list, starting from the first element
 fn parse_list(string: &str) -> (ParseResult, String) {
   match parse_expression(string) {
@@ -91,10 +167,108 @@ pub fn parse(string: &str) -> ParseResult {
   result
 }
 
+/// Parse every expression in a string, like the contents of a file
+pub fn parse_all(string: &str) -> Result<Vec<Expression>, ParseError> {
+  let mut expressions = vec![];
+  let mut string = string.to_string();
+  while let (Some(_), _) = pop_token(&string) {
+    let (result, remainder) = parse_expression(&string);
+    expressions.push(result?);
+    string = remainder;
+  }
+  Ok(expressions)
+}
+
+/// The dialects a #lang line can name, which are close enough to run programs written in them
+const DIALECTS: &[&str] = &["racket", "scheme"];
+
+/// Skip the lines at the start of a program which aren't part of it: a #! line, so that the file
+/// can be run as a script, and then a #lang line naming its dialect, so that it can be shared
+/// with Racket
+pub fn skip_header(string: &str) -> Result<&str, ParseError> {
+  let mut string = string;
+  if string.starts_with("#!") {
+    string = string.find('\n').map_or("", |end| &string[end..]);
+  }
+  match string.trim_start().strip_prefix("#lang") {
+    Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+      let (dialect, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+      let dialect = dialect.trim();
+      if !DIALECTS.contains(&dialect) {
+        return Err(ParseError::UnknownDialect(dialect.to_string()));
+      }
+      Ok(rest)
+    }
+    _ => Ok(string),
+  }
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
 
+  #[test]
+  fn test_parse_char() {
+    assert_eq!(parse("#\\a"), Ok(Expression::Char('a')));
+    assert_eq!(parse("#\\("), Ok(Expression::Char('(')));
+    assert_eq!(parse("#\\ "), Ok(Expression::Char(' ')));
+    assert_eq!(parse("#\\space"), Ok(Expression::Char(' ')));
+    assert_eq!(parse("#\\newline"), Ok(Expression::Char('\n')));
+    assert_eq!(parse("#\\x41"), Ok(Expression::Char('A')));
+    assert_eq!(parse("#\\x"), Ok(Expression::Char('x')));
+    assert_eq!(
+      parse("(#\\) #\\a)"),
+      Ok(list!(Expression::Char(')'), Expression::Char('a'))),
+    );
+    assert_eq!(
+      parse("#\\nope"),
+      Err(ParseError::UnknownCharacterName("nope".to_string())),
+    );
+  }
+
+  #[test]
+  fn test_parse_bytevector() {
+    assert_eq!(parse("#u8(1 255)"), Ok(bytevector!(vec![1, 255])));
+    assert_eq!(parse("#u8()"), Ok(bytevector!(vec![])));
+    assert_eq!(parse("#u8(256)"), Err(ParseError::InvalidBytevector));
+    assert_eq!(parse("#u8(a)"), Err(ParseError::InvalidBytevector));
+    assert_eq!(parse("#u8 1"), Err(ParseError::InvalidBytevector));
+    assert_eq!(parse("#u8(1"), Err(ParseError::UnexpectedEOF));
+  }
+
+  #[test]
+  fn test_parse_all() {
+    assert_eq!(
+      parse_all("(define x 1) ; comment\n x \"y\""),
+      Ok(vec![
+        list!(symbol!("define"), symbol!("x"), int!(1)),
+        symbol!("x"),
+        string!("y"),
+      ]),
+    );
+    assert_eq!(parse_all("  ; nothing\n"), Ok(vec![]));
+    assert_eq!(parse_all("x (y"), Err(ParseError::UnexpectedEOF));
+  }
+
+  #[test]
+  fn test_skip_header() {
+    assert_eq!(skip_header("(a)"), Ok("(a)"));
+    assert_eq!(skip_header("#!/usr/bin/env rust-lisp\n(a)"), Ok("\n(a)"));
+    assert_eq!(skip_header("#!/usr/bin/env rust-lisp"), Ok(""));
+    assert_eq!(skip_header("#lang racket\n(a)"), Ok("(a)"));
+    assert_eq!(
+      skip_header("#!/bin/rust-lisp\n\n#lang scheme\n(a)"),
+      Ok("(a)")
+    );
+    // Only the first line can be a #! line
+    assert_eq!(skip_header("(a)\n#!b"), Ok("(a)\n#!b"));
+    assert_eq!(skip_header("#language"), Ok("#language"));
+    assert_eq!(
+      skip_header("#lang typed/racket\n(a)"),
+      Err(ParseError::UnknownDialect("typed/racket".to_string())),
+    );
+  }
+
   #[test]
   fn test_parse() {
     assert_eq!(parse("aaa"), Ok(symbol!("aaa")));
@@ -125,6 +299,13 @@ mod test {
       parse("( 1 2 . 3)"),
       Ok(cons!(&int!(1), &cons!(&int!(2), &int!(3)))),
     );
+    assert_eq!(parse("\"a b\""), Ok(string!("a b")));
+    assert_eq!(parse("\"\\\"\\\\\\n\""), Ok(string!("\"\\\n")));
+    assert_eq!(
+      parse("(\"a\" \")\")"),
+      Ok(list!(string!("a"), string!(")")))
+    );
+    assert_eq!(parse("\"a"), Err(ParseError::UnexpectedEOF));
     assert_eq!(parse("( 1 . . 2)"), Err(ParseError::IllegalUseOfDot));
     assert_eq!(parse("( . 2)"), Err(ParseError::IllegalUseOfDot));
   }
@@ -0,0 +1,361 @@
+use crate::evaluate::EvaluationError;
+use crate::parse::{parse_expression, ParseError};
+use crate::token::pop_token;
+use crate::Expression;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::rc::Rc;
+
+/// Where an input port gets its characters or bytes from
+pub struct Input {
+  reader: Box<dyn Read>,
+  /// A byte which has been peeked at but not read
+  peeked: Option<u8>,
+  /// Characters which have been read from the reader but not from the port, like peeked ones
+  pending: VecDeque<char>,
+  /// Whether reading from the reader may have to wait for someone to type something
+  interactive: bool,
+}
+
+impl Input {
+  pub fn new(reader: Box<dyn Read>, interactive: bool) -> Input {
+    Input {
+      reader,
+      peeked: None,
+      pending: VecDeque::new(),
+      interactive,
+    }
+  }
+
+  pub fn read_byte(&mut self) -> io::Result<Option<u8>> {
+    if let Some(byte) = self.peeked.take() {
+      return Ok(Some(byte));
+    }
+    let mut byte = [0];
+    loop {
+      match self.reader.read(&mut byte) {
+        Ok(0) => return Ok(None),
+        Ok(_) => return Ok(Some(byte[0])),
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+        Err(err) => return Err(err),
+      }
+    }
+  }
+
+  pub fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+    if self.peeked.is_none() {
+      self.peeked = self.read_byte()?;
+    }
+    Ok(self.peeked)
+  }
+
+  /// Read up to k bytes, fewer only if the end of the input is reached
+  pub fn read_bytes(&mut self, k: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut bytes = vec![];
+    while bytes.len() < k {
+      match self.read_byte()? {
+        Some(byte) => bytes.push(byte),
+        None => break,
+      }
+    }
+    if bytes.is_empty() && k > 0 {
+      Ok(None)
+    } else {
+      Ok(Some(bytes))
+    }
+  }
+
+  /// Decode the next character from the reader, a byte at a time so that nothing past it is read
+  fn decode_char(&mut self) -> io::Result<Option<char>> {
+    let first = match self.read_byte()? {
+      Some(first) => first,
+      None => return Ok(None),
+    };
+    let length = match first {
+      0x00..=0x7f => 1,
+      0xc0..=0xdf => 2,
+      0xe0..=0xef => 3,
+      0xf0..=0xf7 => 4,
+      _ => 0,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..length {
+      match self.read_byte()? {
+        Some(byte) => bytes.push(byte),
+        None => break,
+      }
+    }
+    std::str::from_utf8(&bytes)
+      .ok()
+      .and_then(|string| string.chars().next())
+      .map(Some)
+      .ok_or_else(|| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          "stream did not contain valid UTF-8",
+        )
+      })
+  }
+
+  pub fn read_char(&mut self) -> io::Result<Option<char>> {
+    match self.pending.pop_front() {
+      Some(c) => Ok(Some(c)),
+      None => self.decode_char(),
+    }
+  }
+
+  pub fn peek_char(&mut self) -> io::Result<Option<char>> {
+    if self.pending.is_empty() {
+      if let Some(c) = self.decode_char()? {
+        self.pending.push_back(c);
+      }
+    }
+    Ok(self.pending.front().copied())
+  }
+
+  /// Read the rest of a line, leaving out the newline at the end of it
+  pub fn read_line(&mut self) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    loop {
+      match self.read_char()? {
+        Some('\n') => return Ok(Some(line)),
+        Some(c) => line.push(c),
+        None if line.is_empty() => return Ok(None),
+        None => return Ok(Some(line)),
+      }
+    }
+  }
+
+  /// Read up to k characters, fewer only if the end of the input is reached
+  pub fn read_string(&mut self, k: usize) -> io::Result<Option<String>> {
+    let mut string = String::new();
+    while string.chars().count() < k {
+      match self.read_char()? {
+        Some(c) => string.push(c),
+        None => break,
+      }
+    }
+    if string.is_empty() && k > 0 {
+      Ok(None)
+    } else {
+      Ok(Some(string))
+    }
+  }
+
+  /// Whether a character or byte can be read without waiting for one to be typed
+  pub fn ready(&self) -> bool {
+    !self.pending.is_empty() || self.peeked.is_some() || !self.interactive
+  }
+}
+
+/// What a port is connected to
+pub enum PortState {
+  /// Writes go to a stream, like stdout or a file
+  Output(Box<dyn Write>),
+  /// Writes are kept in memory
+  OutputBytes(Vec<u8>),
+  Input(Input),
+  Closed,
+}
+
+/// Something that a program reads from or writes to
+pub struct Port {
+  /// What the port is connected to, like stdout or the name of a file
+  pub name: String,
+  /// Whether the port is for bytes rather than characters
+  pub binary: bool,
+  pub state: RefCell<PortState>,
+}
+
+fn file_error(filename: &str, err: io::Error) -> EvaluationError {
+  EvaluationError::FileError(filename.to_string(), err.to_string())
+}
+
+impl Port {
+  pub fn new(name: &str, state: PortState) -> Rc<Port> {
+    Rc::new(Port {
+      name: name.to_string(),
+      binary: false,
+      state: RefCell::new(state),
+    })
+  }
+
+  pub fn new_binary(name: &str, state: PortState) -> Rc<Port> {
+    Rc::new(Port {
+      name: name.to_string(),
+      binary: true,
+      state: RefCell::new(state),
+    })
+  }
+
+  /// Open a file to read from
+  pub fn open_input_file(filename: &str, binary: bool) -> Result<Rc<Port>, EvaluationError> {
+    let file = File::open(filename).map_err(|err| file_error(filename, err))?;
+    let input = Input::new(Box::new(BufReader::new(file)), false);
+    Ok(Rc::new(Port {
+      name: filename.to_string(),
+      binary,
+      state: RefCell::new(PortState::Input(input)),
+    }))
+  }
+
+  /// Open a file to write to, replacing anything that was in it
+  pub fn open_output_file(filename: &str, binary: bool) -> Result<Rc<Port>, EvaluationError> {
+    let file = File::create(filename).map_err(|err| file_error(filename, err))?;
+    Ok(Rc::new(Port {
+      name: filename.to_string(),
+      binary,
+      state: RefCell::new(PortState::Output(Box::new(BufWriter::new(file)))),
+    }))
+  }
+
+  fn error(&self, err: io::Error) -> EvaluationError {
+    EvaluationError::FileError(self.name.clone(), err.to_string())
+  }
+
+  pub fn is_output(&self) -> bool {
+    matches!(
+      &*self.state.borrow(),
+      PortState::Output(_) | PortState::OutputBytes(_)
+    )
+  }
+
+  pub fn is_input(&self) -> bool {
+    matches!(&*self.state.borrow(), PortState::Input(_))
+  }
+
+  /// Do something with the input of an input port
+  pub fn with_input<T>(
+    &self,
+    read: impl FnOnce(&mut Input) -> io::Result<T>,
+  ) -> Result<T, EvaluationError> {
+    match &mut *self.state.borrow_mut() {
+      PortState::Input(input) => read(input).map_err(|err| self.error(err)),
+      _ => Err(self.error(io::Error::other("port is not open for input"))),
+    }
+  }
+
+  /// Read one datum, or None at the end of the input.
+  /// Lines are read until they hold a whole datum, and whatever follows it on the last line is
+  /// left for the next read.
+  pub fn read(&self) -> Result<Option<Expression>, EvaluationError> {
+    let result = self.with_input(|input| {
+      let mut text = String::new();
+      loop {
+        let line = input.read_line()?;
+        let at_end = line.is_none();
+        if let Some(line) = line {
+          text.push_str(&line);
+          text.push('\n');
+        }
+        if let (None, _) = pop_token(&text) {
+          if at_end {
+            return Ok(Ok(None));
+          }
+          continue;
+        }
+        match parse_expression(&text) {
+          (Err(ParseError::UnexpectedEOF), _) if !at_end => continue,
+          (Err(err), _) => return Ok(Err(err)),
+          (Ok(datum), remainder) => {
+            for c in remainder.chars().rev() {
+              input.pending.push_front(c);
+            }
+            return Ok(Ok(Some(datum)));
+          }
+        }
+      }
+    })?;
+    result.map_err(|err| EvaluationError::ParseError(self.name.clone(), err))
+  }
+
+  pub fn write_bytes(&self, bytes: &[u8]) -> Result<(), EvaluationError> {
+    match &mut *self.state.borrow_mut() {
+      PortState::Output(writer) => writer.write_all(bytes).map_err(|err| self.error(err)),
+      PortState::OutputBytes(written) => {
+        written.extend_from_slice(bytes);
+        Ok(())
+      }
+      PortState::Input(_) => Err(self.error(io::Error::other("port is not open for output"))),
+      PortState::Closed => Err(self.error(io::Error::other("port is closed"))),
+    }
+  }
+
+  pub fn write_str(&self, string: &str) -> Result<(), EvaluationError> {
+    self.write_bytes(string.as_bytes())
+  }
+
+  pub fn flush(&self) -> Result<(), EvaluationError> {
+    match &mut *self.state.borrow_mut() {
+      PortState::Output(writer) => writer.flush().map_err(|err| self.error(err)),
+      _ => Ok(()),
+    }
+  }
+
+  /// Everything written to a port that keeps what is written in memory
+  pub fn written(&self) -> Option<Vec<u8>> {
+    match &*self.state.borrow() {
+      PortState::OutputBytes(written) => Some(written.clone()),
+      _ => None,
+    }
+  }
+
+  /// Flush anything still waiting to be written, and stop reading or writing
+  pub fn close(&self) -> Result<(), EvaluationError> {
+    self.flush()?;
+    *self.state.borrow_mut() = PortState::Closed;
+    Ok(())
+  }
+}
+
+// Ports are compared by identity, like the streams they are connected to
+impl PartialEq for Port {
+  fn eq(&self, other: &Port) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Port {}
+
+impl fmt::Display for Port {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#<port {}>", self.name)
+  }
+}
+
+thread_local! {
+  static CURRENT_OUTPUT_PORT: RefCell<Rc<Port>> =
+    RefCell::new(Port::new("stdout", PortState::Output(Box::new(io::stdout()))));
+  static CURRENT_ERROR_PORT: RefCell<Rc<Port>> =
+    RefCell::new(Port::new("stderr", PortState::Output(Box::new(io::stderr()))));
+  // Only a terminal has to wait for someone to type something, and anything else, like a pipe,
+  // is read from like a file is
+  static CURRENT_INPUT_PORT: RefCell<Rc<Port>> = RefCell::new(Port::new(
+    "stdin",
+    PortState::Input(Input::new(Box::new(io::stdin()), io::stdin().is_terminal())),
+  ));
+}
+
+pub fn current_input_port() -> Rc<Port> {
+  CURRENT_INPUT_PORT.with(|port| port.borrow().clone())
+}
+
+pub fn current_output_port() -> Rc<Port> {
+  CURRENT_OUTPUT_PORT.with(|port| port.borrow().clone())
+}
+
+/// Make port the current output port, returning the one it replaces
+pub fn set_current_output_port(port: Rc<Port>) -> Rc<Port> {
+  CURRENT_OUTPUT_PORT.with(|current| current.replace(port))
+}
+
+/// Make port the current input port, returning the one it replaces
+pub fn set_current_input_port(port: Rc<Port>) -> Rc<Port> {
+  CURRENT_INPUT_PORT.with(|current| current.replace(port))
+}
+
+pub fn current_error_port() -> Rc<Port> {
+  CURRENT_ERROR_PORT.with(|port| port.borrow().clone())
+}
@@ -1,4 +1,5 @@
 use crate::evaluate::{EvaluationError, EvaluationResult};
+use crate::library::Libraries;
 use crate::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -6,21 +7,44 @@ use std::rc::Rc;
 
 #[derive(Debug, Default)]
 pub struct Scope {
-  global: Option<Rc<RefCell<Scope>>>,
+  parent: Option<Rc<RefCell<Scope>>>,
   mapping: HashMap<String, Expression>,
+  /// The arguments of a compiled procedure call, which compiled code looks up by their position
+  /// instead of by name
+  argument_names: Rc<Vec<String>>,
+  arguments: Vec<Expression>,
+  /// The libraries that have been defined, which only the outermost scope keeps track of
+  libraries: Option<Libraries>,
 }
+// Scopes are compared by identity, since procedures refer to the scope they were defined in
+impl PartialEq for Scope {
+  fn eq(&self, other: &Scope) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Scope {}
 
 impl Scope {
   pub fn new() -> Scope {
     Scope::default()
   }
+  /// Create a scope that can see all the bindings of its parent, like the scope of a procedure
+  /// call, which can see the bindings where the procedure was defined.
   pub fn child(parent: Rc<RefCell<Scope>>) -> Rc<RefCell<Scope>> {
     let mut scope = Scope::new();
-    scope.global = if let Some(global) = &parent.clone().borrow().global {
-      Some(global.clone())
-    } else {
-      Some(parent)
-    };
+    scope.parent = Some(parent);
+    Rc::new(RefCell::new(scope))
+  }
+  /// Create the scope of a call to a compiled procedure, with the arguments bound in order
+  pub fn with_arguments(
+    parent: Rc<RefCell<Scope>>,
+    argument_names: Rc<Vec<String>>,
+    arguments: Vec<Expression>,
+  ) -> Rc<RefCell<Scope>> {
+    let mut scope = Scope::new();
+    scope.parent = Some(parent);
+    scope.argument_names = argument_names;
+    scope.arguments = arguments;
     Rc::new(RefCell::new(scope))
   }
   pub fn builtins() -> Rc<RefCell<Scope>> {
@@ -28,19 +52,114 @@ impl Scope {
     define_builtins(scope.clone());
     scope
   }
-  pub fn define(&mut self, symbol: &str, expression: Expression) {
-    self.mapping.insert(String::from(symbol), expression);
+  /// A scope with only the builtins with these names
+  pub fn builtins_named(names: &[&str]) -> Rc<RefCell<Scope>> {
+    let scope = Scope::builtins();
+    scope
+      .borrow_mut()
+      .mapping
+      .retain(|name, _| names.contains(&name.as_str()));
+    scope
   }
-  pub fn lookup(&self, symbol: &str) -> EvaluationResult {
-    match self.mapping.get(symbol) {
-      Some(expression) => Ok(expression.clone()),
+  /// The outermost scope that scope is inside of, which is where top level definitions go
+  pub fn root(scope: &Rc<RefCell<Scope>>) -> Rc<RefCell<Scope>> {
+    let mut scope = scope.clone();
+    loop {
+      let parent = match &scope.borrow().parent {
+        Some(parent) => parent.clone(),
+        None => break,
+      };
+      scope = parent;
+    }
+    scope
+  }
+  /// The libraries defined by the program that scope is a part of, which are kept by its
+  /// outermost scope
+  pub fn libraries(scope: &Rc<RefCell<Scope>>) -> Libraries {
+    let root = Scope::root(scope);
+    let mut root = root.borrow_mut();
+    root
+      .libraries
+      .get_or_insert_with(Libraries::default)
+      .clone()
+  }
+  /// Make an outermost scope keep track of the same libraries as another program, like a library
+  /// does of the program that defines it
+  pub fn share_libraries(&mut self, libraries: Libraries) {
+    self.libraries = Some(libraries);
+  }
+  /// The names of everything that can be looked up in this scope, in sorted order
+  pub fn names(&self) -> Vec<String> {
+    let mut names = match &self.parent {
+      Some(parent) => parent.borrow().names(),
+      None => vec![],
+    };
+    names.extend(self.mapping.keys().cloned());
+    names.extend(self.argument_names.iter().cloned());
+    names.sort();
+    names.dedup();
+    names
+  }
+  /// The position of an argument. If an argument name is repeated, the last one wins, just like
+  /// it would if the arguments were defined one at a time.
+  fn argument_index(&self, symbol: &str) -> Option<usize> {
+    self.argument_names.iter().rposition(|name| name == symbol)
+  }
+  pub fn define(&mut self, symbol: &str, expression: Expression) {
+    match self.argument_index(symbol) {
+      Some(index) => self.arguments[index] = expression,
       None => {
-        if let Some(global) = &self.global {
-          global.borrow().lookup(symbol)
-        } else {
-          Err(EvaluationError::UndefinedSymbol(symbol.to_string()))
-        }
+        self.mapping.insert(String::from(symbol), expression);
       }
     }
   }
+  pub fn lookup(&self, symbol: &str) -> EvaluationResult {
+    if let Some(expression) = self.mapping.get(symbol) {
+      return Ok(expression.clone());
+    }
+    if let Some(index) = self.argument_index(symbol) {
+      return Ok(self.arguments[index].clone());
+    }
+    if let Some(parent) = &self.parent {
+      parent.borrow().lookup(symbol)
+    } else {
+      Err(EvaluationError::UndefinedSymbol(symbol.to_string()))
+    }
+  }
+  /// Look up the argument at index in the scope depth scopes up.
+  /// Compiled code can only see arguments, so if a scope along the way has defined the symbol
+  /// since it was compiled, that definition shadows the argument instead.
+  pub fn lookup_argument(&self, depth: usize, index: usize, symbol: &str) -> EvaluationResult {
+    if depth == 0 {
+      return Ok(self.arguments[index].clone());
+    }
+    if let Some(expression) = self.defined(symbol) {
+      return Ok(expression.clone());
+    }
+    match &self.parent {
+      Some(parent) => parent.borrow().lookup_argument(depth - 1, index, symbol),
+      None => unreachable!("compiled code refers to a scope that does not exist"),
+    }
+  }
+  /// Look up a symbol which compiled code knows is not an argument of the first depth scopes
+  pub fn lookup_definition(&self, depth: usize, symbol: &str) -> EvaluationResult {
+    if depth == 0 {
+      return self.lookup(symbol);
+    }
+    if let Some(expression) = self.defined(symbol) {
+      return Ok(expression.clone());
+    }
+    match &self.parent {
+      Some(parent) => parent.borrow().lookup_definition(depth - 1, symbol),
+      None => unreachable!("compiled code refers to a scope that does not exist"),
+    }
+  }
+  fn defined(&self, symbol: &str) -> Option<&Expression> {
+    // Most procedure calls never define anything, so skip hashing the symbol if possible
+    if self.mapping.is_empty() {
+      None
+    } else {
+      self.mapping.get(symbol)
+    }
+  }
 }
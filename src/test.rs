@@ -1,9 +1,16 @@
 // We do this awkward module hoist so we don't have to label everything with #[cfg(test)]
+#[cfg(test)]
+pub use _test::*;
+
 #[cfg(test)]
 mod _test {
     use crate::evaluate::EvaluationError;
+    use crate::parse::parse_all;
+    use crate::port::{set_current_output_port, Port, PortState};
     use crate::*;
     use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
     use std::rc::Rc;
 
     /// A convenient helper for writing more concise tests
@@ -35,8 +42,50 @@ mod _test {
                 Err(error)
             );
         }
+        /// Evaluate every expression in a string, and assert on what they print to the current
+        /// output port
+        pub fn assert_output(&self, string: &str, expected: &str) {
+            let port = Port::new("test", PortState::OutputBytes(vec![]));
+            let previous = set_current_output_port(port.clone());
+            let result = parse_all(string)
+                .unwrap()
+                .iter()
+                .try_for_each(|expression| evaluate(expression, self.scope.clone()).map(|_| ()));
+            set_current_output_port(previous);
+            result.unwrap();
+            assert_eq!(
+                String::from_utf8(port.written().unwrap()).unwrap(),
+                expected
+            );
+        }
     }
-}
 
-#[cfg(test)]
-pub use _test::*;
+    /// A directory of files for a test to load, which is removed when the test is done
+    pub struct TestFiles(PathBuf);
+
+    impl TestFiles {
+        pub fn new(name: &str, files: &[(&str, &str)]) -> TestFiles {
+            let directory = std::env::temp_dir().join(format!(
+                "rust-lisp-{}-{}",
+                name,
+                std::process::id()
+            ));
+            fs::create_dir_all(&directory).unwrap();
+            for (filename, contents) in files {
+                let path = directory.join(filename);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            TestFiles(directory)
+        }
+        pub fn path(&self, filename: &str) -> String {
+            self.0.join(filename).display().to_string()
+        }
+    }
+
+    impl Drop for TestFiles {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+}
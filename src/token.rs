@@ -22,15 +22,36 @@ pub fn pop_token(string: &str) -> (Option<String>, String) {
   if let (Some(first_char), mut remainder) = consume_non_tokens(string) {
     match first_char {
       '(' | ')' | '\'' => (Some(String::from(first_char)), remainder),
+      '"' => {
+        // Strings run until the next unescaped ", whitespace and parens included
+        let mut string = String::from(first_char);
+        let mut chars = remainder.chars();
+        let mut escaped = false;
+        for c in &mut chars {
+          string.push(c);
+          if escaped {
+            escaped = false;
+          } else if c == '\\' {
+            escaped = true;
+          } else if c == '"' {
+            return (Some(string), chars.collect());
+          }
+        }
+        // The string was never closed, which the parser will notice
+        (Some(string), String::new())
+      }
       _ => {
         let mut symbol = String::from(first_char);
         while !remainder.is_empty() {
           let next_char = remainder.chars().next().unwrap();
-          if (next_char == '(') | (next_char == ')') | next_char.is_whitespace() {
+          // The character right after #\ is part of a character literal, even if it is a paren
+          let is_character = symbol == "#\\";
+          if !is_character && ((next_char == '(') | (next_char == ')') | next_char.is_whitespace())
+          {
             return (Some(symbol), remainder);
           }
           symbol.push(next_char);
-          remainder = remainder.get(1..).unwrap().to_string();
+          remainder = remainder[next_char.len_utf8()..].to_string();
         }
         (Some(symbol), remainder)
       }
@@ -112,6 +133,46 @@ mod test {
     );
   }
 
+  #[test]
+  fn test_pop_token_characters() {
+    assert_eq!(
+      pop_token("#\\a b"),
+      (Some(String::from("#\\a")), String::from(" b")),
+    );
+    assert_eq!(
+      pop_token("#\\()"),
+      (Some(String::from("#\\(")), String::from(")")),
+    );
+    assert_eq!(
+      pop_token("#\\ )"),
+      (Some(String::from("#\\ ")), String::from(")")),
+    );
+    assert_eq!(
+      pop_token("#\\space)"),
+      (Some(String::from("#\\space")), String::from(")")),
+    );
+    assert_eq!(
+      pop_token("#\\λ"),
+      (Some(String::from("#\\λ")), String::new()),
+    );
+  }
+
+  #[test]
+  fn test_pop_token_strings() {
+    assert_eq!(
+      pop_token("\"a b\" c"),
+      (Some(String::from("\"a b\"")), String::from(" c")),
+    );
+    assert_eq!(
+      pop_token("\"a(\\\")\")"),
+      (Some(String::from("\"a(\\\")\"")), String::from(")")),
+    );
+    assert_eq!(
+      pop_token("\"a b"),
+      (Some(String::from("\"a b")), String::new()),
+    );
+  }
+
   #[test]
   fn test_pop_token_comments() {
     assert_eq!(pop_token(";"), (None, String::new()));
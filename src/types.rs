@@ -1,10 +1,15 @@
-use crate::evaluate::ProcedureResult;
+use crate::compile::Lambda;
+use crate::evaluate::{Continuation, EvaluationError, EvaluationResult, ProcedureResult};
+use crate::macros::SyntaxRules;
+use crate::port::Port;
 use crate::Scope;
 use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 
-#[derive(Clone, Eq, PartialEq)]
+// Lists can be much longer than the Rust stack is deep, so everything that walks down the cdrs of
+// a list (cloning, comparing, dropping, formatting) does so in a loop instead of recursively.
+#[derive(Eq)]
 pub struct Cons {
   pub car: Box<Expression>,
   pub cdr: Box<Expression>,
@@ -21,24 +26,86 @@ impl Cons {
   /// When rendering the outermost Cons, fmt is called, which writes the opening '('.
   /// The subsequent Cons need to avoid writing the '(' again, hence this alternative method.
   fn fmt_as_inner_element(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    match self.cdr.as_ref().clone() {
-      Expression::Cons(cons) => {
-        // There are more Cons in the chain
-        // Format this car and continue recursing
-        write!(f, "{} ", self.car)?;
-        cons.fmt_as_inner_element(f)?;
-      }
-      Expression::Null => {
-        // We have reached the nil terminator
-        write!(f, "{})", self.car)?;
+    let mut cons = self;
+    loop {
+      match cons.cdr.as_ref() {
+        Expression::Cons(next) => {
+          // There are more Cons in the chain
+          // Format this car and continue on to the next one
+          write_inner(f, &cons.car)?;
+          write!(f, " ")?;
+          cons = next;
+        }
+        Expression::Null => {
+          // We have reached the nil terminator
+          write_inner(f, &cons.car)?;
+          return write!(f, ")");
+        }
+        _ => {
+          // There is no nil terminator, so this isn't actually a list!
+          // Format the final symbol with the special cons cell .
+          write_inner(f, &cons.car)?;
+          write!(f, " . ")?;
+          write_inner(f, &cons.cdr)?;
+          return write!(f, ")");
+        }
       }
-      _ => {
-        // There is no nil terminator, so this isn't actually a list!
-        // Format the final symbol with the special cons cell .
-        write!(f, "{} . {})", self.car, self.cdr)?;
+    }
+  }
+}
+
+impl Clone for Cons {
+  fn clone(&self) -> Cons {
+    let mut cars = vec![];
+    let mut cons = self;
+    let terminator = loop {
+      cars.push(cons.car.as_ref().clone());
+      match cons.cdr.as_ref() {
+        Expression::Cons(next) => cons = next,
+        terminator => break terminator.clone(),
       }
     };
-    Ok(())
+    let mut cdr = terminator;
+    while cars.len() > 1 {
+      let car = cars.pop().unwrap();
+      cdr = Expression::Cons(Cons {
+        car: Box::new(car),
+        cdr: Box::new(cdr),
+      });
+    }
+    Cons {
+      car: Box::new(cars.pop().unwrap()),
+      cdr: Box::new(cdr),
+    }
+  }
+}
+
+impl PartialEq for Cons {
+  fn eq(&self, other: &Cons) -> bool {
+    let (mut left, mut right) = (self, other);
+    loop {
+      if left.car != right.car {
+        return false;
+      }
+      match (left.cdr.as_ref(), right.cdr.as_ref()) {
+        (Expression::Cons(left_next), Expression::Cons(right_next)) => {
+          left = left_next;
+          right = right_next;
+        }
+        (left_terminator, right_terminator) => return left_terminator == right_terminator,
+      }
+    }
+  }
+}
+
+impl Drop for Cons {
+  fn drop(&mut self) {
+    // Detach each cdr before it is dropped so that dropping it doesn't recurse down the list
+    let mut cdr = std::mem::replace(self.cdr.as_mut(), Expression::Null);
+    while let Expression::Cons(cons) = &mut cdr {
+      let next = std::mem::replace(cons.cdr.as_mut(), Expression::Null);
+      cdr = next;
+    }
   }
 }
 
@@ -54,7 +121,116 @@ impl fmt::Debug for Cons {
   }
 }
 
+/// What a promise will give when it is forced
+pub enum PromiseState {
+  /// The value a promise has already been forced to
+  Forced(Expression),
+  /// An expression to evaluate in a scope when the promise is forced, which evaluates to another
+  /// promise if it came from delay-force
+  Delayed(Expression, Rc<RefCell<Scope>>, bool),
+  /// A promise which delay-force has merged into the promise that was waiting on it
+  Forwarded(Rc<Promise>),
+}
+
+/// A value which is only computed when it is first forced
+pub struct Promise(pub RefCell<PromiseState>);
+
+impl Promise {
+  pub fn new(state: PromiseState) -> Rc<Promise> {
+    Rc::new(Promise(RefCell::new(state)))
+  }
+  /// The promise which actually holds the state of this one, after any delay-forces
+  pub fn resolve(promise: &Rc<Promise>) -> Rc<Promise> {
+    let mut promise = promise.clone();
+    loop {
+      let next = match &*promise.0.borrow() {
+        PromiseState::Forwarded(next) => next.clone(),
+        _ => break,
+      };
+      promise = next;
+    }
+    promise
+  }
+}
+
+impl PartialEq for Promise {
+  fn eq(&self, other: &Promise) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Promise {}
+
+/// A type of record made by define-record-type
+pub struct RecordType {
+  pub name: String,
+  pub fields: Vec<String>,
+}
+
+impl PartialEq for RecordType {
+  fn eq(&self, other: &RecordType) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for RecordType {}
+
+/// An instance of a record type
+pub struct Record {
+  pub record_type: Rc<RecordType>,
+  pub fields: RefCell<Vec<Expression>>,
+}
+
+impl PartialEq for Record {
+  fn eq(&self, other: &Record) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+impl Eq for Record {}
+
+impl fmt::Display for Record {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#<{}", self.record_type.name)?;
+    for field in self.fields.borrow().iter() {
+      write!(f, " ")?;
+      write_inner(f, field)?;
+    }
+    write!(f, ">")
+  }
+}
+
+/// The procedures that define-record-type makes for a record type
+#[derive(Clone, Eq, PartialEq)]
+pub enum RecordProcedure {
+  /// Make a record, taking the values of the fields at these indices in order
+  Constructor(Rc<RecordType>, Rc<Vec<usize>>),
+  Predicate(Rc<RecordType>),
+  Accessor(Rc<RecordType>, usize),
+  Modifier(Rc<RecordType>, usize),
+}
+impl RecordProcedure {
+  pub fn argc(&self) -> usize {
+    match self {
+      RecordProcedure::Constructor(_, fields) => fields.len(),
+      RecordProcedure::Predicate(_) => 1,
+      RecordProcedure::Accessor(_, _) => 1,
+      RecordProcedure::Modifier(_, _) => 2,
+    }
+  }
+}
+
+/// A Rust closure that a program embedding the interpreter defines as a procedure, which is
+/// given the values of its arguments. Unlike a builtin, it can capture state.
+/// Closures are only equal to themselves.
+#[derive(Clone)]
+pub struct BuiltinClosure(pub Rc<dyn Fn(Vec<Expression>) -> EvaluationResult>);
+impl PartialEq for BuiltinClosure {
+  fn eq(&self, other: &BuiltinClosure) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
+impl Eq for BuiltinClosure {}
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[non_exhaustive]
 pub enum Number {
   Integer(i32),
 }
@@ -72,41 +248,93 @@ impl fmt::Debug for Number {
   }
 }
 
+/// Something that can be called. Programs embedding the interpreter can only make closures, and
+/// every other kind of procedure is made and looked inside of by the interpreter alone.
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Procedure {
-  FixedArgumentForm(Vec<String>, Vec<Expression>),
-  VariableArgumentForm(Vec<String>, String, Vec<Expression>),
+  /// A lambda, along with the scope it was defined in
+  #[doc(hidden)]
+  FixedArgumentForm(Vec<String>, Vec<Expression>, Rc<RefCell<Scope>>),
+  #[doc(hidden)]
+  VariableArgumentForm(Vec<String>, String, Vec<Expression>, Rc<RefCell<Scope>>),
+  /// A compiled lambda, along with the scope it was defined in
+  #[doc(hidden)]
+  Compiled(Rc<Lambda>, Rc<RefCell<Scope>>),
+  /// A builtin procedure, which is given the values of its arguments
+  #[doc(hidden)]
   BuiltinFixedArgumentForm(
     &'static str,
     fn(Vec<Expression>, Rc<RefCell<Scope>>) -> ProcedureResult,
     usize,
   ),
+  #[doc(hidden)]
   #[allow(clippy::type_complexity)]
   BuiltinVariableArgumentForm(
     &'static str,
     fn(Vec<Expression>, Vec<Expression>, Rc<RefCell<Scope>>) -> ProcedureResult,
     usize,
   ),
+  /// A builtin special form like define or quote, which is given its arguments unevaluated
+  #[doc(hidden)]
+  SpecialFixedArgumentForm(
+    &'static str,
+    fn(Vec<Expression>, Rc<RefCell<Scope>>) -> ProcedureResult,
+    usize,
+  ),
+  #[doc(hidden)]
+  #[allow(clippy::type_complexity)]
+  SpecialVariableArgumentForm(
+    &'static str,
+    fn(Vec<Expression>, Vec<Expression>, Rc<RefCell<Scope>>) -> ProcedureResult,
+    usize,
+  ),
+  #[doc(hidden)]
+  Continuation(Rc<Continuation>),
+  /// A procedure made by define-record-type, along with its name
+  #[doc(hidden)]
+  Record(String, RecordProcedure),
+  /// A Rust closure, along with its name and how many arguments it takes
+  ClosureFixedArgumentForm(String, BuiltinClosure, usize),
+  /// A Rust closure, along with its name and the least number of arguments it takes
+  ClosureVariableArgumentForm(String, BuiltinClosure, usize),
 }
 impl Procedure {
   pub fn name(&self) -> String {
     match self {
-      Procedure::FixedArgumentForm(_, _) => "#<procedure>".to_string(),
-      Procedure::VariableArgumentForm(_, _, _) => "#<procedure>".to_string(),
+      Procedure::FixedArgumentForm(_, _, _) => "#<procedure>".to_string(),
+      Procedure::VariableArgumentForm(_, _, _, _) => "#<procedure>".to_string(),
+      Procedure::Compiled(_, _) => "#<procedure>".to_string(),
       Procedure::BuiltinFixedArgumentForm(procedure_name, _, _) => procedure_name.to_string(),
       Procedure::BuiltinVariableArgumentForm(procedure_name, _, _) => procedure_name.to_string(),
+      Procedure::SpecialFixedArgumentForm(procedure_name, _, _) => procedure_name.to_string(),
+      Procedure::SpecialVariableArgumentForm(procedure_name, _, _) => procedure_name.to_string(),
+      Procedure::Continuation(_) => "#<continuation>".to_string(),
+      Procedure::Record(procedure_name, _)
+      | Procedure::ClosureFixedArgumentForm(procedure_name, _, _)
+      | Procedure::ClosureVariableArgumentForm(procedure_name, _, _) => procedure_name.clone(),
     }
   }
 }
 impl fmt::Display for Procedure {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Procedure::FixedArgumentForm(_, _) => write!(f, "#<procedure>"),
-      Procedure::VariableArgumentForm(_, _, _) => write!(f, "#<procedure>"),
-      Procedure::BuiltinFixedArgumentForm(procedure_name, _, _) => {
+      Procedure::FixedArgumentForm(_, _, _) => write!(f, "#<procedure>"),
+      Procedure::VariableArgumentForm(_, _, _, _) => write!(f, "#<procedure>"),
+      Procedure::Compiled(_, _) => write!(f, "#<procedure>"),
+      Procedure::BuiltinFixedArgumentForm(procedure_name, _, _)
+      | Procedure::SpecialFixedArgumentForm(procedure_name, _, _) => {
         write!(f, "#<procedure:{}>", procedure_name)
       }
-      Procedure::BuiltinVariableArgumentForm(procedure_name, _, _) => {
+      Procedure::BuiltinVariableArgumentForm(procedure_name, _, _)
+      | Procedure::SpecialVariableArgumentForm(procedure_name, _, _) => {
+        write!(f, "#<procedure:{}>", procedure_name)
+      }
+      Procedure::Continuation(_) => write!(f, "#<continuation>"),
+      Procedure::Record(procedure_name, _)
+      | Procedure::ClosureFixedArgumentForm(procedure_name, _, _)
+      | Procedure::ClosureVariableArgumentForm(procedure_name, _, _) => {
         write!(f, "#<procedure:{}>", procedure_name)
       }
     }
@@ -118,13 +346,36 @@ impl fmt::Debug for Procedure {
   }
 }
 
+/// A Lisp value. The kinds of values that only the interpreter can make or look inside of, like
+/// macros and ports, are left out of the documentation.
 #[derive(Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Expression {
   Symbol(String),
+  String(String),
+  Char(char),
   Cons(Cons),
   Number(Number),
   Boolean(bool),
   Procedure(Procedure),
+  #[doc(hidden)]
+  Macro(Rc<SyntaxRules>),
+  /// An error object, which is what is raised when an error occurs
+  Error(Rc<EvaluationError>),
+  /// Any number of values other than one, which is just the value itself
+  Values(Vec<Expression>),
+  #[doc(hidden)]
+  Promise(Rc<Promise>),
+  #[doc(hidden)]
+  Record(Rc<Record>),
+  /// A scope which can be passed around, to evaluate expressions in with eval
+  Environment(Rc<RefCell<Scope>>),
+  /// A sequence of bytes, which can be changed in place
+  Bytevector(Rc<RefCell<Vec<u8>>>),
+  #[doc(hidden)]
+  Port(Rc<Port>),
+  /// What reading from a port gives once there is nothing left to read
+  Eof,
   Null,
   Void,
 }
@@ -133,7 +384,12 @@ impl fmt::Display for Expression {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
       Expression::Symbol(symbol) => write!(f, "{}", symbol),
-      Expression::Cons(cons) => write!(f, "{}", cons),
+      // Displaying a string or a character leaves out the syntax it would be written with
+      Expression::String(string) if f.alternate() => write!(f, "{}", string),
+      Expression::String(string) => write_string(f, string),
+      Expression::Char(c) if f.alternate() => write!(f, "{}", c),
+      Expression::Char(c) => write_char(f, *c),
+      Expression::Cons(cons) => fmt::Display::fmt(cons, f),
       Expression::Number(number) => write!(f, "{}", number),
       Expression::Boolean(boolean) => {
         if *boolean {
@@ -143,11 +399,74 @@ impl fmt::Display for Expression {
         }
       }
       Expression::Procedure(procedure) => write!(f, "{}", procedure),
+      Expression::Macro(_) => write!(f, "#<macro>"),
+      Expression::Error(err) => write!(f, "#<error {}>", err),
+      Expression::Values(values) => {
+        for (index, value) in values.iter().enumerate() {
+          if index > 0 {
+            write!(f, " ")?;
+          }
+          write_inner(f, value)?;
+        }
+        Ok(())
+      }
+      Expression::Promise(_) => write!(f, "#<promise>"),
+      Expression::Record(record) => fmt::Display::fmt(record.as_ref(), f),
+      Expression::Environment(_) => write!(f, "#<environment>"),
+      Expression::Bytevector(bytes) => {
+        write!(f, "#u8(")?;
+        for (index, byte) in bytes.borrow().iter().enumerate() {
+          if index > 0 {
+            write!(f, " ")?;
+          }
+          write!(f, "{}", byte)?;
+        }
+        write!(f, ")")
+      }
+      Expression::Port(port) => write!(f, "{}", port),
+      Expression::Eof => write!(f, "#<eof>"),
       Expression::Null => write!(f, "'()"),
       Expression::Void => write!(f, "#<void>"),
     }
   }
 }
+/// Write an expression that is part of another one, displaying it if the other one is being
+/// displayed rather than written
+fn write_inner(f: &mut fmt::Formatter, expression: &Expression) -> fmt::Result {
+  if f.alternate() {
+    write!(f, "{:#}", expression)
+  } else {
+    write!(f, "{}", expression)
+  }
+}
+
+/// Write a string the way it would be written in source code
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in string.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\t' => write!(f, "\\t")?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+/// Write a character the way it would be written in source code
+fn write_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+  match c {
+    ' ' => write!(f, "#\\space"),
+    '\n' => write!(f, "#\\newline"),
+    '\t' => write!(f, "#\\tab"),
+    '\r' => write!(f, "#\\return"),
+    '\0' => write!(f, "#\\null"),
+    c => write!(f, "#\\{}", c),
+  }
+}
+
 impl fmt::Debug for Expression {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     write!(f, "{}", self)
@@ -155,9 +474,20 @@ impl fmt::Debug for Expression {
 }
 
 impl Expression {
+  /// Whether there is nothing to show for the result of an evaluation
+  pub fn is_empty_result(&self) -> bool {
+    match self {
+      Expression::Void => true,
+      Expression::Values(values) => values.is_empty(),
+      _ => false,
+    }
+  }
+
   pub fn outer_representation(&self) -> String {
     match self {
       Expression::Symbol(symbol) => format!("'{}", symbol),
+      Expression::String(_) => format!("{}", self),
+      Expression::Char(_) => format!("{}", self),
       Expression::Cons(cons) => format!("'{}", cons),
       Expression::Number(number) => format!("{}", number),
       Expression::Boolean(boolean) => {
@@ -168,6 +498,20 @@ impl Expression {
         }
       }
       Expression::Procedure(procedure) => format!("{}", procedure),
+      Expression::Macro(_) => "#<macro>".to_string(),
+      Expression::Error(err) => format!("#<error {}>", err),
+      // Each value goes on its own line
+      Expression::Values(values) => values
+        .iter()
+        .map(|value| value.outer_representation())
+        .collect::<Vec<String>>()
+        .join("\n"),
+      Expression::Promise(_) => "#<promise>".to_string(),
+      Expression::Record(record) => format!("{}", record),
+      Expression::Environment(_) => "#<environment>".to_string(),
+      Expression::Bytevector(_) => format!("{}", self),
+      Expression::Port(port) => format!("{}", port),
+      Expression::Eof => "#<eof>".to_string(),
       Expression::Null => "'()".to_string(),
       Expression::Void => "#<void>".to_string(),
     }
@@ -177,65 +521,81 @@ impl Expression {
 #[macro_export]
 macro_rules! symbol {
   ($symbol:expr) => {
-    Expression::Symbol(String::from($symbol))
+    $crate::Expression::Symbol(String::from($symbol))
+  };
+}
+
+#[macro_export]
+macro_rules! bytevector {
+  ($bytes:expr) => {
+    $crate::Expression::Bytevector(std::rc::Rc::new(std::cell::RefCell::new($bytes)))
+  };
+}
+
+#[macro_export]
+macro_rules! string {
+  ($string:expr) => {
+    $crate::Expression::String(String::from($string))
   };
 }
 
 #[macro_export]
 macro_rules! cons {
   ($left:expr, $right:expr) => {
-    Expression::Cons(Cons::new($left, $right))
+    $crate::Expression::Cons($crate::Cons::new($left, $right))
   };
 }
 
 #[macro_export]
 macro_rules! list {
     () => {
-        null!()
+        $crate::null!()
     };
     ($car:expr) => {
-        cons!(&$car, &null!())
+        $crate::cons!(&$car, &$crate::null!())
     };
     ($car:expr, $($cdr:expr),*) => {
-        cons!(&$car, &list!($($cdr),*))
+        $crate::cons!(&$car, &$crate::list!($($cdr),*))
     };
 }
 
 #[macro_export]
 macro_rules! int {
   ($number:expr) => {
-    Expression::Number(Number::Integer($number))
+    $crate::Expression::Number($crate::Number::Integer($number))
   };
 }
 
 #[macro_export]
 macro_rules! boolean {
   ($boolean:expr) => {
-    Expression::Boolean($boolean)
+    $crate::Expression::Boolean($boolean)
   };
 }
 
 #[macro_export]
 macro_rules! procedure {
-  ($arg:expr, $body:expr) => {
-    Expression::Procedure(Procedure::FixedArgumentForm($arg, $body))
+  ($arg:expr, $body:expr, $scope:expr) => {
+    $crate::Expression::Procedure($crate::Procedure::FixedArgumentForm($arg, $body, $scope))
   };
-  ($arg:expr , $vararg:expr, $body:expr) => {
-    Expression::Procedure(Procedure::VariableArgumentForm($arg, $vararg, $body))
+  ($arg:expr , $vararg:expr, $body:expr, $scope:expr) => {
+    $crate::Expression::Procedure($crate::Procedure::VariableArgumentForm(
+      $arg, $vararg, $body, $scope,
+    ))
   };
 }
 
 #[macro_export]
 macro_rules! null {
   () => {
-    Expression::Null
+    $crate::Expression::Null
   };
 }
 
 #[macro_export]
 macro_rules! void {
   () => {
-    Expression::Void
+    $crate::Expression::Void
   };
 }
 
@@ -256,6 +616,22 @@ mod test {
     assert_expr_eq!(symbol!("foo"), "foo", "'foo");
   }
 
+  #[test]
+  fn test_fmt_char() {
+    assert_expr_eq!(Expression::Char('a'), "#\\a", "#\\a");
+    assert_expr_eq!(Expression::Char(' '), "#\\space", "#\\space");
+    assert_expr_eq!(Expression::Char('\n'), "#\\newline", "#\\newline");
+  }
+
+  #[test]
+  fn test_fmt_display() {
+    // Displaying leaves out the quotes of strings and characters, even inside of lists
+    let list = list!(string!("a \"b\""), Expression::Char('c'), int!(1));
+    assert_eq!(format!("{:#}", list), "(a \"b\" c 1)");
+    assert_eq!(format!("{}", list), "(\"a \\\"b\\\"\" #\\c 1)");
+    assert_eq!(format!("{:#}", cons!(&int!(1), &string!("x"))), "(1 . x)");
+  }
+
   #[test]
   fn test_fmt_cons() {
     assert_expr_eq!(cons!(&int!(1), &null!()), "(1)", "'(1)");
@@ -263,6 +639,21 @@ mod test {
     assert_expr_eq!(list!(&int!(1), &int!(2), &int!(3)), "(1 2 3)", "'(1 2 3)");
   }
 
+  #[test]
+  fn test_long_list() {
+    // None of these should overflow the stack
+    let mut list = null!();
+    for i in 0..1_000_000 {
+      list = Expression::Cons(Cons {
+        car: Box::new(int!(i)),
+        cdr: Box::new(list),
+      });
+    }
+    let copy = list.clone();
+    assert_eq!(list, copy);
+    assert!(format!("{}", list).starts_with("(999999 999998 "));
+  }
+
   #[test]
   fn test_fmt_number() {
     assert_expr_eq!(int!(1), "1", "1");
@@ -278,6 +669,12 @@ mod test {
     assert_expr_eq!(boolean!(false), "#f", "#f");
   }
 
+  #[test]
+  fn test_fmt_string() {
+    assert_expr_eq!(string!("a b"), "\"a b\"", "\"a b\"");
+    assert_expr_eq!(string!("\"\\\n"), "\"\\\"\\\\\\n\"", "\"\\\"\\\\\\n\"");
+  }
+
   #[test]
   fn test_fmt_null() {
     assert_expr_eq!(null!(), "'()", "'()");
@@ -0,0 +1,382 @@
+use crate::compile::{compile, Code, Combination, Instruction, Lambda};
+use crate::evaluate::*;
+use crate::macros::SyntaxRules;
+use crate::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A call to compiled code which is in progress
+#[derive(Clone)]
+pub struct Execution {
+  code: Rc<Code>,
+  /// The next instruction to execute
+  pc: usize,
+  scope: Rc<RefCell<Scope>>,
+  values: Vec<Expression>,
+}
+impl fmt::Debug for Execution {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#<execution {:?}>", self.code.instructions.get(self.pc))
+  }
+}
+impl Execution {
+  pub fn new(code: Rc<Code>, scope: Rc<RefCell<Scope>>) -> Execution {
+    Execution {
+      code,
+      pc: 0,
+      scope,
+      values: vec![],
+    }
+  }
+  /// Pass a value to the code
+  pub fn push(&mut self, value: Expression) {
+    self.values.push(value);
+  }
+}
+
+/// Create the scope for a call to a compiled lambda
+pub fn bind_arguments(
+  lambda: &Lambda,
+  scope: Rc<RefCell<Scope>>,
+  mut args: Vec<Expression>,
+) -> Result<Rc<RefCell<Scope>>, EvaluationError> {
+  if lambda.variable {
+    let varargs = args.split_off(lambda.argc());
+    args.push(vec_arg(varargs)?);
+  }
+  Ok(Scope::with_arguments(scope, lambda.arguments.clone(), args))
+}
+
+/// The compiled code for the expansion of a combination whose operator is a macro
+fn expand(
+  combination: &Combination,
+  syntax_rules: Rc<SyntaxRules>,
+  code: &Code,
+  scope: &Rc<RefCell<Scope>>,
+) -> Result<Rc<Code>, EvaluationError> {
+  if let Some((expanded_by, expansion)) = combination.expansion.borrow().as_ref() {
+    if Rc::ptr_eq(expanded_by, &syntax_rules) {
+      return Ok(expansion.clone());
+    }
+  }
+  let expansion = syntax_rules.expand(&combination.form)?;
+  let expansion = Rc::new(compile(&expansion, &code.environment, scope));
+  *combination.expansion.borrow_mut() = Some((syntax_rules, expansion.clone()));
+  Ok(expansion)
+}
+
+fn operands(combination: &Combination) -> &Expression {
+  match &combination.form {
+    Expression::Cons(cons) => &cons.cdr,
+    _ => unreachable!("combinations are always lists"),
+  }
+}
+
+impl Machine {
+  /// Execute compiled code until it returns a value to a frame which isn't compiled code, or
+  /// until something needs to be done by the rest of the machine, like calling a builtin's
+  /// continuation.
+  pub fn execute(&mut self, mut execution: Execution) -> ProcedureResult {
+    loop {
+      let instruction = execution.code.instructions[execution.pc];
+      execution.pc += 1;
+      let returned = match instruction {
+        Instruction::Constant(index) => {
+          let constant = execution.code.constants[index].clone();
+          execution.values.push(constant);
+          None
+        }
+        Instruction::Argument(depth, index, symbol) => {
+          let value = execution.scope.borrow().lookup_argument(
+            depth,
+            index,
+            &execution.code.symbols[symbol],
+          )?;
+          execution.values.push(value);
+          None
+        }
+        Instruction::Definition(depth, symbol) => {
+          let value = execution
+            .scope
+            .borrow()
+            .lookup_definition(depth, &execution.code.symbols[symbol])?;
+          execution.values.push(value);
+          None
+        }
+        Instruction::Define(symbol) => {
+          let value = execution.values.pop().unwrap();
+          execution
+            .scope
+            .borrow_mut()
+            .define(&execution.code.symbols[symbol], value);
+          execution.values.push(void!());
+          None
+        }
+        Instruction::Lambda(index) => {
+          let lambda = execution.code.lambdas[index].clone();
+          let procedure = Procedure::Compiled(lambda, execution.scope.clone());
+          execution.values.push(Expression::Procedure(procedure));
+          None
+        }
+        Instruction::JumpIfFalse(target) => {
+          if execution.values.pop().unwrap() == boolean!(false) {
+            execution.pc = target;
+          }
+          None
+        }
+        Instruction::JumpIfTrue(target) => {
+          if execution.values.last().unwrap() != &boolean!(false) {
+            execution.pc = target;
+          } else {
+            execution.values.pop();
+          }
+          None
+        }
+        Instruction::Jump(target) => {
+          execution.pc = target;
+          None
+        }
+        Instruction::Pop => {
+          execution.values.pop();
+          None
+        }
+        Instruction::Operator(index) => {
+          let code = execution.code.clone();
+          let combination = &code.combinations[index];
+          match execution.values.pop().unwrap() {
+            // Special forms get their operands without evaluating them
+            Expression::Procedure(
+              procedure @ Procedure::SpecialFixedArgumentForm(_, _, _)
+              | procedure @ Procedure::SpecialVariableArgumentForm(_, _, _),
+            ) => {
+              let operands = arg_vec(&procedure.name(), operands(combination))?;
+              check_arity(&procedure, operands.len())?;
+              execution.pc = combination.after;
+              match self.apply(procedure, operands, execution.scope.clone())? {
+                ProcedureValue::Expression(value) => {
+                  execution.values.push(value);
+                  None
+                }
+                procedure_value => {
+                  if !combination.tail {
+                    self.push(Frame::Execute(execution))?;
+                  }
+                  return Ok(procedure_value);
+                }
+              }
+            }
+            // Macro uses are replaced by their expansion, which is then executed in their place
+            Expression::Macro(syntax_rules) => {
+              let expansion = expand(combination, syntax_rules, &code, &execution.scope)?;
+              let expansion = Execution::new(expansion, execution.scope.clone());
+              execution.pc = combination.after;
+              if !combination.tail {
+                self.push(Frame::Execute(execution))?;
+              }
+              execution = expansion;
+              None
+            }
+            Expression::Procedure(procedure) => {
+              match combination.argc {
+                Some(argc) => check_arity(&procedure, argc)?,
+                None => {
+                  arg_vec(&procedure.name(), operands(combination))?;
+                }
+              }
+              // Leave the procedure for the call
+              execution.values.push(Expression::Procedure(procedure));
+              None
+            }
+            non_procedure => return Err(EvaluationError::NotAProcedure(Box::new(non_procedure))),
+          }
+        }
+        Instruction::Call(argc) | Instruction::TailCall(argc) => {
+          let tail = matches!(instruction, Instruction::TailCall(_));
+          let args = execution.values.split_off(execution.values.len() - argc);
+          let procedure = match execution.values.pop() {
+            Some(Expression::Procedure(procedure)) => procedure,
+            _ => unreachable!("the operator instruction makes sure there is a procedure"),
+          };
+          match procedure {
+            // Traced procedures print what they return, so they have to return it through the
+            // stack like a procedure that doesn't return right away
+            procedure if traced_name(&procedure).is_some() => {
+              let scope = execution.scope.clone();
+              if !tail {
+                self.push(Frame::Execute(execution))?;
+              }
+              return self.apply(procedure, args, scope);
+            }
+            // The operator instruction has already checked the arity
+            // Loops are calls which never leave compiled code, so this is where they are stopped
+            Procedure::Compiled(lambda, procedure_scope) => {
+              self.check_interrupt()?;
+              let scope = bind_arguments(&lambda, procedure_scope, args)?;
+              if !tail {
+                self.push(Frame::Execute(execution))?;
+              }
+              execution = Execution::new(lambda.code.clone(), scope);
+              None
+            }
+            // Calling a continuation abandons this execution entirely
+            Procedure::Continuation(_) => {
+              return self.apply(procedure, args, execution.scope.clone())
+            }
+            procedure => match self.apply(procedure, args, execution.scope.clone())? {
+              ProcedureValue::Expression(value) if tail => Some(value),
+              ProcedureValue::Expression(value) => {
+                execution.values.push(value);
+                None
+              }
+              procedure_value => {
+                if !tail {
+                  self.push(Frame::Execute(execution))?;
+                }
+                return Ok(procedure_value);
+              }
+            },
+          }
+        }
+        Instruction::Return => Some(execution.values.pop().unwrap()),
+      };
+      if let Some(value) = returned {
+        match self.stack.pop() {
+          Some(Frame::Execute(caller)) => {
+            execution = caller;
+            execution.values.push(value);
+          }
+          Some(frame) => return self.resume(frame, value),
+          None => return Ok(ProcedureValue::Expression(value)),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::evaluate::interpret;
+  use crate::parse::parse_expression;
+  use crate::*;
+
+  /// Run the program with both the compiler and the tree walker, and make sure that every line
+  /// has the same outcome
+  fn assert_same(program: &str) {
+    let compiled_scope = Scope::builtins();
+    let interpreted_scope = Scope::builtins();
+    let mut program = program.to_string();
+    while !program.trim().is_empty() {
+      let (expression, remainder) = parse_expression(&program);
+      let expression = expression.unwrap();
+      program = remainder;
+      let compiled = evaluate(&expression, compiled_scope.clone());
+      let interpreted = interpret(&expression, interpreted_scope.clone());
+      assert_eq!(
+        format!("{:?}", compiled),
+        format!("{:?}", interpreted),
+        "{}",
+        expression
+      );
+    }
+  }
+
+  #[test]
+  fn test_same_as_interpreter() {
+    assert_same(
+      "
+(define fib (lambda (n) (cond ((< n 2) n) (else (+ (fib (- n 1)) (fib (- n 2)))))))
+(fib 15)
+(define make-counter (lambda (n) (lambda () (define n (+ n 1)) n)))
+(define counter (make-counter 5))
+(counter)
+(counter)
+(define list (lambda items items))
+(list 1 2 3)
+((lambda (a . rest) (cons rest a)) 1 2 3)
+((lambda (x x) x) 1 2)
+(cond (#f) (2))
+(cond (#f 1) (3 => 4))
+(cond (#f 1))
+(cond (else 1 2))
+((lambda (x) ((lambda () (define x 2) x))) 1)
+((lambda (x) ((lambda () (define y x) (define x 2) (+ x y)))) 1)
+((lambda (quote) (quote 5)) (lambda (x) (+ x 1)))
+(define q quote)
+(q (a b))
+(define l lambda)
+((l (x) (* x x)) 4)
+(define-syntax swap-args (syntax-rules () ((_ (f a b)) (f b a))))
+(swap-args (- 1 10))
+(define apply-twice (lambda (f x) (swap-args (f x (f x 0)))))
+(apply-twice - 3)
+(let-syntax ((foo (syntax-rules () ((_ a) (* a 2))))) (foo 21))
+",
+    );
+  }
+
+  #[test]
+  fn test_same_errors_as_interpreter() {
+    assert_same(
+      "
+(undefined)
+(1 2 3)
+((lambda (x) x))
+((lambda (x) x) 1 (car 1))
+(+ . 1)
+(car . 1)
+(quote)
+(quote 1 2)
+(define)
+(define 1 2)
+(lambda)
+(lambda (1) 1)
+(lambda x)
+(cond 1)
+(cond ())
+(cond (else))
+(cond (#f 1) (else))
+((lambda (f) (f)) car)
+(define-syntax foo (syntax-rules () ((_ a) a)))
+(foo)
+",
+    );
+  }
+
+  #[test]
+  fn test_same_continuations_as_interpreter() {
+    assert_same(
+      "
+(define r (call/cc (lambda (k) (cons 0 k))))
+(cond ((< (car r) 3) ((cdr r) (cons (+ (car r) 1) (cdr r)))) (else (car r)))
+(car r)
+(define count-up (lambda (n)
+  (define k (call/cc (lambda (k) (cons 0 k))))
+  (cond
+    ((eq? (car k) n) (car k))
+    (else ((cdr k) (cons (+ (car k) 1) (cdr k)))))))
+(count-up 10)
+(+ 1 (call/cc (lambda (k) (+ 10 (k 1)))))
+(dynamic-wind (lambda () 1) (lambda () 2) (lambda () 3))
+(call/cc (lambda (k) (dynamic-wind (lambda () 1) (lambda () (k 2)) (lambda () 3))))
+(dynamic-wind (lambda () 1) (lambda () (car 1)) (lambda () 3))
+(guard (e (#t (error-object-message e))) (dynamic-wind (lambda () 1) (lambda () (car 1)) (lambda () 3)))
+(guard (e ((eq? e 'a) 1)) (raise 'b))
+(guard (e (#f 1)) (error \"boom\" 1 2))
+(with-exception-handler (lambda (e) (+ e 1)) (lambda () (+ 10 (raise-continuable 1))))
+(with-exception-handler (lambda (e) 0) (lambda () (/ 1 0)))
+(call-with-values (lambda () (call/cc (lambda (k) (k 1 2)))) cons)
+(let-values (((q r) (floor/ -7 2)) (all (values 1 2))) (cons q (cons r all)))
+(let*-values (((a) 1) ((b) (+ a 1))) (receive (c . d) (values a b) (cons c d)))
+(define-values (x y) (truncate/ -7 2))
+(cons x y)
+(let-values (((a b) (values 1))) a)
+",
+    );
+  }
+
+  #[test]
+  fn test_same_mergesort_as_interpreter() {
+    assert_same(&std::fs::read_to_string("test/src/mergesort.lisp").unwrap());
+  }
+}
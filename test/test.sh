@@ -5,9 +5,13 @@ mkdir -p target/racket/src/
 mkdir -p target/rust_lisp/
 
 test_racket () {
-    # Racket files must start with #lang racket
-    echo "#lang racket\n" > target/racket/src/$1
-    cat src/$1 >> target/racket/src/$1
+    # Racket files must start with #lang racket, which rust-lisp skips over
+    if head -n 1 src/$1 | grep -q '^#lang'; then
+        cp src/$1 target/racket/src/$1
+    else
+        echo "#lang racket\n" > target/racket/src/$1
+        cat src/$1 >> target/racket/src/$1
+    fi
     # echo "racket target/racket/src/$1 > target/racket/$1"
     racket target/racket/src/$1 > target/racket/$1
 }
@@ -15,7 +19,9 @@ test_racket () {
 test_rust_lisp () {
     cd ..
     cargo run -- test/src/$1 > test/target/rust_lisp/$1 2>/dev/null
+    STATUS=$?
     cd test
+    return $STATUS
 }
 
 test_file () {